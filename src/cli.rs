@@ -0,0 +1,281 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::models::object::Object;
+use crate::models::{gltf, mesh, obj};
+
+use crate::render::backend;
+use crate::render::headless::Headless;
+use crate::render::projection::{DepthMode, Mode};
+use crate::render::scene::{self, Scene};
+use crate::render::screenshot;
+use crate::render::software::Rasterizer;
+use crate::render::transform::Transform;
+use crate::render::vec3d::Vec3d;
+use crate::settings::Settings;
+use crate::world::generation::Generator;
+use crate::world::graph::Node;
+use crate::world::map::{Map, CHUNK_SIZE, MIN_Y, SECTIONS};
+use crate::world::world::World;
+
+const USAGE: &str = "usage: cubegame check-model <file.obj|file.gltf|file.glb>
+       cubegame bench-generation [--seed SEED] [--radius CHUNKS]
+       cubegame screenshot <output.png> [--size WIDTHxHEIGHT] [--position X,Y,Z] [--direction X,Y,Z] [--fov DEGREES] [--orthographic HEIGHT] [--reversed-z] [--teapots N]
+           [--settings FILE] [--software] [--seed SEED | --save FILE]";
+
+/// Runs a command given on the command line, returns false if there was none
+pub fn run(args: &[String]) -> bool {
+    let Some(command) = args.first() else {
+        return false;
+    };
+
+    let result = match command.as_str() {
+        "screenshot" => screenshot(&args[1..]),
+        "check-model" => check_model(&args[1..]),
+        "bench-generation" => bench_generation(&args[1..]),
+        _ => Err(format!("unknown command `{}`", command)),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+    true
+}
+
+/// Renders a world without any window and writes the image: the one of the save, or the one
+/// generated from the seed (0 by default). The camera starts at the spawn
+fn screenshot(args: &[String]) -> Result<(), String> {
+    let mut output: Option<PathBuf> = None;
+    let mut size = (800, 600);
+    let mut software = false;
+    let mut world = World::new();
+    // before the other options, which move the camera from the spawn
+    match (find_option(args, "--seed"), find_option(args, "--save")) {
+        (Some(_), Some(_)) => return Err("--seed and --save cannot be used together".to_string()),
+        (None, Some(path)) => world.load(Path::new(path)).map_err(|err| err.to_string())?,
+        (seed, None) => world.generate(seed.map_or(Ok(0), |seed| seed.parse().map_err(|_| "invalid seed".to_string()))?),
+    }
+    world.spawn();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => size = parse_size(value(&mut args, arg)?)?,
+            "--position" => {
+                let position = parse_vec3d(value(&mut args, arg)?)?;
+                world.camera.position = [position.x, position.y, position.z];
+            },
+            "--direction" => {
+                let direction = parse_vec3d(value(&mut args, arg)?)?;
+                world.camera.direction = direction.to_quaternion().normalize();
+            },
+            "--fov" => {
+                let fov = parse_f32(value(&mut args, arg)?)?;
+                world.camera.projection.set_fov(fov.to_radians());
+            },
+            "--orthographic" => {
+                world.camera.projection.mode = Mode::Orthographic;
+                world.camera.projection.height = parse_f32(value(&mut args, arg)?)?;
+            },
+            "--reversed-z" => world.camera.projection.depth = DepthMode::Reversed,
+            "--teapots" => {
+                let count: usize = value(&mut args, arg)?.parse().map_err(|_| "invalid number of teapots".to_string())?;
+                let groups = obj::load(Path::new(crate::TEAPOT)).map_err(|err| err.to_string())?;
+                let objects: Vec<Object> = groups.into_iter().map(|group| group.object).collect();
+                let teapot = world.add_model(mesh::merge(&objects).map_err(|err| err.to_string())?);
+                // a row on top of the floor, moved as a whole with its parent
+                let row = world.graph.add(Node::new(Transform::from_translation([0.5, 0.15, 2.5])), None);
+                for i in 0..count {
+                    world.place(teapot, [0.5 * i as f32, 0.0, 0.0], Some(row));
+                }
+            },
+            "--settings" => {
+                let path = Path::new(value(&mut args, arg)?);
+                world.settings = Settings::load(path).map_err(|err| err.to_string())?;
+            },
+            "--software" => software = true,
+            "--seed" | "--save" => {
+                value(&mut args, arg)?;
+            },
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg)),
+            _ => output = Some(PathBuf::from(arg)),
+        }
+    }
+    let output = output.ok_or("missing output file")?;
+
+    let (image, stats) = if software {
+        let mut rasterizer = Rasterizer::new(size);
+        let stats = backend::draw_world(&mut rasterizer, &mut world);
+        (rasterizer.into_image(), stats)
+    } else {
+        let headless = Headless::new(size).map_err(|err| format!("could not create an OpenGL context: {}", err))?;
+        if world.camera.projection.depth == DepthMode::Reversed && !scene::supports_reversed_depth(&headless) {
+            return Err("--reversed-z needs OpenGL 4.5 or ARB_clip_control".to_string());
+        }
+        let scene = Scene::new(&headless);
+        screenshot::capture(&headless, &scene, &mut world, size).map_err(|err| format!("could not draw: {}", err))?
+    };
+    screenshot::save(&image, &output).map_err(|err| format!("could not write {}: {}", output.display(), err))?;
+
+    println!("Chunks drawn: {}, culled: {}, meshed: {}, instances: {}, culled: {}",
+        stats.chunks_drawn, stats.chunks_culled, stats.chunks_meshed, stats.instances_drawn, stats.instances_culled);
+    println!("Screenshot written to {}", output.display());
+    Ok(())
+}
+
+/// Times the generation of the chunks of the columns around the origin, the terrain alone then
+/// with the features. The features are added to whole columns, their time is shared by the chunks
+fn bench_generation(args: &[String]) -> Result<(), String> {
+    let mut seed = 0;
+    let mut radius = 3;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = value(&mut args, arg)?.parse().map_err(|_| "invalid seed".to_string())?,
+            "--radius" => radius = value(&mut args, arg)?.parse().map_err(|_| "invalid radius".to_string())?,
+            _ => return Err(format!("unknown option `{}`", arg)),
+        }
+    }
+    let mut columns = vec![];
+    for x in -radius..=radius {
+        for z in -radius..=radius {
+            columns.push([x, z]);
+        }
+    }
+
+    let bottom = MIN_Y.div_euclid(CHUNK_SIZE);
+    let generator = Generator::new(seed);
+    let terrain: Vec<Duration> = columns.iter()
+        .flat_map(|[x, z]| (bottom..bottom + SECTIONS).map(move |y| [*x, y, *z]))
+        .map(|coordinates| {
+            let start = Instant::now();
+            generator.terrain_chunk(coordinates);
+            start.elapsed()
+        })
+        .collect();
+
+    let mut generator = Generator::new(seed);
+    let mut map = Map::from_columns(vec![]);
+    let full: Vec<Duration> = columns.iter()
+        .flat_map(|coordinates| {
+            let start = Instant::now();
+            generator.generate(&mut map, *coordinates);
+            std::iter::repeat_n(start.elapsed() / SECTIONS as u32, SECTIONS as usize)
+        })
+        .collect();
+
+    println!("{} columns of {} chunks, seed {}, times per chunk", columns.len(), SECTIONS, seed);
+    for (name, times) in [("terrain and caves", terrain), ("with features", full)] {
+        let total: Duration = times.iter().sum();
+        let min = times.iter().min().unwrap();
+        let max = times.iter().max().unwrap();
+        println!("{:<18} mean {:>8.2?}  min {:>8.2?}  max {:>8.2?}  total {:>8.2?}", name, total / times.len() as u32, min, max, total);
+    }
+    Ok(())
+}
+
+/// Loads a model file and prints what it contains
+fn check_model(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err("expected a single model file".to_string());
+    };
+    let path = Path::new(path);
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("obj") => check_obj(path),
+        Some("gltf" | "glb") => check_gltf(path),
+        _ => Err(format!("unknown model format for {}", path.display())),
+    }
+}
+
+fn check_obj(path: &Path) -> Result<(), String> {
+    let groups = obj::load(path).map_err(|err| err.to_string())?;
+
+    for group in &groups {
+        let material = group.material.as_ref().map_or("none", |material| material.name.as_str());
+        println!("{}: {} vertices, {} triangles, {} normals, {} texture coordinates, material {}",
+            group.name, group.object.vertices.len(), group.object.indices.len() / 3,
+            group.object.normals.len(), group.object.tex_coords.len(), material);
+    }
+
+    let objects: Vec<Object> = groups.into_iter().map(|group| group.object).collect();
+    let merged = mesh::merge(&objects).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if let Some((min, max)) = merged.bounding_box() {
+        println!("total: {} vertices, {} triangles, bounds {:?} to {:?}",
+            merged.vertices.len(), merged.indices.len() / 3, min, max);
+    }
+    Ok(())
+}
+
+fn check_gltf(path: &Path) -> Result<(), String> {
+    let model = gltf::load(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    fn print_node(model: &gltf::Model, index: usize, depth: usize) {
+        let node = &model.nodes[index];
+        let translation = &node.transform[3][..3];
+        println!("{}node {} {} at {:?}{}{}", "  ".repeat(depth), index, node.name.as_deref().unwrap_or(""), translation,
+            node.mesh.map_or(String::new(), |mesh| format!(", mesh {}", mesh)),
+            node.skin.map_or(String::new(), |skin| format!(", skin {}", skin)));
+        for &child in &node.children {
+            print_node(model, child, depth + 1);
+        }
+    }
+    for &root in &model.roots {
+        print_node(&model, root, 0);
+    }
+
+    for (index, mesh) in model.meshes.iter().enumerate() {
+        for primitive in &mesh.primitives {
+            println!("mesh {} {}: {} vertices, {} triangles, {} skinned vertices, material {:?}",
+                index, mesh.name.as_deref().unwrap_or(""), primitive.object.vertices.len(),
+                primitive.object.indices.len() / 3, primitive.joints.len().min(primitive.weights.len()),
+                primitive.material);
+        }
+    }
+    for (index, skin) in model.skins.iter().enumerate() {
+        println!("skin {} {}: joints {:?}, {} inverse bind matrices", index, skin.name.as_deref().unwrap_or(""),
+            skin.joints, skin.inverse_bind_matrices.len());
+    }
+    for (index, material) in model.materials.iter().enumerate() {
+        println!("material {} {}: base color {:?}, texture {:?}", index, material.name.as_deref().unwrap_or(""),
+            material.base_color, material.base_color_texture);
+    }
+    for (index, image) in model.images.iter().enumerate() {
+        println!("image {}: {}x{}", index, image.width(), image.height());
+    }
+    Ok(())
+}
+
+fn value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> Result<&'a str, String> {
+    args.next().map(|value| value.as_str()).ok_or(format!("missing value for `{}`", option))
+}
+
+/// Value of an option anywhere in the arguments
+fn find_option<'a>(args: &'a [String], option: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == option).and_then(|index| args.get(index + 1)).map(|value| value.as_str())
+}
+
+/// Neither side can be 0, there would be nothing to draw on
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value.split_once('x').ok_or(format!("invalid size `{}`", value))?;
+    let width = width.parse().ok().filter(|&width| width > 0).ok_or(format!("invalid width `{}`", width))?;
+    let height = height.parse().ok().filter(|&height| height > 0).ok_or(format!("invalid height `{}`", height))?;
+    Ok((width, height))
+}
+
+fn parse_f32(value: &str) -> Result<f32, String> {
+    value.parse().map_err(|_| format!("invalid number `{}`", value))
+}
+
+fn parse_vec3d(value: &str) -> Result<Vec3d, String> {
+    let coords = value.split(',')
+        .map(|coord| coord.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|_| format!("invalid vector `{}`", value))?;
+    match coords[..] {
+        [x, y, z] => Ok(Vec3d::new(x, y, z)),
+        _ => Err(format!("expected 3 coordinates in `{}`", value)),
+    }
+}
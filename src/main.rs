@@ -1,13 +1,34 @@
 #[macro_use]
 extern crate glium;
 
+mod cli;
 mod models;
 mod render;
+mod settings;
 mod world;
 
-use world::world::World;
+use render::renderer::Renderer;
+use render::screenshot;
+use world::world::{Action, World};
+
+use std::path::{Path, PathBuf};
+
+const TEAPOT: &str = "assets/models/teapot.obj";
+/// Atlas of the texts on the screen, DejaVu Sans Mono in cells of 8x16 pixels
+const FONT: &str = "assets/fonts/font.png";
+/// Optional, the defaults are used without it
+const SETTINGS: &str = "settings.cfg";
+/// Written with F5, loaded at start if it exists
+const SAVE: &str = "world.save";
+/// Optional, foreign blocks of the schematics mapped to ours on top of the default mapping
+const MAPPING: &str = "blocks.mapping";
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::run(&args) {
+        return;
+    }
+
     let event_loop = glium::winit::event_loop::EventLoop::builder()
         .build()
         .expect("event loop building");
@@ -16,10 +37,69 @@ fn main() {
         .with_title("Cubegame")
         .build(&event_loop);
 
-    let mut world = World::new(display, window);
+    let mut world = World::new();
+    if Path::new(SETTINGS).exists() {
+        match settings::Settings::load(Path::new(SETTINGS)) {
+            Ok(settings) => world.settings = settings,
+            Err(err) => println!("Could not load the settings: {}", err),
+        }
+    }
+    world.save_path = Some(PathBuf::from(SAVE));
+    world.mapping_path = Some(PathBuf::from(MAPPING));
+    let loaded = Path::new(SAVE).exists() && match world.load(Path::new(SAVE)) {
+        Ok(()) => true,
+        Err(err) => {
+            println!("Could not load the map: {}", err);
+            false
+        },
+    };
+    if !loaded {
+        let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        println!("Generating the map with the seed {}", seed);
+        world.generate(seed);
+    }
+    world.spawn();
+    match models::obj::load(Path::new(TEAPOT)) {
+        Ok(groups) => {
+            let objects: Vec<_> = groups.into_iter().map(|group| group.object).collect();
+            match models::mesh::merge(&objects) {
+                Ok(teapot) => {
+                    world.add_model(teapot);
+                },
+                Err(err) => println!("Could not load the teapot: {}", err),
+            }
+        },
+        Err(err) => println!("Could not load the teapot: {}", err),
+    }
+    match render::text::Font::load(Path::new(FONT)) {
+        Ok(font) => world.font = Some(font),
+        Err(err) => println!("Could not load the font: {}", err),
+    }
+    let renderer = Renderer::new(display, window);
+    world.reversed_depth = renderer.supports_reversed_depth();
 
     #[allow(deprecated)]
     event_loop.run(move |ev, window_target| {
-        world.run(ev, window_target);
+        match world.run(ev) {
+            Some(Action::Exit) => window_target.exit(),
+            Some(Action::Render) => {
+                if let Err(err) = renderer.render(&mut world) {
+                    println!("Could not draw the frame: {}", err);
+                }
+            },
+            Some(Action::RequestRedraw) => renderer.request_redraw(),
+            Some(Action::Resize(window_size)) => renderer.resize(window_size),
+            Some(Action::Screenshot) => {
+                let path = screenshot::timestamped_path();
+                match renderer.screenshot(&mut world) {
+                    Ok((image, _)) => match screenshot::save(&image, &path) {
+                        Ok(()) => println!("Screenshot saved to {}", path.display()),
+                        Err(err) => println!("Could not save screenshot: {}", err),
+                    },
+                    Err(err) => println!("Could not draw the screenshot: {}", err),
+                }
+            },
+            None => (),
+        }
     }).unwrap();
-}
\ No newline at end of file
+}
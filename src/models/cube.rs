@@ -1,57 +1,31 @@
-use crate::models::object::Vertex;
-use crate::models::object::Normal;
-
-use super::object::Object;
-
-pub const SIZE: f32 = 10.0;
-
-fn vertices(pos: [f32; 3]) -> [Vertex; 8] {
-    [
-        Vertex { position: ( SIZE+pos[0],  SIZE+pos[1],  SIZE+pos[2]) },
-        Vertex { position: ( SIZE+pos[0],  SIZE+pos[1], -SIZE+pos[2]) },
-        Vertex { position: ( SIZE+pos[0], -SIZE+pos[1],  SIZE+pos[2]) },
-        Vertex { position: ( SIZE+pos[0], -SIZE+pos[1], -SIZE+pos[2]) },
-        Vertex { position: (-SIZE+pos[0],  SIZE+pos[1],  SIZE+pos[2]) },
-        Vertex { position: (-SIZE+pos[0],  SIZE+pos[1], -SIZE+pos[2]) },
-        Vertex { position: (-SIZE+pos[0], -SIZE+pos[1],  SIZE+pos[2]) },
-        Vertex { position: (-SIZE+pos[0], -SIZE+pos[1], -SIZE+pos[2]) },
-    ]
-}
-
-pub const NORMALS: [Normal; 12] = [
-    Normal { normal: ( 0.0,  0.0,  1.0) },
-    Normal { normal: ( 0.0,  0.0, -1.0) },
-    Normal { normal: ( 1.0,  0.0,  0.0) },
-    Normal { normal: (-1.0,  0.0,  0.0) },
-    Normal { normal: ( 0.0,  1.0,  0.0) },
-    Normal { normal: ( 0.0, -1.0,  0.0) },
-    Normal { normal: ( 1.0,  1.0,  1.0) },
-    Normal { normal: ( 1.0,  1.0, -1.0) },
-    Normal { normal: ( 1.0, -1.0,  1.0) },
-    Normal { normal: ( 1.0, -1.0, -1.0) },
-    Normal { normal: (-1.0,  1.0,  1.0) },
-    Normal { normal: (-1.0,  1.0, -1.0) },
-];
-
-pub const INDICES: [u16; 3*12] = [
-    0, 1, 2,
-    1, 2, 3,
-    0, 1, 4,
-    1, 4, 5,
-    4, 5, 6,
-    5, 6, 7,
-    2, 3, 6,
-    3, 6, 7,
-    0, 2, 4,
-    2, 4, 6,
-    1, 3, 5,
-    3, 5, 7,
-];
-
-pub fn new(pos: [f32; 3]) -> Object {
-    Object::new(
-        vertices(pos).to_vec(),
-        NORMALS.to_vec(),
-        INDICES.to_vec()
-    )
-}
\ No newline at end of file
+use crate::models::mesh::{MeshBuilder, MeshError};
+
+pub const SIZE: f32 = 10.0;
+
+/// Outward direction and corners of each face of the cube, counter-clockwise seen from outside
+pub const FACES: [([i64; 3], [[f32; 3]; 4]); 6] = [
+    ([ 1,  0,  0], [[ 1.0, -1.0,  1.0], [ 1.0, -1.0, -1.0], [ 1.0,  1.0, -1.0], [ 1.0,  1.0,  1.0]]),
+    ([-1,  0,  0], [[-1.0, -1.0, -1.0], [-1.0, -1.0,  1.0], [-1.0,  1.0,  1.0], [-1.0,  1.0, -1.0]]),
+    ([ 0,  1,  0], [[-1.0,  1.0,  1.0], [ 1.0,  1.0,  1.0], [ 1.0,  1.0, -1.0], [-1.0,  1.0, -1.0]]),
+    ([ 0, -1,  0], [[-1.0, -1.0, -1.0], [ 1.0, -1.0, -1.0], [ 1.0, -1.0,  1.0], [-1.0, -1.0,  1.0]]),
+    ([ 0,  0,  1], [[-1.0, -1.0,  1.0], [ 1.0, -1.0,  1.0], [ 1.0,  1.0,  1.0], [-1.0,  1.0,  1.0]]),
+    ([ 0,  0, -1], [[ 1.0, -1.0, -1.0], [-1.0, -1.0, -1.0], [-1.0,  1.0, -1.0], [ 1.0,  1.0, -1.0]]),
+];
+
+const TEX_COORDS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+/// Adds one face (index in `FACES`) of the cube centered on `pos`
+pub fn add_face(builder: &mut MeshBuilder, pos: [f32; 3], face: usize) -> Result<(), MeshError> {
+    add_box_face(builder, pos, [1.0; 3], face)
+}
+
+/// Same as `add_face` for a box, `half` is its size along each axis relative to the cube
+pub fn add_box_face(builder: &mut MeshBuilder, pos: [f32; 3], half: [f32; 3], face: usize) -> Result<(), MeshError> {
+    let (_, corners) = FACES[face];
+    let corners = corners.map(|corner| [
+        corner[0] * half[0] * SIZE + pos[0],
+        corner[1] * half[1] * SIZE + pos[1],
+        corner[2] * half[2] * SIZE + pos[2],
+    ]);
+    builder.quad(corners, TEX_COORDS)
+}
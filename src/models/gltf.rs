@@ -0,0 +1,300 @@
+//! glTF 2.0 (`.gltf` / `.glb`) loader
+
+use std::fmt;
+use std::path::Path;
+
+use image::RgbaImage;
+
+use crate::models::mesh::MeshError;
+use crate::models::object::{Normal, Object, TexCoord, Vertex};
+use crate::render::matrix::{Matrix, IDENTITY};
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    /// Index in `Model::images`
+    pub base_color_texture: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Primitive {
+    pub object: Object,
+    /// Index in `Model::materials`
+    pub material: Option<usize>,
+    /// Indices of the joints in the skin of the node using the mesh, 4 per vertex
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<[f32; 4]>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: Option<String>,
+    /// Relative to the parent node
+    pub transform: Matrix,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub skin: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Skin {
+    pub name: Option<String>,
+    /// Indices in `Model::nodes`
+    pub joints: Vec<usize>,
+    /// One per joint, from model space to the space of the joint
+    pub inverse_bind_matrices: Vec<Matrix>,
+}
+
+/// Everything needed to draw and animate a glTF scene. Nodes, meshes, skins, materials
+/// and images reference each other by their index in the vectors
+#[derive(Clone, Debug)]
+pub struct Model {
+    /// Nodes at the top of the hierarchy of the scene
+    pub roots: Vec<usize>,
+    pub nodes: Vec<Node>,
+    pub meshes: Vec<Mesh>,
+    pub skins: Vec<Skin>,
+    pub materials: Vec<Material>,
+    pub images: Vec<RgbaImage>,
+}
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import(::gltf::Error),
+    MissingPositions { mesh: usize },
+    UnsupportedPrimitive { mesh: usize, mode: ::gltf::mesh::Mode },
+    UnsupportedImage { image: usize, format: ::gltf::image::Format },
+    Mesh { mesh: usize, error: MeshError },
+    InvalidIndex { mesh: usize, index: u32, vertices: usize },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Import(error) => write!(f, "{}", error),
+            GltfError::MissingPositions { mesh } => write!(f, "mesh {} has a primitive without positions", mesh),
+            GltfError::UnsupportedPrimitive { mesh, mode } => write!(f, "mesh {} uses {:?}, only triangles are supported", mesh, mode),
+            GltfError::UnsupportedImage { image, format } => write!(f, "image {} uses the unsupported format {:?}", image, format),
+            GltfError::Mesh { mesh, error } => write!(f, "mesh {}: {}", mesh, error),
+            GltfError::InvalidIndex { mesh, index, vertices } => write!(f, "mesh {} uses the vertex {} of {}", mesh, index, vertices),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+impl From<::gltf::Error> for GltfError {
+    fn from(error: ::gltf::Error) -> GltfError {
+        GltfError::Import(error)
+    }
+}
+
+pub fn load(path: &Path) -> Result<Model, GltfError> {
+    let (document, buffers, images) = ::gltf::import(path)?;
+
+    let nodes = document.nodes()
+        .map(|node| Node {
+            name: node.name().map(str::to_string),
+            transform: node.transform().matrix(),
+            children: node.children().map(|child| child.index()).collect(),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            skin: node.skin().map(|skin| skin.index()),
+        })
+        .collect();
+
+    let roots = document.default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.nodes().map(|node| node.index()).collect())
+        .unwrap_or_default();
+
+    let mut meshes = vec![];
+    for mesh in document.meshes() {
+        let mut primitives = vec![];
+        for primitive in mesh.primitives() {
+            primitives.push(load_primitive(mesh.index(), &primitive, &buffers)?);
+        }
+        meshes.push(Mesh {
+            name: mesh.name().map(str::to_string),
+            primitives,
+        });
+    }
+
+    let skins = document.skins()
+        .map(|skin| {
+            let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+            let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+            // without matrices, every joint is at the origin in bind pose
+            let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+                Some(matrices) => matrices.collect(),
+                None => vec![IDENTITY; joints.len()],
+            };
+            Skin {
+                name: skin.name().map(str::to_string),
+                joints,
+                inverse_bind_matrices,
+            }
+        })
+        .collect();
+
+    let materials = document.materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            Material {
+                name: material.name().map(str::to_string),
+                base_color: pbr.base_color_factor(),
+                base_color_texture: pbr.base_color_texture().map(|info| info.texture().source().index()),
+            }
+        })
+        .collect();
+
+    let images = images.into_iter()
+        .enumerate()
+        .map(|(index, image)| to_rgba(index, image))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Model {
+        roots,
+        nodes,
+        meshes,
+        skins,
+        materials,
+        images,
+    })
+}
+
+fn load_primitive(mesh: usize, primitive: &::gltf::Primitive, buffers: &[::gltf::buffer::Data]) -> Result<Primitive, GltfError> {
+    if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+        return Err(GltfError::UnsupportedPrimitive { mesh, mode: primitive.mode() });
+    }
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let vertices: Vec<Vertex> = reader.read_positions()
+        .ok_or(GltfError::MissingPositions { mesh })?
+        .map(|[x, y, z]| Vertex { position: (x, y, z) })
+        .collect();
+    let normals: Option<Vec<Normal>> = reader.read_normals()
+        .map(|normals| normals.map(|[x, y, z]| Normal { normal: (x, y, z) }).collect());
+    let tex_coords = reader.read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().map(|[u, v]| TexCoord { tex_coords: (u, v) }).collect())
+        .unwrap_or_default();
+
+    // primitives without indices are plain lists of triangles
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertices.len()) {
+        return Err(GltfError::InvalidIndex { mesh, index, vertices: vertices.len() });
+    }
+
+    let joints: Vec<[u16; 4]> = reader.read_joints(0)
+        .map(|joints| joints.into_u16().collect())
+        .unwrap_or_default();
+    let weights: Vec<[f32; 4]> = reader.read_weights(0)
+        .map(|weights| weights.into_f32().collect())
+        .unwrap_or_default();
+
+    let (object, joints, weights) = match normals {
+        Some(normals) => (Object::new(vertices, normals, indices).with_tex_coords(tex_coords), joints, weights),
+        // the specification asks for flat normals when there are none, which gives
+        // each corner of each triangle its own vertex
+        None => {
+            let (joints, weights) = (unweld(&joints, &indices), unweld(&weights, &indices));
+            let object = Object::new(vertices, vec![], indices).with_tex_coords(tex_coords).with_flat_normals()
+                .map_err(|error| GltfError::Mesh { mesh, error })?;
+            (object, joints, weights)
+        }
+    };
+
+    Ok(Primitive {
+        object,
+        material: primitive.material().index(),
+        joints,
+        weights,
+    })
+}
+
+/// Attribute of each corner of each triangle
+fn unweld<T: Copy>(attribute: &[T], indices: &[u32]) -> Vec<T> {
+    if attribute.is_empty() {
+        return vec![];
+    }
+    indices.iter().map(|&index| attribute[index as usize]).collect()
+}
+
+fn to_rgba(index: usize, image: ::gltf::image::Data) -> Result<RgbaImage, GltfError> {
+    use ::gltf::image::Format;
+
+    let pixels: Vec<u8> = match image.format {
+        Format::R8G8B8A8 => image.pixels,
+        Format::R8G8B8 => image.pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        Format::R8G8 => image.pixels.chunks_exact(2).flat_map(|p| [p[0], p[1], 0, 255]).collect(),
+        Format::R8 => image.pixels.iter().flat_map(|&r| [r, r, r, 255]).collect(),
+        format => return Err(GltfError::UnsupportedImage { image: index, format }),
+    };
+    // the size always matches the format, the decoder made the data
+    Ok(RgbaImage::from_raw(image.width, image.height, pixels).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(extension: &str) -> Model {
+        load(Path::new(&format!("assets/models/samples/skinned_strip.{}", extension))).unwrap()
+    }
+
+    #[test]
+    fn loads_the_skinned_sample() {
+        for extension in ["gltf", "glb"] {
+            let model = sample(extension);
+            assert_eq!(model.roots, [0]);
+            let names: Vec<_> = model.nodes.iter().map(|node| node.name.as_deref().unwrap()).collect();
+            assert_eq!(names, ["Armature", "root_bone", "tip_bone", "Strip"]);
+            assert_eq!(model.nodes[0].children, [1, 3]);
+            assert_eq!(model.nodes[2].transform[3][..3], [0.0, 1.0, 0.0]);
+            assert_eq!((model.nodes[3].mesh, model.nodes[3].skin), (Some(0), Some(0)));
+
+            let primitive = &model.meshes[0].primitives[0];
+            assert_eq!(primitive.object.vertices.len(), 6);
+            assert_eq!(primitive.object.indices.len(), 12);
+            assert_eq!(primitive.object.tex_coords.len(), 6);
+            assert_eq!(primitive.joints.len(), 6);
+            assert!(primitive.weights.iter().all(|weights| (weights.iter().sum::<f32>() - 1.0).abs() < 1e-5));
+
+            assert_eq!(model.skins[0].joints, [1, 2]);
+            assert_eq!(model.skins[0].inverse_bind_matrices.len(), 2);
+            assert_eq!(model.materials[0].base_color_texture, Some(0));
+            assert_eq!(model.images.len(), 1);
+        }
+    }
+
+    #[test]
+    fn reads_the_same_model_from_both_formats() {
+        let (text, binary) = (sample("gltf"), sample("glb"));
+        let positions = |model: &Model| -> Vec<(f32, f32, f32)> {
+            model.meshes[0].primitives[0].object.vertices.iter().map(|vertex| vertex.position).collect()
+        };
+        assert_eq!(positions(&text), positions(&binary));
+        assert_eq!(text.skins[0].inverse_bind_matrices, binary.skins[0].inverse_bind_matrices);
+        assert_eq!(text.images[0], binary.images[0]);
+    }
+
+    #[test]
+    fn refuses_indices_past_the_vertices() {
+        let error = load(Path::new("assets/models/samples/invalid_index.gltf")).unwrap_err();
+        assert!(matches!(error, GltfError::InvalidIndex { mesh: 0, index: 5, vertices: 3 }), "{}", error);
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        assert!(matches!(load(Path::new("assets/models/samples/missing.gltf")), Err(GltfError::Import(_))));
+    }
+}
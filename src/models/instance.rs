@@ -0,0 +1,10 @@
+use crate::render::matrix::Matrix;
+
+/// Per-instance attributes of a model drawn many times
+#[derive(Copy, Clone, Debug)]
+pub struct Instance {
+    pub transform: Matrix,
+    pub colour: [f32; 4],
+}
+
+implement_vertex!(Instance, transform, colour);
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use crate::models::object::{Colour, Normal, Object, TexCoord, Vertex};
+
+/// Builds an `Object` piece by piece, every vertex gets a normal, texture coordinates
+/// and the current colour
+pub struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    normals: Vec<Normal>,
+    tex_coords: Vec<TexCoord>,
+    colours: Vec<Colour>,
+    indices: Vec<u32>,
+    colour: [f32; 4],
+}
+
+impl MeshBuilder {
+    pub fn new() -> MeshBuilder {
+        MeshBuilder {
+            vertices: vec![],
+            normals: vec![],
+            tex_coords: vec![],
+            colours: vec![],
+            indices: vec![],
+            colour: [1.0; 4],
+        }
+    }
+
+    /// Colour of the vertices added from now on
+    pub fn set_colour(&mut self, colour: [f32; 4]) {
+        self.colour = colour;
+    }
+
+    /// Returns the index of the vertex
+    pub fn vertex(&mut self, position: [f32; 3], normal: [f32; 3], tex_coord: [f32; 2]) -> Result<u32, MeshError> {
+        let index = u32::try_from(self.vertices.len()).map_err(|_| MeshError::TooManyVertices)?;
+        self.vertices.push(Vertex { position: position.into() });
+        self.normals.push(Normal { normal: normal.into() });
+        self.tex_coords.push(TexCoord { tex_coords: tex_coord.into() });
+        self.colours.push(Colour { colour: self.colour });
+        Ok(index)
+    }
+
+    /// Corners in counter-clockwise order, seen from the side the normal points to
+    pub fn triangle(&mut self, corners: [[f32; 3]; 3], tex_coords: [[f32; 2]; 3]) -> Result<(), MeshError> {
+        let normal = face_normal(corners[0], corners[1], corners[2]);
+        for (corner, tex_coord) in corners.into_iter().zip(tex_coords) {
+            let index = self.vertex(corner, normal, tex_coord)?;
+            self.indices.push(index);
+        }
+        Ok(())
+    }
+
+    /// Corners in counter-clockwise order, seen from the side the normal points to
+    pub fn quad(&mut self, corners: [[f32; 3]; 4], tex_coords: [[f32; 2]; 4]) -> Result<(), MeshError> {
+        let normal = face_normal(corners[0], corners[1], corners[2]);
+        let mut indices = [0; 4];
+        for (index, (corner, tex_coord)) in indices.iter_mut().zip(corners.into_iter().zip(tex_coords)) {
+            *index = self.vertex(corner, normal, tex_coord)?;
+        }
+        let [a, b, c, d] = indices;
+        self.indices.extend([a, b, c, a, c, d]);
+        Ok(())
+    }
+
+    /// Adds all the triangles of the object, missing attributes are zeroed. Nothing is added
+    /// if the indices would not fit
+    pub fn append(&mut self, object: &Object) -> Result<(), MeshError> {
+        let offset = u32::try_from(self.vertices.len()).map_err(|_| MeshError::TooManyVertices)?;
+        let indices = object.indices.iter()
+            .map(|index| index.checked_add(offset).ok_or(MeshError::TooManyVertices))
+            .collect::<Result<Vec<u32>, _>>()?;
+        if u32::try_from(self.vertices.len() + object.vertices.len()).is_err() {
+            return Err(MeshError::TooManyVertices);
+        }
+        for (i, vertex) in object.vertices.iter().enumerate() {
+            let normal = object.normals.get(i).map_or([0.0; 3], |normal| normal.normal.into());
+            let tex_coord = object.tex_coords.get(i).map_or([0.0; 2], |uv| uv.tex_coords.into());
+            self.vertex(vertex.position.into(), normal, tex_coord)?;
+            if let Some(colour) = object.colours.get(i) {
+                *self.colours.last_mut().unwrap() = *colour;
+            }
+        }
+        self.indices.extend(indices);
+        Ok(())
+    }
+
+    pub fn build(self) -> Object {
+        Object::new(self.vertices, self.normals, self.indices)
+            .with_tex_coords(self.tex_coords)
+            .with_colours(self.colours)
+    }
+}
+
+/// Every object in a single one, to draw them with one call
+pub fn merge(objects: &[Object]) -> Result<Object, MeshError> {
+    let mut builder = MeshBuilder::new();
+    for object in objects {
+        builder.append(object)?;
+    }
+    Ok(builder.build())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MeshError {
+    /// The indices are `u32`
+    TooManyVertices,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::TooManyVertices => write!(f, "more than {} vertices", u32::MAX),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Unit normal of the triangle, following the right hand rule
+pub fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    normalize(cross(sub(b, a), sub(c, a)))
+}
+
+pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// The zero vector stays zero
+pub fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len == 0.0 {
+        v
+    } else {
+        [v[0] / len, v[1] / len, v[2] / len]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Object {
+        let mut builder = MeshBuilder::new();
+        builder.triangle([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], [[0.0; 2]; 3]).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn offsets_the_indices_of_the_merged_objects() {
+        let mut quad = MeshBuilder::new();
+        quad.set_colour([1.0, 0.0, 0.0, 1.0]);
+        quad.quad([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]], [[0.0; 2]; 4]).unwrap();
+        let merged = merge(&[triangle(), quad.build(), triangle()]).unwrap();
+        assert_eq!(merged.vertices.len(), 10);
+        assert_eq!(merged.indices, [0, 1, 2, 3, 4, 5, 3, 5, 6, 7, 8, 9]);
+        assert_eq!(merged.colours[3].colour, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(merged.normals[9].normal, (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn refuses_indices_past_u32() {
+        let mut builder = MeshBuilder::new();
+        builder.append(&triangle()).unwrap();
+        let mut object = triangle();
+        object.indices[2] = u32::MAX;
+        assert_eq!(builder.append(&object), Err(MeshError::TooManyVertices));
+        // nothing of the object was added
+        assert_eq!(builder.build().vertices.len(), 3);
+    }
+}
@@ -1,3 +1,6 @@
 pub mod cube;
-pub mod teapot;
+pub mod obj;
+pub mod gltf;
 pub mod object;
+pub mod mesh;
+pub mod instance;
@@ -0,0 +1,369 @@
+//! Wavefront OBJ (+ MTL) loader
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::models::object::{Normal, Object, TexCoord, Vertex};
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    /// 1.0 is opaque
+    pub dissolve: f32,
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+impl Material {
+    fn new(name: &str) -> Material {
+        Material {
+            name: name.to_string(),
+            ambient: [0.0; 3],
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            dissolve: 1.0,
+            diffuse_texture: None,
+        }
+    }
+}
+
+/// Part of a model using a single material, one per `o` / `g` / `usemtl` of the file
+#[derive(Clone, Debug)]
+pub struct Group {
+    pub name: String,
+    pub material: Option<Material>,
+    pub object: Object,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, error: std::io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "could not read {}: {}", path.display(), error),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+pub fn load(path: &Path) -> Result<Vec<Group>, ObjError> {
+    let source = read(path)?;
+    parse(&source, path)
+}
+
+pub fn load_materials(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let source = read(path)?;
+    parse_materials(&source, path)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|error| ObjError::Io { path: path.to_path_buf(), error })
+}
+
+/// `path` is used in the errors and to find the material libraries
+pub fn parse(source: &str, path: &Path) -> Result<Vec<Group>, ObjError> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let mut positions: Vec<Vertex> = vec![];
+    let mut normals: Vec<Normal> = vec![];
+    let mut tex_coords: Vec<TexCoord> = vec![];
+    let mut materials: HashMap<String, Material> = HashMap::new();
+
+    let mut groups: Vec<Group> = vec![];
+    let mut current = GroupBuilder::new("default", None);
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { path: path.to_path_buf(), line: number + 1, message };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = floats::<3>(&arguments).map_err(error)?;
+                positions.push(Vertex { position: (x, y, z) });
+            }
+            "vn" => {
+                let [x, y, z] = floats::<3>(&arguments).map_err(error)?;
+                normals.push(Normal { normal: (x, y, z) });
+            }
+            "vt" => {
+                // the optional w coordinate is ignored
+                let [u, v] = floats::<2>(&arguments[..arguments.len().min(2)]).map_err(error)?;
+                tex_coords.push(TexCoord { tex_coords: (u, v) });
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error(format!("a face needs at least 3 vertices, got {}", arguments.len())));
+                }
+                let mut corners = vec![];
+                for argument in &arguments {
+                    let key = face_vertex(argument, positions.len(), tex_coords.len(), normals.len()).map_err(error)?;
+                    corners.push(current.index(key, &positions, &tex_coords, &normals).map_err(error)?);
+                }
+                // polygons are split as a fan around the first vertex
+                for i in 1..corners.len() - 1 {
+                    current.indices.extend([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "o" | "g" => {
+                let name = arguments.join(" ");
+                let material = current.material.clone();
+                groups.extend(std::mem::replace(&mut current, GroupBuilder::new(&name, material)).build());
+            }
+            "usemtl" => {
+                // the default material stands for the ones the libraries do not define
+                let name = arguments.join(" ");
+                let material = materials.get(&name).cloned().unwrap_or_else(|| Material::new(&name));
+                let group_name = current.name.clone();
+                groups.extend(std::mem::replace(&mut current, GroupBuilder::new(&group_name, Some(material))).build());
+            }
+            "mtllib" => {
+                // a missing library only leaves its materials undefined
+                for library in &arguments {
+                    match load_materials(&directory.join(library)) {
+                        Ok(library) => materials.extend(library),
+                        Err(ObjError::Io { .. }) => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+            }
+            // smoothing groups, lines, points, ...
+            _ => (),
+        }
+    }
+    groups.extend(current.build());
+
+    Ok(groups)
+}
+
+pub fn parse_materials(source: &str, path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut materials = HashMap::new();
+    let mut current: Option<Material> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { path: path.to_path_buf(), line: number + 1, message };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some(material) = current.take() {
+                materials.insert(material.name.clone(), material);
+            }
+            current = Some(Material::new(&arguments.join(" ")));
+            continue;
+        }
+
+        let material = current.as_mut()
+            .ok_or_else(|| error(format!("`{}` before any `newmtl`", keyword)))?;
+        match keyword {
+            "Ka" => material.ambient = floats::<3>(&arguments).map_err(error)?,
+            "Kd" => material.diffuse = floats::<3>(&arguments).map_err(error)?,
+            "Ks" => material.specular = floats::<3>(&arguments).map_err(error)?,
+            "d" => material.dissolve = floats::<1>(&arguments).map_err(error)?[0],
+            "Tr" => material.dissolve = 1.0 - floats::<1>(&arguments).map_err(error)?[0],
+            "map_Kd" => {
+                // options such as `-s 1 1 1` come before the file name
+                let file = arguments.last().ok_or_else(|| error("missing texture file".to_string()))?;
+                material.diffuse_texture = Some(directory.join(file));
+            }
+            _ => (),
+        }
+    }
+    if let Some(material) = current {
+        materials.insert(material.name.clone(), material);
+    }
+
+    Ok(materials)
+}
+
+fn floats<const N: usize>(arguments: &[&str]) -> Result<[f32; N], String> {
+    if arguments.len() != N {
+        return Err(format!("expected {} numbers, got {}", N, arguments.len()));
+    }
+    let mut result = [0.0; N];
+    for (value, argument) in result.iter_mut().zip(arguments) {
+        *value = argument.parse().map_err(|_| format!("invalid number `{}`", argument))?;
+    }
+    Ok(result)
+}
+
+/// Position, texture coordinate and normal of a face vertex, 0-based
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn face_vertex(argument: &str, positions: usize, tex_coords: usize, normals: usize) -> Result<FaceVertex, String> {
+    let mut parts = argument.split('/');
+    let position = resolve(parts.next().unwrap_or(""), positions, "vertex")?;
+    let tex_coord = match parts.next() {
+        None | Some("") => None,
+        Some(index) => Some(resolve(index, tex_coords, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(index) => Some(resolve(index, normals, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex `{}`", argument));
+    }
+    Ok((position, tex_coord, normal))
+}
+
+/// Indices start at 1, negative ones are relative to the end of the list read so far
+fn resolve(index: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let value: i64 = index.parse().map_err(|_| format!("invalid {} index `{}`", kind, index))?;
+    let resolved = match value {
+        0 => return Err(format!("{} index 0 is invalid, indices start at 1", kind)),
+        value if value > 0 => value - 1,
+        value => count as i64 + value,
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} out of range, {} defined so far", kind, value, count));
+    }
+    Ok(resolved as usize)
+}
+
+struct GroupBuilder {
+    name: String,
+    material: Option<Material>,
+    vertices: Vec<Vertex>,
+    normals: Vec<Option<Normal>>,
+    tex_coords: Vec<Option<TexCoord>>,
+    indices: Vec<u32>,
+    /// each distinct combination of the face indices becomes one vertex
+    known: HashMap<FaceVertex, u32>,
+}
+
+impl GroupBuilder {
+    fn new(name: &str, material: Option<Material>) -> GroupBuilder {
+        GroupBuilder {
+            name: name.to_string(),
+            material,
+            vertices: vec![],
+            normals: vec![],
+            tex_coords: vec![],
+            indices: vec![],
+            known: HashMap::new(),
+        }
+    }
+
+    fn index(&mut self, key: FaceVertex, positions: &[Vertex], tex_coords: &[TexCoord], normals: &[Normal]) -> Result<u32, String> {
+        if let Some(&index) = self.known.get(&key) {
+            return Ok(index);
+        }
+        let index = u32::try_from(self.vertices.len())
+            .map_err(|_| format!("more than {} vertices in group `{}`", u32::MAX, self.name))?;
+
+        let (position, tex_coord, normal) = key;
+        self.vertices.push(positions[position]);
+        self.tex_coords.push(tex_coord.map(|i| tex_coords[i]));
+        self.normals.push(normal.map(|i| normals[i]));
+        self.known.insert(key, index);
+        Ok(index)
+    }
+
+    fn build(self) -> Option<Group> {
+        if self.indices.is_empty() {
+            return None;
+        }
+
+        // texture coordinates are only kept if the file gives them, missing ones are zeroed
+        let has_normals = self.normals.iter().any(Option::is_some);
+        let normals = self.normals.iter().map(|normal| normal.unwrap_or(Normal { normal: (0.0, 0.0, 0.0) })).collect();
+        let tex_coords = if self.tex_coords.iter().any(Option::is_some) {
+            self.tex_coords.iter().map(|uv| uv.unwrap_or(TexCoord { tex_coords: (0.0, 0.0) })).collect()
+        } else {
+            vec![]
+        };
+
+        let object = Object::new(self.vertices, normals, self.indices).with_tex_coords(tex_coords);
+
+        Some(Group {
+            name: self.name,
+            material: self.material,
+            object: if has_normals { object } else { object.with_smooth_normals() },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    fn group(source: &str) -> Group {
+        let mut groups = parse(source, Path::new("model.obj")).unwrap();
+        assert_eq!(groups.len(), 1);
+        groups.remove(0)
+    }
+
+    /// Line of the parse error
+    fn error_line(source: &str) -> usize {
+        match parse(source, Path::new("model.obj")) {
+            Err(ObjError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other.map(|groups| groups.len())),
+        }
+    }
+
+    #[test]
+    fn reads_absolute_and_relative_indices() {
+        let absolute = group(&format!("{}f 1 2 3\n", SQUARE));
+        assert_eq!(absolute.object.indices, [0, 1, 2]);
+        assert_eq!(absolute.object.vertices[2].position, (1.0, 1.0, 0.0));
+        // relative to the 4 vertices read so far
+        let relative = group(&format!("{}f -3 -2 -1\n", SQUARE));
+        assert_eq!(relative.object.vertices.iter().map(|vertex| vertex.position).collect::<Vec<_>>(),
+            [(1.0, 0.0, 0.0), (1.0, 1.0, 0.0), (0.0, 1.0, 0.0)]);
+        // polygons become fans
+        assert_eq!(group(&format!("{}f 1 2 3 4\n", SQUARE)).object.indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn reports_the_line_of_invalid_indices() {
+        assert_eq!(error_line(&format!("{}f 0 1 2\n", SQUARE)), 5);
+        assert_eq!(error_line(&format!("{}\nf 1 2 5\n", SQUARE)), 6);
+        assert_eq!(error_line(&format!("{}f -5 1 2\n", SQUARE)), 5);
+        assert_eq!(error_line(&format!("{}f 1/1 2 3\n", SQUARE)), 5);
+        assert_eq!(error_line("v 0 0\n"), 1);
+    }
+
+    #[test]
+    fn reads_texture_coordinates_and_normals() {
+        let source = format!("{}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\nf 1//1 3//1 4//1\n", SQUARE);
+        let group = group(&source);
+        // without their texture coordinates the vertices of the second face are other vertices
+        assert_eq!(group.object.vertices.len(), 6);
+        assert_eq!(group.object.indices, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(group.object.tex_coords[1].tex_coords, (1.0, 0.0));
+        assert_eq!(group.object.tex_coords[3].tex_coords, (0.0, 0.0));
+        assert!(group.object.normals.iter().all(|normal| normal.normal == (0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn uses_a_default_material_for_the_unknown_ones() {
+        let source = format!("mtllib missing.mtl\n{}usemtl red\nf 1 2 3\n", SQUARE);
+        let material = group(&source).material.unwrap();
+        assert_eq!(material.name, "red");
+        assert_eq!(material.diffuse, [1.0; 3]);
+    }
+}
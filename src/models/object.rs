@@ -1,3 +1,5 @@
+use crate::models::mesh::{self, MeshBuilder, MeshError};
+
 #[derive(Copy, Clone, Debug)]
 pub struct Vertex {
     pub position: (f32, f32, f32)
@@ -12,20 +14,142 @@ pub struct Normal {
 
 implement_vertex!(Normal, normal);
 
+#[derive(Copy, Clone, Debug)]
+pub struct TexCoord {
+    pub tex_coords: (f32, f32)
+}
+
+implement_vertex!(TexCoord, tex_coords);
+
+#[derive(Copy, Clone, Debug)]
+pub struct Colour {
+    pub colour: [f32; 4]
+}
+
+implement_vertex!(Colour, colour);
+
 #[derive(Debug, Clone)]
 pub struct Object {
     pub vertices: Vec<Vertex>,
     #[allow(unused)]
     pub normals: Vec<Normal>,
-    pub indices: Vec<u16>,
+    #[allow(unused)]
+    pub tex_coords: Vec<TexCoord>,
+    /// Multiplied with the colour the object is drawn with, white when empty
+    pub colours: Vec<Colour>,
+    pub indices: Vec<u32>,
 }
 
 impl Object {
-    pub fn new(vertices: Vec<Vertex>, normals: Vec<Normal>, indices: Vec<u16>) -> Object {
+    pub fn new(vertices: Vec<Vertex>, normals: Vec<Normal>, indices: Vec<u32>) -> Object {
         Object {
-            vertices: vertices,
-            normals: normals,
-            indices: indices
+            vertices,
+            normals,
+            tex_coords: vec![],
+            colours: vec![],
+            indices
+        }
+    }
+
+    pub fn with_tex_coords(mut self, tex_coords: Vec<TexCoord>) -> Object {
+        self.tex_coords = tex_coords;
+        self
+    }
+
+    pub fn with_colours(mut self, colours: Vec<Colour>) -> Object {
+        self.colours = colours;
+        self
+    }
+
+    /// Smallest box containing every vertex, None if there are none
+    pub fn bounding_box(&self) -> Option<([f32; 3], [f32; 3])> {
+        let first: [f32; 3] = self.vertices.first()?.position.into();
+        Some(self.vertices.iter().fold((first, first), |(min, max), vertex| {
+            let position: [f32; 3] = vertex.position.into();
+            (
+                core::array::from_fn(|i| min[i].min(position[i])),
+                core::array::from_fn(|i| max[i].max(position[i])),
+            )
+        }))
+    }
+
+    /// Every triangle gets its own vertices with the normal of its face, for sharp edges
+    pub fn with_flat_normals(&self) -> Result<Object, MeshError> {
+        let mut builder = MeshBuilder::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position.into());
+            let tex_coords = [0, 1, 2].map(|i| {
+                self.tex_coords.get(triangle[i] as usize).map_or([0.0; 2], |uv| uv.tex_coords.into())
+            });
+            builder.triangle(corners, tex_coords)?;
+        }
+        Ok(builder.build())
+    }
+
+    /// Normals averaged over the faces sharing each vertex, weighted by their area
+    pub fn with_smooth_normals(&self) -> Object {
+        let mut sums = vec![[0.0f32; 3]; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position.into());
+            // not normalized, so bigger faces count more
+            let normal = mesh::cross(mesh::sub(b, a), mesh::sub(c, a));
+            for &index in triangle {
+                let sum = &mut sums[index as usize];
+                *sum = core::array::from_fn(|i| sum[i] + normal[i]);
+            }
+        }
+
+        let mut object = self.clone();
+        object.normals = sums.into_iter()
+            .map(|sum| Normal { normal: mesh::normalize(sum).into() })
+            .collect();
+        object
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two triangles folded along the z axis like a roof, sharing the vertices 0 and 1
+    fn roof() -> Object {
+        let vertices = [(0.0, 0.0, 0.0), (0.0, 0.0, 1.0), (1.0, -1.0, 0.0), (-1.0, -1.0, 0.0)]
+            .map(|position| Vertex { position });
+        Object::new(vertices.to_vec(), vec![], vec![0, 1, 2, 1, 0, 3])
+    }
+
+    fn assert_close(a: (f32, f32, f32), b: [f32; 3]) {
+        let a: [f32; 3] = a.into();
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn gives_each_face_its_normal() {
+        let flat = roof().with_flat_normals().unwrap();
+        assert_eq!(flat.vertices.len(), 6);
+        assert_eq!(flat.indices, [0, 1, 2, 3, 4, 5]);
+        let side = std::f32::consts::FRAC_1_SQRT_2;
+        for i in 0..3 {
+            assert_close(flat.normals[i].normal, [side, side, 0.0]);
+            assert_close(flat.normals[i + 3].normal, [-side, side, 0.0]);
         }
     }
+
+    #[test]
+    fn averages_the_normals_of_the_faces() {
+        let smooth = roof().with_smooth_normals();
+        assert_eq!(smooth.vertices.len(), 4);
+        // the ridge between both faces points up, the eaves like their face
+        assert_close(smooth.normals[0].normal, [0.0, 1.0, 0.0]);
+        assert_close(smooth.normals[1].normal, [0.0, 1.0, 0.0]);
+        let side = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(smooth.normals[2].normal, [side, side, 0.0]);
+        assert_close(smooth.normals[3].normal, [-side, side, 0.0]);
+    }
+
+    #[test]
+    fn bounds_the_vertices() {
+        assert_eq!(roof().bounding_box(), Some(([-1.0, -1.0, 0.0], [1.0, 0.0, 1.0])));
+        assert_eq!(Object::new(vec![], vec![], vec![]).bounding_box(), None);
+    }
 }
@@ -0,0 +1,276 @@
+use crate::models::cube;
+use crate::models::instance::Instance;
+use crate::models::mesh::MeshBuilder;
+use crate::models::object::Object;
+use crate::render::fog::Fog;
+use crate::render::frustum::Frustum;
+use crate::render::matrix::{self, Matrix};
+use crate::render::projection::DepthMode;
+use crate::render::sky::{Rays, Sky};
+use crate::render::text::{Font, Glyph};
+use crate::render::hud;
+use crate::world::world::World;
+
+#[derive(Clone, Copy)]
+pub struct Uniforms {
+    pub model: Matrix,
+    pub view: Matrix,
+    pub perspective: Matrix,
+    pub depth: DepthMode,
+    pub fog: Fog,
+}
+
+/// How the triangles are drawn. The colour is multiplied with the colours of the vertices,
+/// except for the wireframe
+#[derive(Clone, Copy, Debug)]
+pub enum Style {
+    Fill([f32; 4]),
+    Wireframe([f32; 4]),
+    /// Pixels rejected by `cutout_visible` are discarded, the others are opaque
+    Cutout([f32; 4]),
+    /// Alpha blended over what is already drawn, without writing the depth
+    Blend([f32; 4]),
+}
+
+/// Something objects can be drawn on: the glium path (`scene::GliumBackend`)
+/// or the CPU rasterizer (`software::Rasterizer`)
+pub trait RenderBackend {
+    fn dimensions(&self) -> (u32, u32);
+    fn clear(&mut self, color: [f32; 4], depth: DepthMode);
+    fn draw(&mut self, object: &Object, uniforms: &Uniforms, style: Style);
+    /// Fills the whole target with the sky, without touching the depth buffer
+    fn draw_sky(&mut self, sky: &Sky, rays: &Rays);
+    /// Alpha blends the glyphs over everything, without any depth test. The atlas of the font
+    /// is sampled at the nearest pixel
+    fn draw_glyphs(&mut self, font: &Font, glyphs: &[Glyph]);
+
+    /// Draws the object once per instance, with the transform of the instance as `model`
+    /// and filled with its colour. `model_id` identifies the object across frames, so it
+    /// does not have to be uploaded again.
+    fn draw_instanced(&mut self, model_id: usize, object: &Object, instances: &[Instance], uniforms: &Uniforms) {
+        let _ = model_id;
+        for instance in instances {
+            let uniforms = Uniforms {
+                model: instance.transform,
+                ..*uniforms
+            };
+            self.draw(object, &uniforms, Style::Fill(instance.colour));
+        }
+    }
+}
+
+/// What happened during the last frame
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub chunks_drawn: usize,
+    pub chunks_culled: usize,
+    /// Chunks drawn whose mesh was made again for this frame
+    pub chunks_meshed: usize,
+    pub instances_drawn: usize,
+    /// Instances outside of the view or past the render distance
+    pub instances_culled: usize,
+}
+
+/// Border of the cutout faces always kept, as a fraction of their side
+pub const CUTOUT_BORDER: f32 = 1.0 / 16.0;
+/// Side of the pattern deciding which pixels of a cutout face are kept
+pub const CUTOUT_CELLS: f32 = 8.0;
+
+/// Whether a pixel of a cutout face is kept. Without textures the pattern is made from the
+/// texture coordinates: the border of the face, and a dither inside covering `alpha` of it.
+/// Same as `cutout_visible` in the shader
+pub fn cutout_visible(alpha: f32, tex_coords: [f32; 2]) -> bool {
+    let [u, v] = tex_coords;
+    if u.min(v) < CUTOUT_BORDER || u.max(v) > 1.0 - CUTOUT_BORDER {
+        return true;
+    }
+    // 2x2 ordered dither
+    let x = (u * CUTOUT_CELLS).floor() as i32 & 1;
+    let y = (v * CUTOUT_CELLS).floor() as i32 & 1;
+    let threshold = [[0.125, 0.625], [0.875, 0.375]][y as usize][x as usize];
+    alpha > threshold
+}
+
+const WHITE: [f32; 4] = [1.0; 4];
+const EDGE_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+
+/// Meshes the chunks changed since the last frame, which is why the world is mutable
+pub fn draw_world<B: RenderBackend>(backend: &mut B, world: &mut World) -> FrameStats {
+    let projection = &world.camera.projection;
+    let sky = Sky::new(&world.time).tinted(world.sky_tint());
+    let [r, g, b] = sky.horizon;
+    backend.clear([r, g, b, 1.0], projection.depth);
+
+    let perspective = projection.matrix(backend.dimensions());
+    let view = world.camera.render();
+    backend.draw_sky(&sky, &Rays::new(&view, &perspective));
+
+    let uniforms = Uniforms {
+        model: world.grid.matrix(),
+        view,
+        perspective,
+        depth: projection.depth,
+        fog: Fog {
+            mode: world.settings.fog,
+            colour: sky.horizon,
+            start: world.render_distance() * world.settings.fog_start,
+            end: world.render_distance(),
+        },
+    };
+
+    let frustum = Frustum::from_matrix(
+        &matrix::multiply(&uniforms.perspective, &matrix::multiply(&uniforms.view, &uniforms.model)),
+        uniforms.depth,
+    );
+    let mut stats = FrameStats::default();
+
+    world.meshes.update(&mut world.map);
+    let mut visible = vec![];
+    for chunk in world.map.chunks().filter(|chunk| !chunk.is_empty()) {
+        let (min, max) = chunk.bounds();
+        let distance = distance_to_box(world.camera.position, to_world(&uniforms.model, min), to_world(&uniforms.model, max));
+        if distance > world.render_distance() || !frustum.intersects_aabb(min, max) {
+            stats.chunks_culled += 1;
+            continue;
+        }
+        stats.chunks_drawn += 1;
+        if world.meshes.prepare(&world.map, chunk) {
+            stats.chunks_meshed += 1;
+        }
+        visible.push((distance, chunk.coordinates()));
+    }
+
+    // translucent meshes are drawn after everything else
+    let mut translucent = vec![];
+    for (distance, coordinates) in visible {
+        let mesh = world.meshes.get(coordinates).unwrap();
+        if !mesh.opaque.indices.is_empty() {
+            backend.draw(&mesh.opaque, &uniforms, Style::Fill(sky.lit(WHITE)));
+            // Mode wireframe pour dessiner les bords
+            backend.draw(&mesh.opaque, &uniforms, Style::Wireframe(sky.lit(EDGE_COLOR)));
+        }
+        if !mesh.cutout.indices.is_empty() {
+            backend.draw(&mesh.cutout, &uniforms, Style::Cutout(sky.lit(WHITE)));
+        }
+        if !mesh.translucent.is_empty() {
+            translucent.push((distance, mesh));
+        }
+    }
+
+    if !world.falling.is_empty() {
+        let mut builder = MeshBuilder::new();
+        for block in &world.falling {
+            builder.set_colour(block.block_type.colour());
+            let center = block.position.map(|coordinate| coordinate * cube::SIZE * 2.0);
+            for face in 0..cube::FACES.len() {
+                // bien moins de blocs qui tombent que u32::MAX / 24
+                cube::add_face(&mut builder, center, face).expect("too many falling blocks");
+            }
+        }
+        let mesh = builder.build();
+        backend.draw(&mesh, &uniforms, Style::Fill(sky.lit(WHITE)));
+        backend.draw(&mesh, &uniforms, Style::Wireframe(sky.lit(EDGE_COLOR)));
+    }
+
+    // the nodes are placed in the world, not in the grid
+    let world_frustum = Frustum::from_matrix(&matrix::multiply(&uniforms.perspective, &uniforms.view), uniforms.depth);
+    let bounds: Vec<_> = world.models.iter().map(Object::bounding_box).collect();
+    let mut instances: Vec<Vec<Instance>> = vec![vec![]; world.models.len()];
+    for (model, instance) in world.graph.instances() {
+        let Some((min, max)) = bounds[model] else {
+            continue;
+        };
+        let (min, max) = transformed_box(&instance.transform, min, max);
+        if distance_to_box(world.camera.position, min, max) > world.render_distance() || !world_frustum.intersects_aabb(min, max) {
+            stats.instances_culled += 1;
+            continue;
+        }
+        instances[model].push(Instance { colour: sky.lit(instance.colour), ..instance });
+    }
+    for (model_id, (object, instances)) in world.models.iter().zip(instances).enumerate() {
+        if instances.is_empty() {
+            continue;
+        }
+        backend.draw_instanced(model_id, object, &instances, &uniforms);
+        stats.instances_drawn += instances.len();
+    }
+
+    // from the farthest chunk to the closest, each sorting its own faces
+    let eye = to_world(&world.grid.inverse_matrix(), world.camera.position);
+    translucent.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    for (_, mesh) in translucent {
+        backend.draw(&mesh.translucent_sorted(eye), &uniforms, Style::Blend(sky.lit(WHITE)));
+    }
+
+    if let Some(font) = &world.font {
+        hud::draw(backend, world, font);
+    }
+
+    stats
+}
+
+fn to_world(model: &Matrix, point: [f32; 3]) -> [f32; 3] {
+    let [x, y, z, _] = matrix::transform(model, [point[0], point[1], point[2], 1.0]);
+    [x, y, z]
+}
+
+/// Box containing the 8 corners of the box once transformed
+fn transformed_box(transform: &Matrix, min: [f32; 3], max: [f32; 3]) -> ([f32; 3], [f32; 3]) {
+    let corners = (0..8).map(|corner| {
+        to_world(transform, std::array::from_fn(|i| if corner >> i & 1 == 0 { min[i] } else { max[i] }))
+    });
+    corners.fold(([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]), |(min, max), corner| {
+        (std::array::from_fn(|i| min[i].min(corner[i])), std::array::from_fn(|i| max[i].max(corner[i])))
+    })
+}
+
+/// 0.0 inside the box
+fn distance_to_box(point: [f32; 3], min: [f32; 3], max: [f32; 3]) -> f32 {
+    let delta: [f32; 3] = std::array::from_fn(|i| point[i] - point[i].clamp(min[i], max[i]));
+    (delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::software::Rasterizer;
+    use crate::render::vec3d::Vec3d;
+
+    #[test]
+    fn draws_the_visible_instances() {
+        let mut world = World::new();
+        let mut builder = MeshBuilder::new();
+        for face in 0..cube::FACES.len() {
+            cube::add_face(&mut builder, [0.0; 3], face).unwrap();
+        }
+        let model = world.add_model(builder.build());
+        world.camera.position = [0.0; 3];
+        world.camera.direction = Vec3d::new(0.0, 0.0, 1.0).to_quaternion();
+        let empty = {
+            let mut rasterizer = Rasterizer::new((64, 64));
+            draw_world(&mut rasterizer, &mut world);
+            rasterizer.into_image()
+        };
+
+        let mut nodes = vec![];
+        for x in [-2.0, 0.0, 2.0] {
+            nodes.push(world.place(model, [x, 0.0, 5.0], None));
+        }
+        // behind the camera, then past the render distance
+        nodes.push(world.place(model, [0.0, 0.0, -5.0], None));
+        nodes.push(world.place(model, [0.0, 0.0, world.render_distance() + 1.0], None));
+        // the cube is 20 units wide, 1 once scaled
+        for node in nodes {
+            world.graph.get_mut(node).unwrap().transform.scale = [0.05; 3];
+        }
+
+        let mut rasterizer = Rasterizer::new((64, 64));
+        let stats = draw_world(&mut rasterizer, &mut world);
+        assert_eq!(stats.instances_drawn, 3);
+        assert_eq!(stats.instances_culled, 2);
+        // the one in the middle covers the center of the screen
+        let image = rasterizer.into_image();
+        assert_ne!(image.get_pixel(32, 32), empty.get_pixel(32, 32));
+        assert_eq!(image.get_pixel(32, 2), empty.get_pixel(32, 2));
+    }
+}
@@ -1,11 +1,13 @@
 use crate::render::quaternion::Quaternion;
 
+use super::projection::Projection;
 use super::vec3d::Vec3d;
 
 pub struct Camera {
     pub position: [f32; 3],
     pub direction: Quaternion,
     pub up: [f32; 3],
+    pub projection: Projection,
 }
 
 impl Camera {
@@ -13,16 +15,17 @@ impl Camera {
         Camera {
             position: [2.0, -1.0, 1.0],
             direction: Vec3d::new(-2.0, 1.0, 1.0).to_quaternion().normalize(), 
-            up: [0.0, 1.0, 0.0]
+            up: [0.0, 1.0, 0.0],
+            projection: Projection::new(),
         }
     }
 
     pub fn render(self: &Camera) -> [[f32; 4]; 4] {
-        return view_matrix(
+        view_matrix(
             &self.position, 
             &[self.direction.x, self.direction.y, self.direction.z], 
             &self.up
-        );
+        )
     }
 
     pub fn deplace(self: &mut Camera, vect: Vec3d) {
@@ -0,0 +1,52 @@
+use crate::settings::FogMode;
+
+/// Visibility left at the render distance with the exponential fog
+const EXPONENTIAL_VISIBILITY: f32 = 1.0 / 256.0;
+
+/// Fog hiding the edge of the render distance, blended towards the colour of the sky
+#[derive(Clone, Copy, Debug)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub colour: [f32; 3],
+    /// Distances from the camera in world units
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Fog {
+    /// How much of the object is seen at the distance, 1.0 without any fog
+    pub fn visibility(&self, distance: f32) -> f32 {
+        match self.mode {
+            FogMode::Off => 1.0,
+            FogMode::Linear => ((self.end - distance) / (self.end - self.start)).clamp(0.0, 1.0),
+            FogMode::Exponential => {
+                let density = self.density();
+                (-(distance * density) * (distance * density)).exp()
+            }
+        }
+    }
+
+    /// For the exponential mode, chosen so the fog is almost opaque at the end
+    pub fn density(&self) -> f32 {
+        (-EXPONENTIAL_VISIBILITY.ln()).sqrt() / self.end
+    }
+
+    /// Number given to the shaders
+    pub fn mode_id(&self) -> i32 {
+        match self.mode {
+            FogMode::Off => 0,
+            FogMode::Linear => 1,
+            FogMode::Exponential => 2,
+        }
+    }
+
+    pub fn apply(&self, colour: [f32; 4], visibility: f32) -> [f32; 4] {
+        let [r, g, b] = self.colour;
+        [
+            r + (colour[0] - r) * visibility,
+            g + (colour[1] - g) * visibility,
+            b + (colour[2] - b) * visibility,
+            colour[3],
+        ]
+    }
+}
@@ -0,0 +1,118 @@
+use crate::render::matrix::Matrix;
+use crate::render::projection::DepthMode;
+
+/// Plane `a*x + b*y + c*z + d = 0`, the normal points inside the frustum
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    pub normal: [f32; 3],
+    pub d: f32,
+}
+
+impl Plane {
+    fn from_row(row: [f32; 4]) -> Plane {
+        let len = (row[0] * row[0] + row[1] * row[1] + row[2] * row[2]).sqrt();
+        Plane {
+            normal: [row[0] / len, row[1] / len, row[2] / len],
+            d: row[3] / len,
+        }
+    }
+
+    pub fn distance(&self, point: [f32; 3]) -> f32 {
+        self.normal[0] * point[0] + self.normal[1] * point[1] + self.normal[2] * point[2] + self.d
+    }
+}
+
+/// The six planes of the visible volume, extracted from a projection matrix
+/// (Gribb & Hartmann). With `perspective * view * model` the planes are in model space.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    pub fn from_matrix(m: &Matrix, depth: DepthMode) -> Frustum {
+        let row = |i: usize| [m[0][i], m[1][i], m[2][i], m[3][i]];
+        let combine = |a: [f32; 4], b: [f32; 4], sign: f32| {
+            Plane::from_row(core::array::from_fn(|i| a[i] + sign * b[i]))
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let (near, far) = match depth {
+            DepthMode::Standard => (combine(w, z, 1.0), combine(w, z, -1.0)),
+            // near is at z = w, far at z = 0
+            DepthMode::Reversed => (combine(w, z, -1.0), Plane::from_row(z)),
+        };
+
+        Frustum {
+            planes: [
+                combine(w, x, 1.0),  // left
+                combine(w, x, -1.0), // right
+                combine(w, y, 1.0),  // bottom
+                combine(w, y, -1.0), // top
+                near,
+                far,
+            ],
+        }
+    }
+
+    /// False only if the box is entirely outside one of the planes, so boxes near the
+    /// corners of the frustum may be kept even if they are not visible
+    pub fn intersects_aabb(&self, min: [f32; 3], max: [f32; 3]) -> bool {
+        self.planes.iter().all(|plane| {
+            // corner of the box the furthest along the normal
+            let positive: [f32; 3] = core::array::from_fn(|i| if plane.normal[i] >= 0.0 { max[i] } else { min[i] });
+            plane.distance(positive) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::projection::Projection;
+
+    /// Square view with a field of view of 60°, from z = 0.1 to z = 1024
+    fn frustum(depth: DepthMode) -> Frustum {
+        let projection = Projection { depth, ..Projection::new() };
+        Frustum::from_matrix(&projection.matrix((100, 100)), depth)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn extracts_the_planes() {
+        let edge = 10.0 * (std::f32::consts::PI / 6.0).tan();
+        for depth in [DepthMode::Standard, DepthMode::Reversed] {
+            let [left, right, bottom, top, near, far] = frustum(depth).planes;
+            assert_close(left.distance([-edge, 0.0, 10.0]), 0.0);
+            assert_close(right.distance([edge, 0.0, 10.0]), 0.0);
+            assert_close(bottom.distance([0.0, -edge, 10.0]), 0.0);
+            assert_close(top.distance([0.0, edge, 10.0]), 0.0);
+            assert!(left.normal[0] > 0.0 && right.normal[0] < 0.0);
+            assert!(bottom.normal[1] > 0.0 && top.normal[1] < 0.0);
+            // relative to the distance, the far plane loses some precision
+            for (plane, normal, z) in [(near, 1.0, 0.1), (far, -1.0, 1024.0)] {
+                assert_close(plane.normal[2], normal);
+                assert_close(plane.distance([3.0, -2.0, z]) / z, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_the_boxes_inside_or_across_the_planes() {
+        for depth in [DepthMode::Standard, DepthMode::Reversed] {
+            let frustum = frustum(depth);
+            // inside
+            assert!(frustum.intersects_aabb([-1.0, -1.0, 5.0], [1.0, 1.0, 6.0]));
+            // across the left plane, and across the near plane
+            assert!(frustum.intersects_aabb([-7.0, -1.0, 10.0], [-5.0, 1.0, 11.0]));
+            assert!(frustum.intersects_aabb([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]));
+            // behind the camera, beyond the far plane, on the side and above
+            assert!(!frustum.intersects_aabb([-1.0, -1.0, -6.0], [1.0, 1.0, -5.0]));
+            assert!(!frustum.intersects_aabb([-1.0, -1.0, 1100.0], [1.0, 1.0, 1200.0]));
+            assert!(!frustum.intersects_aabb([20.0, -1.0, 10.0], [21.0, 1.0, 11.0]));
+            assert!(!frustum.intersects_aabb([-1.0, 20.0, 10.0], [1.0, 21.0, 11.0]));
+        }
+    }
+}
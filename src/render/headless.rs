@@ -0,0 +1,93 @@
+use std::error::Error;
+use std::ffi::{c_void, CString};
+use std::rc::Rc;
+
+use glium::backend::{Backend, Context, Facade};
+use glium::debug::DebugCallbackBehavior;
+use glium::glutin::api::egl::context::PossiblyCurrentContext;
+use glium::glutin::api::egl::device::Device;
+use glium::glutin::api::egl::display::Display as EglDisplay;
+use glium::glutin::config::{ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glium::glutin::context::ContextAttributesBuilder;
+use glium::glutin::display::GetGlDisplay;
+use glium::glutin::prelude::*;
+use glium::SwapBuffersError;
+
+/// An OpenGL context without any window, made current without a surface through EGL.
+/// Everything has to be drawn into a framebuffer object (see `screenshot::capture`),
+/// which works with software implementations such as llvmpipe on machines without GPU.
+pub struct Headless {
+    context: Rc<Context>,
+}
+
+struct EglBackend {
+    context: PossiblyCurrentContext,
+    dimensions: (u32, u32),
+}
+
+unsafe impl Backend for EglBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        // nothing to present
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        let symbol = CString::new(symbol).unwrap();
+        self.context.display().get_proc_address(&symbol)
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn resize(&self, _new_size: (u32, u32)) {}
+
+    fn is_current(&self) -> bool {
+        self.context.is_current()
+    }
+
+    unsafe fn make_current(&self) {
+        self.context.make_current_surfaceless().unwrap();
+    }
+}
+
+impl Headless {
+    pub fn new(dimensions: (u32, u32)) -> Result<Headless, Box<dyn Error>> {
+        let mut last_error: Box<dyn Error> = "no EGL device found".into();
+
+        for device in Device::query_devices()? {
+            match Self::with_device(&device, dimensions) {
+                Ok(headless) => return Ok(headless),
+                Err(err) => last_error = err,
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn with_device(device: &Device, dimensions: (u32, u32)) -> Result<Headless, Box<dyn Error>> {
+        let display = unsafe { EglDisplay::with_device(device, None)? };
+
+        let template = ConfigTemplateBuilder::new()
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .build();
+        let config = unsafe { display.find_configs(template)? }
+            .next()
+            .ok_or("no EGL config available")?;
+
+        let attributes = ContextAttributesBuilder::new().build(None);
+        let context = unsafe { display.create_context(&config, &attributes)? }
+            .make_current_surfaceless()?;
+
+        let backend = EglBackend { context, dimensions };
+        let context = unsafe { Context::new(backend, true, DebugCallbackBehavior::default())? };
+
+        Ok(Headless { context })
+    }
+}
+
+impl Facade for Headless {
+    fn get_context(&self) -> &Rc<Context> {
+        &self.context
+    }
+}
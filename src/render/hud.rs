@@ -0,0 +1,99 @@
+//! Texts drawn over the world: the chat at the bottom left with the line being typed, and where
+//! the player is at the top right
+
+use crate::render::backend::RenderBackend;
+use crate::render::text::{self, Align, Font, Glyph, Text};
+use crate::world::time::TICKS_PER_SECOND;
+use crate::world::world::World;
+
+/// Pixels of screen height for each step of the scale, whole steps keep the pixels of the font sharp
+const PIXELS_PER_SCALE: u32 = 360;
+/// Lines of chat shown at most
+const CHAT_LINES: usize = 10;
+/// Characters per line of chat at most
+const CHAT_WIDTH: usize = 60;
+/// How long the messages stay while the console is closed
+const MESSAGE_TICKS: u64 = 10 * TICKS_PER_SECOND as u64;
+/// Drawn at the center of the screen
+const CROSSHAIR: &str = "+";
+/// Space between the texts and the sides of the screen, in pixels of the font
+const MARGIN: f32 = 2.0;
+
+const TEXT_COLOUR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const INPUT_COLOUR: [f32; 4] = [1.0, 1.0, 0.6, 1.0];
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+
+pub fn draw<B: RenderBackend>(backend: &mut B, world: &World, font: &Font) {
+    let (width, height) = backend.dimensions();
+    let scale = (height / PIXELS_PER_SCALE).max(1) as f32;
+    let margin = MARGIN * scale;
+    let columns = ((width as f32 - 2.0 * margin) / (font.cell.0 as f32 * scale)) as usize;
+    let columns = columns.clamp(1, CHAT_WIDTH);
+    let mut glyphs = vec![];
+
+    // the chat goes up from the bottom: the line being typed, then the newest messages
+    let mut bottom = height as f32 - margin;
+    let console = &world.console;
+    let input = format!("{}_", console.input);
+    let chat = |content, colour| Text {
+        content,
+        position: [margin, 0.0],
+        scale,
+        colour,
+        align: Align::Left,
+        width: Some(columns),
+    };
+    if console.open {
+        bottom = add_above(&mut glyphs, font, chat(&input, INPUT_COLOUR), bottom);
+    }
+    let messages = if console.open { &console.log[..] } else { console.recent(MESSAGE_TICKS) };
+    if !messages.is_empty() {
+        let messages: Vec<&str> = messages.iter().map(|message| message.text.as_str()).collect();
+        let lines = text::wrap(&messages.join("\n"), Some(columns));
+        let shown = lines[lines.len().saturating_sub(CHAT_LINES)..].join("\n");
+        add_above(&mut glyphs, font, chat(&shown, TEXT_COLOUR), bottom);
+    }
+
+    let [x, y, z] = world.camera_block().map(|c| c.round() as i64);
+    let biome = world.map.biome(x, z).map_or("unloaded", |biome| biome.name());
+    let status = format!("{} {}\n{} {} {}\n{}", world.game_mode.name(), world.held.name(), x, y, z, biome);
+    let mut text = Text {
+        content: &status,
+        position: [0.0, margin],
+        scale,
+        colour: TEXT_COLOUR,
+        align: Align::Right,
+        width: None,
+    };
+    text.position[0] = width as f32 - margin - font.size(&text)[0];
+    add_boxed(&mut glyphs, font, &text);
+
+    let mut crosshair = Text {
+        content: CROSSHAIR,
+        position: [0.0, 0.0],
+        scale,
+        colour: TEXT_COLOUR,
+        align: Align::Center,
+        width: None,
+    };
+    let [crosshair_width, crosshair_height] = font.size(&crosshair);
+    crosshair.position = [((width as f32 - crosshair_width) / 2.0).round(), ((height as f32 - crosshair_height) / 2.0).round()];
+    glyphs.extend(font.glyphs(&crosshair));
+
+    backend.draw_glyphs(font, &glyphs);
+}
+
+/// Adds the text so its box ends at `bottom`, and returns the top of the box
+fn add_above(glyphs: &mut Vec<Glyph>, font: &Font, mut text: Text, bottom: f32) -> f32 {
+    text.position[1] = bottom - font.size(&text)[1];
+    add_boxed(glyphs, font, &text);
+    text.position[1]
+}
+
+/// The text over a background as big as its box
+fn add_boxed(glyphs: &mut Vec<Glyph>, font: &Font, text: &Text) {
+    let [left, top] = text.position;
+    let [width, height] = font.size(text);
+    glyphs.push(font.rectangle([left, top, left + width, top + height], BACKGROUND));
+    glyphs.extend(font.glyphs(text));
+}
@@ -0,0 +1,35 @@
+/// Column major 4x4 matrix, as expected by the shaders (`m[column][row]`)
+pub type Matrix = [[f32; 4]; 4];
+
+pub const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+pub fn translation(v: [f32; 3]) -> Matrix {
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [v[0], v[1], v[2], 1.0],
+    ]
+}
+
+/// `a * b`, so `b` is applied first
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for (column, b_column) in b.iter().enumerate() {
+        result[column] = transform(a, *b_column);
+    }
+    result
+}
+
+pub fn transform(m: &Matrix, v: [f32; 4]) -> [f32; 4] {
+    let mut result = [0.0; 4];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = m[0][row] * v[0] + m[1][row] * v[1] + m[2][row] * v[2] + m[3][row] * v[3];
+    }
+    result
+}
@@ -1,3 +1,17 @@
 pub mod quaternion;
 pub mod vec3d;
+pub mod matrix;
+pub mod transform;
+pub mod fog;
+pub mod frustum;
+pub mod projection;
 pub mod camera;
+pub mod backend;
+pub mod renderer;
+pub mod scene;
+pub mod sky;
+pub mod text;
+pub mod hud;
+pub mod software;
+pub mod screenshot;
+pub mod headless;
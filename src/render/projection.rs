@@ -0,0 +1,185 @@
+use crate::render::matrix::Matrix;
+
+pub const MIN_FOV: f32 = 30.0 * std::f32::consts::PI / 180.0;
+pub const MAX_FOV: f32 = 110.0 * std::f32::consts::PI / 180.0;
+/// How much the zoom key narrows the view
+pub const ZOOM_FACTOR: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Perspective,
+    /// Top-down / map views, without any foreshortening
+    Orthographic,
+}
+
+/// How depth values are stored in the depth buffer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthMode {
+    /// OpenGL default: z in [-1, 1], the near plane is at -1, smaller is closer
+    Standard,
+    /// z in [0, 1], the near plane is at 1, bigger is closer. Spreads the float precision
+    /// evenly over the distance, which avoids z-fighting far from the camera
+    Reversed,
+}
+
+impl DepthMode {
+    /// Value of the depth buffer before anything is drawn
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::Reversed => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Projection {
+    pub mode: Mode,
+    pub depth: DepthMode,
+    /// Vertical field of view in radians, for the perspective mode
+    pub fov: f32,
+    /// Height of the visible area in world units, for the orthographic mode
+    pub height: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub zoomed: bool,
+}
+
+impl Projection {
+    pub fn new() -> Projection {
+        Projection {
+            mode: Mode::Perspective,
+            depth: DepthMode::Standard,
+            fov: std::f32::consts::PI / 3.0,
+            height: 8.0,
+            znear: 0.1,
+            zfar: 1024.0,
+            zoomed: false,
+        }
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    /// Field of view actually used, narrowed while zooming
+    pub fn current_fov(&self) -> f32 {
+        if self.zoomed {
+            2.0 * ((self.fov / 2.0).tan() / ZOOM_FACTOR).atan()
+        } else {
+            self.fov
+        }
+    }
+
+    pub fn current_height(&self) -> f32 {
+        if self.zoomed {
+            self.height / ZOOM_FACTOR
+        } else {
+            self.height
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            Mode::Perspective => Mode::Orthographic,
+            Mode::Orthographic => Mode::Perspective,
+        };
+    }
+
+    pub fn toggle_depth(&mut self) {
+        self.depth = match self.depth {
+            DepthMode::Standard => DepthMode::Reversed,
+            DepthMode::Reversed => DepthMode::Standard,
+        };
+    }
+
+    /// Projection matrix for a target of the given size in pixels
+    pub fn matrix(&self, dimensions: (u32, u32)) -> Matrix {
+        let (width, height) = dimensions;
+        let aspect_ratio = height as f32 / width as f32;
+        let (znear, zfar) = (self.znear, self.zfar);
+
+        // z of the clip space, as `a * z + b`
+        let (a, b) = match (self.mode, self.depth) {
+            (Mode::Perspective, DepthMode::Standard) => ((zfar+znear)/(zfar-znear), -(2.0*zfar*znear)/(zfar-znear)),
+            (Mode::Perspective, DepthMode::Reversed) => (znear/(znear-zfar), (zfar*znear)/(zfar-znear)),
+            (Mode::Orthographic, DepthMode::Standard) => (2.0/(zfar-znear), -(zfar+znear)/(zfar-znear)),
+            (Mode::Orthographic, DepthMode::Reversed) => (-1.0/(zfar-znear), zfar/(zfar-znear)),
+        };
+
+        match self.mode {
+            Mode::Perspective => {
+                let f = 1.0 / (self.current_fov() / 2.0).tan();
+                [
+                    [f *   aspect_ratio   ,    0.0,  0.0,   0.0],
+                    [         0.0         ,     f ,  0.0,   0.0],
+                    [         0.0         ,    0.0,   a ,   1.0],
+                    [         0.0         ,    0.0,   b ,   0.0],
+                ]
+            }
+            Mode::Orthographic => {
+                let f = 2.0 / self.current_height();
+                [
+                    [f *   aspect_ratio   ,    0.0,  0.0,   0.0],
+                    [         0.0         ,     f ,  0.0,   0.0],
+                    [         0.0         ,    0.0,   a ,   0.0],
+                    [         0.0         ,    0.0,   b ,   1.0],
+                ]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::matrix;
+
+    /// Normalized device coordinates of a point of the view space
+    fn project(projection: &Projection, point: [f32; 3]) -> [f32; 3] {
+        let [x, y, z, w] = matrix::transform(&projection.matrix((200, 100)), [point[0], point[1], point[2], 1.0]);
+        [x / w, y / w, z / w]
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn reverses_the_depth() {
+        for mode in [Mode::Perspective, Mode::Orthographic] {
+            let projection = Projection { mode, depth: DepthMode::Reversed, ..Projection::new() };
+            assert_close(project(&projection, [0.0, 0.0, projection.znear])[2], 1.0);
+            assert_close(project(&projection, [0.0, 0.0, projection.zfar])[2], 0.0);
+            assert!(project(&projection, [0.0, 0.0, 10.0])[2] > project(&projection, [0.0, 0.0, 20.0])[2]);
+
+            let projection = Projection { depth: DepthMode::Standard, ..projection };
+            assert_close(project(&projection, [0.0, 0.0, projection.znear])[2], -1.0);
+            assert_close(project(&projection, [0.0, 0.0, projection.zfar])[2], 1.0);
+        }
+    }
+
+    #[test]
+    fn clamps_the_field_of_view() {
+        let mut projection = Projection::new();
+        projection.set_fov(0.1);
+        assert_eq!(projection.fov, MIN_FOV);
+        projection.set_fov(3.0);
+        assert_eq!(projection.fov, MAX_FOV);
+        projection.set_fov(1.0);
+        assert_eq!(projection.fov, 1.0);
+    }
+
+    #[test]
+    fn projects_without_foreshortening() {
+        let mut projection = Projection { mode: Mode::Orthographic, height: 8.0, ..Projection::new() };
+        for z in [1.0, 100.0] {
+            // twice as wide as high
+            let [x, y, _] = project(&projection, [8.0, -4.0, z]);
+            assert_close(x, 1.0);
+            assert_close(y, -1.0);
+        }
+        projection.zoomed = true;
+        assert_close(project(&projection, [0.0, 1.0, 5.0])[1], 1.0);
+    }
+}
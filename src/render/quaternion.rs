@@ -1,5 +1,6 @@
 use std::ops::Mul;
 
+use crate::render::matrix::Matrix;
 use crate::render::vec3d::Vec3d;
 
 #[derive(Debug, Clone, Copy)]
@@ -20,6 +21,17 @@ impl Quaternion {
         Quaternion::new(self.w / len, self.x / len, self.y / len, self.z / len)
     }
 
+    /// Rotation matrix of a unit quaternion
+    pub fn to_matrix(self) -> Matrix {
+        let Quaternion { w, x, y, z } = self;
+        [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + w * z), 2.0 * (x * z - w * y), 0.0],
+            [2.0 * (x * y - w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + w * x), 0.0],
+            [2.0 * (x * z + w * y), 2.0 * (y * z - w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
     pub fn from_rotation(axis: Vec3d, angle: f32) -> Quaternion {
         let half_angle = angle / 2.0;
         let sin_half_angle = half_angle.sin();
@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::Texture2d;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::winit::window::Window;
+use glium::{glutin::surface::WindowSurface, Display, DrawError, Surface};
+use image::RgbaImage;
+
+use crate::render::backend::FrameStats;
+use crate::render::scene::{self, Scene};
+use crate::render::screenshot;
+use crate::world::world::World;
+
+/// Draws a `World` into the window it owns
+pub struct Renderer {
+    display: Display<WindowSurface>,
+    window: Window,
+    scene: Scene,
+    /// Where the frames are drawn before being copied to the window, whose depth buffer
+    /// is not in floats. Made again when the size of the window changes
+    buffers: RefCell<Option<(Texture2d, DepthRenderBuffer)>>,
+}
+
+impl Renderer {
+    pub fn new(display: Display<WindowSurface>, window: Window) -> Renderer {
+        let scene = Scene::new(&display);
+
+        Renderer {
+            display,
+            window,
+            scene,
+            buffers: RefCell::new(None),
+        }
+    }
+
+    /// See `scene::supports_reversed_depth`
+    pub fn supports_reversed_depth(&self) -> bool {
+        scene::supports_reversed_depth(&self.display)
+    }
+
+    pub fn resize(&self, window_size: (u32, u32)) {
+        self.display.resize(window_size);
+    }
+
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+    }
+
+    pub fn render(&self, world: &mut World) -> Result<FrameStats, DrawError> {
+        let dimensions = self.display.get_framebuffer_dimensions();
+        // réduite, il n'y a rien à dessiner
+        if dimensions.0 == 0 || dimensions.1 == 0 {
+            return Ok(FrameStats::default());
+        }
+        let mut buffers = self.buffers.borrow_mut();
+        if buffers.as_ref().is_none_or(|(color, _)| color.dimensions() != dimensions) {
+            *buffers = Some(screenshot::buffers(&self.display, dimensions));
+        }
+        let (color, depth) = buffers.as_ref().unwrap();
+        let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(&self.display, color, depth).unwrap();
+        let stats = self.scene.draw(&self.display, &mut framebuffer, world);
+
+        let target = self.display.draw();
+        color.as_surface().fill(&target, MagnifySamplerFilter::Nearest);
+        target.finish().unwrap();
+        stats
+    }
+
+    /// Renders the world offscreen at the size of the window
+    pub fn screenshot(&self, world: &mut World) -> Result<(RgbaImage, FrameStats), DrawError> {
+        let dimensions = self.display.get_framebuffer_dimensions();
+        screenshot::capture(&self.display, &self.scene, world, dimensions)
+    }
+}
@@ -0,0 +1,436 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use glium::backend::Facade;
+use glium::texture::{RawImage2d, Texture2d};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glium::{draw_parameters, Api, CapabilitiesSource, DrawError, DrawParameters, IndexBuffer, Program, Version, VertexBuffer};
+
+use crate::glium::Surface;
+use crate::models::instance::Instance;
+use crate::models::object::{Colour, Object, TexCoord, Vertex};
+use crate::render::backend::{self, FrameStats, RenderBackend, Style, Uniforms};
+use crate::render::projection::DepthMode;
+use crate::render::sky::{Rays, Sky};
+use crate::render::text::{Font, Glyph};
+use crate::world::world::World;
+
+/// Same as `Fog::visibility`, computed per vertex like the CPU rasterizer does
+macro_rules! fog_visibility_glsl {
+    () => { r#"
+    uniform int fog_mode;
+    uniform float fog_start;
+    uniform float fog_end;
+    uniform float fog_density;
+
+    float fog_visibility(vec4 view_position) {
+        float distance = length(view_position.xyz);
+        if (fog_mode == 1) {
+            return clamp((fog_end - distance) / (fog_end - fog_start), 0.0, 1.0);
+        } else if (fog_mode == 2) {
+            return exp(-(distance * fog_density) * (distance * fog_density));
+        }
+        return 1.0;
+    }
+"# };
+}
+
+const VERTEX_SHADER_SRC: &str = concat!("#version 140\n", fog_visibility_glsl!(), r#"
+    in vec3 position;
+    in vec4 colour;
+    in vec2 tex_coords;
+
+    out float visibility;
+    out vec4 v_colour;
+    out vec2 v_tex_coords;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+    uniform mat4 model;
+
+    void main() {
+        mat4 modelview = view * model;
+        vec4 view_position = modelview * vec4(position, 1.0);
+        visibility = fog_visibility(view_position);
+        v_colour = colour;
+        v_tex_coords = tex_coords;
+        gl_Position = perspective * view_position;
+    }
+"#);
+
+/// `style`: 0 fill or blend, 1 wireframe, 2 cutout
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 140
+
+    in float visibility;
+    in vec4 v_colour;
+    in vec2 v_tex_coords;
+
+    uniform vec4 tint;
+    uniform int style;
+    uniform vec3 fog_colour;
+
+    out vec4 color;
+
+    // same as `backend::cutout_visible`
+    bool cutout_visible(float alpha, vec2 uv) {
+        if (min(uv.x, uv.y) < 1.0 / 16.0 || max(uv.x, uv.y) > 1.0 - 1.0 / 16.0) {
+            return true;
+        }
+        int x = int(floor(uv.x * 8.0)) & 1;
+        int y = int(floor(uv.y * 8.0)) & 1;
+        float threshold = y == 0 ? (x == 0 ? 0.125 : 0.625) : (x == 0 ? 0.875 : 0.375);
+        return alpha > threshold;
+    }
+
+    void main() {
+        vec4 colour = style == 1 ? tint : tint * v_colour;
+        if (style == 2) {
+            if (!cutout_visible(v_colour.a, v_tex_coords)) {
+                discard;
+            }
+            colour.a = tint.a;
+        }
+        color = vec4(mix(fog_colour, colour.rgb, visibility), colour.a);
+    }
+"#;
+
+const INSTANCED_VERTEX_SHADER_SRC: &str = concat!("#version 140\n", fog_visibility_glsl!(), r#"
+    in vec3 position;
+    in mat4 transform;
+    in vec4 colour;
+
+    out vec4 v_colour;
+    out float visibility;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+
+    void main() {
+        v_colour = colour;
+        vec4 view_position = view * transform * vec4(position, 1.0);
+        visibility = fog_visibility(view_position);
+        gl_Position = perspective * view_position;
+    }
+"#);
+
+const INSTANCED_FRAGMENT_SHADER_SRC: &str = r#"
+    #version 140
+
+    in vec4 v_colour;
+    in float visibility;
+
+    uniform vec3 fog_colour;
+
+    out vec4 color;
+
+    void main() {
+        color = vec4(mix(fog_colour, v_colour.rgb, visibility), v_colour.a);
+    }
+"#;
+
+const SKY_VERTEX_SHADER_SRC: &str = r#"
+    #version 140
+
+    in vec3 position;
+
+    out vec2 ndc;
+
+    void main() {
+        ndc = position.xy;
+        gl_Position = vec4(position.xy, 0.0, 1.0);
+    }
+"#;
+
+/// Same as `Sky::colour`
+const SKY_FRAGMENT_SHADER_SRC: &str = r#"
+    #version 140
+
+    in vec2 ndc;
+
+    uniform vec3 right;
+    uniform vec3 up;
+    uniform vec3 forward;
+    uniform vec3 zenith;
+    uniform vec3 horizon;
+    uniform vec3 sun_direction;
+    uniform vec3 moon_direction;
+
+    out vec4 color;
+
+    const vec3 SUN_COLOUR = vec3(1.0, 0.95, 0.75);
+    const vec3 MOON_COLOUR = vec3(0.85, 0.88, 0.95);
+
+    void main() {
+        vec3 direction = normalize(forward + ndc.x * right + ndc.y * up);
+        vec3 colour = mix(horizon, zenith, sqrt(max(direction.y, 0.0)));
+
+        float sun = dot(direction, sun_direction);
+        if (sun > 0.9990) {
+            colour = SUN_COLOUR;
+        } else if (dot(direction, moon_direction) > 0.9994) {
+            colour = MOON_COLOUR;
+        } else {
+            colour = mix(colour, SUN_COLOUR, pow(max(sun, 0.0), 64.0) * 0.5);
+        }
+        color = vec4(colour, 1.0);
+    }
+"#;
+
+/// A triangle covering the whole screen
+const SKY_TRIANGLE: [Vertex; 3] = [
+    Vertex { position: (-1.0, -1.0, 0.0) },
+    Vertex { position: (3.0, -1.0, 0.0) },
+    Vertex { position: (-1.0, 3.0, 0.0) },
+];
+
+/// Converts the pixels of the glyphs, from the top left corner, to normalized device coordinates
+const TEXT_VERTEX_SHADER_SRC: &str = r#"
+    #version 140
+
+    in vec2 position;
+    in vec2 tex_coords;
+    in vec4 colour;
+
+    out vec2 v_tex_coords;
+    out vec4 v_colour;
+
+    uniform vec2 screen;
+
+    void main() {
+        v_tex_coords = tex_coords;
+        v_colour = colour;
+        gl_Position = vec4(position.x / screen.x * 2.0 - 1.0, 1.0 - position.y / screen.y * 2.0, 0.0, 1.0);
+    }
+"#;
+
+const TEXT_FRAGMENT_SHADER_SRC: &str = r#"
+    #version 140
+
+    in vec2 v_tex_coords;
+    in vec4 v_colour;
+
+    uniform sampler2D atlas;
+
+    out vec4 color;
+
+    void main() {
+        color = v_colour * texture(atlas, v_tex_coords);
+    }
+"#;
+
+#[derive(Copy, Clone)]
+struct GlyphVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    colour: [f32; 4],
+}
+implement_vertex!(GlyphVertex, position, tex_coords, colour);
+
+type ModelBuffers = (VertexBuffer<Vertex>, IndexBuffer<u32>);
+
+/// Programs and buffers needed to show a `World` with glium, independent of where it is drawn
+pub struct Scene {
+    program: Program,
+    instanced_program: Program,
+    sky_program: Program,
+    sky_triangle: VertexBuffer<Vertex>,
+    /// Buffers of the instanced models, by model id
+    models: RefCell<HashMap<usize, ModelBuffers>>,
+    text_program: Program,
+    /// Atlas of the font, uploaded when the first text is drawn. There is only one font
+    font: RefCell<Option<Texture2d>>,
+}
+
+impl Scene {
+    pub fn new<F: Facade>(facade: &F) -> Scene {
+        let program = glium::Program::from_source(facade, VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC, None).unwrap();
+        let instanced_program = glium::Program::from_source(facade, INSTANCED_VERTEX_SHADER_SRC, INSTANCED_FRAGMENT_SHADER_SRC, None).unwrap();
+        let sky_program = glium::Program::from_source(facade, SKY_VERTEX_SHADER_SRC, SKY_FRAGMENT_SHADER_SRC, None).unwrap();
+        let text_program = glium::Program::from_source(facade, TEXT_VERTEX_SHADER_SRC, TEXT_FRAGMENT_SHADER_SRC, None).unwrap();
+
+        Scene {
+            program,
+            instanced_program,
+            sky_program,
+            sky_triangle: VertexBuffer::new(facade, &SKY_TRIANGLE).unwrap(),
+            models: RefCell::new(HashMap::new()),
+            text_program,
+            font: RefCell::new(None),
+        }
+    }
+
+    /// Returns the first error of the draws, the others still happen
+    pub fn draw<F: Facade, S: Surface>(&self, facade: &F, target: &mut S, world: &mut World) -> Result<FrameStats, DrawError> {
+        let mut backend = GliumBackend {
+            facade,
+            target,
+            scene: self,
+            error: None,
+        };
+        let stats = backend::draw_world(&mut backend, world);
+        match backend.error {
+            Some(error) => Err(error),
+            None => Ok(stats),
+        }
+    }
+}
+
+/// `DepthMode::Reversed` needs the depth of the clip space in [0, 1], from OpenGL 4.5 or ARB_clip_control
+pub fn supports_reversed_depth<F: Facade>(facade: &F) -> bool {
+    let context = facade.get_context();
+    *context.get_opengl_version() >= Version(Api::Gl, 4, 5) || context.get_extensions().gl_arb_clip_control
+}
+
+/// `RenderBackend` drawing on any glium surface
+pub struct GliumBackend<'a, F: Facade, S: Surface> {
+    facade: &'a F,
+    target: &'a mut S,
+    scene: &'a Scene,
+    error: Option<DrawError>,
+}
+
+impl<F: Facade, S: Surface> GliumBackend<'_, F, S> {
+    /// Keeps the first error, `RenderBackend` has no way to return them
+    fn check(&mut self, result: Result<(), DrawError>) {
+        if let (None, Err(error)) = (&self.error, result) {
+            self.error = Some(error);
+        }
+    }
+}
+
+fn parameters(depth: DepthMode, polygon_mode: draw_parameters::PolygonMode) -> DrawParameters<'static> {
+    let (test, clip_control_depth) = match depth {
+        DepthMode::Standard => (draw_parameters::DepthTest::IfLess, draw_parameters::ClipControlDepth::NegativeOneToOne),
+        DepthMode::Reversed => (draw_parameters::DepthTest::IfMore, draw_parameters::ClipControlDepth::ZeroToOne),
+    };
+
+    DrawParameters {
+        depth: glium::Depth {
+            test,
+            write: true,
+            .. Default::default()
+        },
+        clip_control_depth,
+        polygon_mode,
+        line_width: Some(1.0),
+        .. Default::default()
+    }
+}
+
+impl<F: Facade, S: Surface> RenderBackend for GliumBackend<'_, F, S> {
+    fn dimensions(&self) -> (u32, u32) {
+        self.target.get_dimensions()
+    }
+
+    fn clear(&mut self, color: [f32; 4], depth: DepthMode) {
+        self.target.clear_color_and_depth((color[0], color[1], color[2], color[3]), depth.clear_value());
+    }
+
+    fn draw_sky(&mut self, sky: &Sky, rays: &Rays) {
+        // no depth test, the sky is behind everything
+        let result = self.target.draw(&self.scene.sky_triangle, glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &self.scene.sky_program,
+            &uniform! {
+                right: rays.right, up: rays.up, forward: rays.forward,
+                zenith: sky.zenith, horizon: sky.horizon,
+                sun_direction: sky.sun_direction, moon_direction: sky.moon_direction,
+            },
+        &Default::default());
+        self.check(result);
+    }
+
+    fn draw_glyphs(&mut self, font: &Font, glyphs: &[Glyph]) {
+        let mut texture = self.scene.font.borrow_mut();
+        let texture = texture.get_or_insert_with(|| {
+            // the first row of the image stays the first row of the texture, at v = 0
+            let image = RawImage2d::from_raw_rgba(font.atlas.as_raw().clone(), font.atlas.dimensions());
+            Texture2d::new(self.facade, image).unwrap()
+        });
+
+        let mut vertices = Vec::with_capacity(glyphs.len() * 4);
+        let mut indices = Vec::with_capacity(glyphs.len() * 6);
+        for glyph in glyphs {
+            let [left, top, right, bottom] = glyph.screen;
+            let [u0, v0, u1, v1] = glyph.atlas;
+            let start = vertices.len() as u32;
+            for (position, tex_coords) in [([left, top], [u0, v0]), ([right, top], [u1, v0]), ([right, bottom], [u1, v1]), ([left, bottom], [u0, v1])] {
+                vertices.push(GlyphVertex { position, tex_coords, colour: glyph.colour });
+            }
+            indices.extend([0, 1, 2, 0, 2, 3].map(|i| start + i));
+        }
+        let vertices = glium::VertexBuffer::new(self.facade, &vertices).unwrap();
+        let indices = glium::IndexBuffer::new(self.facade, glium::index::PrimitiveType::TrianglesList, &indices).unwrap();
+
+        let (width, height) = self.target.get_dimensions();
+        let atlas = texture.sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest);
+        let params = DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+        let result = self.target.draw(&vertices, &indices, &self.scene.text_program,
+            &uniform! { screen: [width as f32, height as f32], atlas: atlas },
+        &params);
+        self.check(result);
+    }
+
+    fn draw(&mut self, object: &Object, uniforms: &Uniforms, style: Style) {
+        let positions = glium::VertexBuffer::new(self.facade, &object.vertices).unwrap();
+        // the program always needs them, missing ones are white and zero
+        let colours = if object.colours.len() == object.vertices.len() {
+            glium::VertexBuffer::new(self.facade, &object.colours).unwrap()
+        } else {
+            glium::VertexBuffer::new(self.facade, &vec![Colour { colour: [1.0; 4] }; object.vertices.len()]).unwrap()
+        };
+        let tex_coords = if object.tex_coords.len() == object.vertices.len() {
+            glium::VertexBuffer::new(self.facade, &object.tex_coords).unwrap()
+        } else {
+            glium::VertexBuffer::new(self.facade, &vec![TexCoord { tex_coords: (0.0, 0.0) }; object.vertices.len()]).unwrap()
+        };
+        let indices = glium::IndexBuffer::new(self.facade, glium::index::PrimitiveType::TrianglesList,
+                                            &object.indices).unwrap();
+
+        let (tint, polygon_mode, style_id) = match style {
+            Style::Fill(colour) | Style::Blend(colour) => (colour, draw_parameters::PolygonMode::Fill, 0),
+            Style::Wireframe(colour) => (colour, draw_parameters::PolygonMode::Line, 1),
+            Style::Cutout(colour) => (colour, draw_parameters::PolygonMode::Fill, 2),
+        };
+
+        let mut params = parameters(uniforms.depth, polygon_mode);
+        if let Style::Blend(_) = style {
+            params.blend = glium::Blend::alpha_blending();
+            params.depth.write = false;
+        }
+
+        let result = self.target.draw((&positions, &colours, &tex_coords), &indices, &self.scene.program,
+            &uniform! {
+                model: uniforms.model, view: uniforms.view, perspective: uniforms.perspective,
+                tint: tint, style: style_id,
+                fog_mode: uniforms.fog.mode_id(), fog_start: uniforms.fog.start, fog_end: uniforms.fog.end,
+                fog_density: uniforms.fog.density(), fog_colour: uniforms.fog.colour,
+            },
+        &params);
+        self.check(result);
+    }
+    fn draw_instanced(&mut self, model_id: usize, object: &Object, instances: &[Instance], uniforms: &Uniforms) {
+        let mut models = self.scene.models.borrow_mut();
+        let (positions, indices) = models.entry(model_id).or_insert_with(|| (
+            glium::VertexBuffer::new(self.facade, &object.vertices).unwrap(),
+            glium::IndexBuffer::new(self.facade, glium::index::PrimitiveType::TrianglesList, &object.indices).unwrap(),
+        ));
+        let per_instance = glium::VertexBuffer::new(self.facade, instances).unwrap();
+
+        let params = parameters(uniforms.depth, draw_parameters::PolygonMode::Fill);
+
+        let result = self.target.draw((&*positions, per_instance.per_instance().unwrap()), &*indices, &self.scene.instanced_program,
+            &uniform! {
+                view: uniforms.view, perspective: uniforms.perspective,
+                fog_mode: uniforms.fog.mode_id(), fog_start: uniforms.fog.start, fog_end: uniforms.fog.end,
+                fog_density: uniforms.fog.density(), fog_colour: uniforms.fog.colour,
+            },
+        &params);
+        self.check(result);
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glium::backend::Facade;
+use glium::DrawError;
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use image::RgbaImage;
+
+use crate::render::backend::FrameStats;
+use crate::render::scene::Scene;
+use crate::world::world::World;
+
+pub const DIRECTORY: &str = "screenshots";
+
+/// Colour texture and depth buffer to draw offscreen. The depth is in floats, which
+/// `DepthMode::Reversed` needs to be any better than the standard depth
+pub fn buffers<F: Facade>(facade: &F, dimensions: (u32, u32)) -> (Texture2d, DepthRenderBuffer) {
+    let (width, height) = dimensions;
+    let color = Texture2d::empty_with_format(facade, UncompressedFloatFormat::U8U8U8U8,
+                                             MipmapsOption::NoMipmap, width, height).unwrap();
+    let depth = DepthRenderBuffer::new(facade, DepthFormat::F32, width, height).unwrap();
+    (color, depth)
+}
+
+/// Draws the world into an offscreen framebuffer and reads it back
+pub fn capture<F: Facade>(facade: &F, scene: &Scene, world: &mut World, dimensions: (u32, u32)) -> Result<(RgbaImage, FrameStats), DrawError> {
+    let (color, depth) = buffers(facade, dimensions);
+    let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(facade, &color, &depth).unwrap();
+
+    let stats = scene.draw(facade, &mut framebuffer, world)?;
+
+    let pixels: RawImage2d<u8> = color.read();
+    let image = RgbaImage::from_raw(pixels.width, pixels.height, pixels.data.into_owned()).unwrap();
+
+    // OpenGL rows start at the bottom
+    Ok((image::imageops::flip_vertical(&image), stats))
+}
+
+pub fn save(image: &RgbaImage, path: &Path) -> image::ImageResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image.save(path)
+}
+
+/// `screenshots/<unix time in ms>.png`
+pub fn timestamped_path() -> PathBuf {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    Path::new(DIRECTORY).join(format!("{}.png", millis))
+}
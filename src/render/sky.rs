@@ -0,0 +1,124 @@
+use crate::render::matrix::Matrix;
+use crate::world::time::{WorldTime, MIN_SKY_LIGHT};
+
+const DAY_ZENITH: [f32; 3] = [0.25, 0.5, 0.95];
+const DAY_HORIZON: [f32; 3] = [0.7, 0.85, 1.0];
+const NIGHT_ZENITH: [f32; 3] = [0.01, 0.01, 0.04];
+const NIGHT_HORIZON: [f32; 3] = [0.04, 0.05, 0.1];
+const TWILIGHT: [f32; 3] = [1.0, 0.45, 0.2];
+const SUN_COLOUR: [f32; 3] = [1.0, 0.95, 0.75];
+const MOON_COLOUR: [f32; 3] = [0.85, 0.88, 0.95];
+
+/// Cosine of the angular radius of the discs
+const SUN_SIZE: f32 = 0.9990;
+const MOON_SIZE: f32 = 0.9994;
+
+/// Colours of the sky for a moment of the day, shared by the shader and the CPU rasterizer
+#[derive(Clone, Copy, Debug)]
+pub struct Sky {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    pub sun_direction: [f32; 3],
+    pub moon_direction: [f32; 3],
+    /// Sky light intensity, applied to the blocks and the props
+    pub light: f32,
+}
+
+/// Direction of the ray through a pixel, as `forward + x * right + y * up` with x and y
+/// in normalized device coordinates
+#[derive(Clone, Copy, Debug)]
+pub struct Rays {
+    pub right: [f32; 3],
+    pub up: [f32; 3],
+    pub forward: [f32; 3],
+}
+
+impl Sky {
+    pub fn new(time: &WorldTime) -> Sky {
+        let light = time.sky_light();
+        // 0 at night, 1 by day
+        let day = (light - MIN_SKY_LIGHT) / (1.0 - MIN_SKY_LIGHT);
+        let sun_direction = time.sun_direction();
+        // the horizon turns orange while the sun is close to it
+        let twilight = (1.0 - sun_direction[1].abs() * 5.0).max(0.0) * 0.6;
+
+        Sky {
+            zenith: mix(NIGHT_ZENITH, DAY_ZENITH, day),
+            horizon: mix(mix(NIGHT_HORIZON, DAY_HORIZON, day), TWILIGHT, twilight),
+            sun_direction,
+            moon_direction: time.moon_direction(),
+            light,
+        }
+    }
+
+    /// Sky of a biome, the fog follows as it takes the colour of the horizon
+    pub fn tinted(self, tint: [f32; 3]) -> Sky {
+        let tint = |colour: [f32; 3]| std::array::from_fn(|i| (colour[i] * tint[i]).min(1.0));
+        Sky {
+            zenith: tint(self.zenith),
+            horizon: tint(self.horizon),
+            ..self
+        }
+    }
+
+    /// Colour seen in the direction, which does not have to be normalized
+    pub fn colour(&self, direction: [f32; 3]) -> [f32; 4] {
+        let direction = normalize(direction);
+        let height = direction[1].max(0.0).sqrt();
+        let mut colour = mix(self.horizon, self.zenith, height);
+
+        let sun = dot(direction, self.sun_direction);
+        if sun > SUN_SIZE {
+            colour = SUN_COLOUR;
+        } else if dot(direction, self.moon_direction) > MOON_SIZE {
+            colour = MOON_COLOUR;
+        } else {
+            // halo around the sun
+            let glow = sun.max(0.0).powi(64) * 0.5;
+            colour = mix(colour, SUN_COLOUR, glow);
+        }
+        [colour[0], colour[1], colour[2], 1.0]
+    }
+
+    /// Multiplies a colour by the sky light, the alpha is kept
+    pub fn lit(&self, colour: [f32; 4]) -> [f32; 4] {
+        [colour[0] * self.light, colour[1] * self.light, colour[2] * self.light, colour[3]]
+    }
+}
+
+impl Rays {
+    /// Camera rays in world space. An orthographic projection has parallel rays,
+    /// so the whole sky has the colour seen straight ahead
+    pub fn new(view: &Matrix, perspective: &Matrix) -> Rays {
+        // the rows of the rotation of the view are the axes of the camera
+        let axis = |row: usize| [view[0][row], view[1][row], view[2][row]];
+        let perspective_projection = perspective[2][3] != 0.0;
+        let (x, y) = if perspective_projection {
+            (1.0 / perspective[0][0], 1.0 / perspective[1][1])
+        } else {
+            (0.0, 0.0)
+        };
+        Rays {
+            right: axis(0).map(|coord| coord * x),
+            up: axis(1).map(|coord| coord * y),
+            forward: axis(2),
+        }
+    }
+
+    pub fn at(&self, x: f32, y: f32) -> [f32; 3] {
+        std::array::from_fn(|i| self.forward[i] + x * self.right[i] + y * self.up[i])
+    }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    v.map(|coord| coord / length)
+}
@@ -0,0 +1,400 @@
+use image::{Rgba, RgbaImage};
+
+use crate::models::object::Object;
+use crate::render::backend::{self, RenderBackend, Style, Uniforms};
+use crate::render::fog::Fog;
+use crate::render::matrix::{self, Matrix};
+use crate::render::projection::DepthMode;
+use crate::render::sky::{Rays, Sky};
+use crate::render::text::{Font, Glyph};
+
+/// Wireframe lines are drawn on top of the faces they belong to, like glPolygonOffset would
+const LINE_DEPTH_BIAS: f32 = 1e-5;
+
+/// CPU triangle rasterizer with a depth buffer, for machines without GPU.
+/// Follows the OpenGL conventions so images match the glium path.
+pub struct Rasterizer {
+    image: RgbaImage,
+    depth: Vec<f32>,
+    depth_mode: DepthMode,
+}
+
+/// Values interpolated over the triangles: fog visibility, colour and texture coordinates
+const VARYINGS: usize = 7;
+
+/// Position in clip space followed by the varyings, clipped like the position
+type ClipVertex = [f32; 4 + VARYINGS];
+
+/// Vertex after the perspective division, in pixels, with the value of the depth buffer
+#[derive(Clone, Copy, Debug)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    depth: f32,
+    /// 1 / w, the varyings are divided by w so they can be interpolated in screen space
+    /// and stay perspective correct like on the GPU
+    inverse_w: f32,
+    varyings: [f32; VARYINGS],
+}
+
+/// What a pixel of a triangle looks like before the fog
+#[derive(Clone, Copy, Debug)]
+struct Fragment {
+    visibility: f32,
+    colour: [f32; 4],
+    tex_coords: [f32; 2],
+}
+
+impl Fragment {
+    fn new(varyings: [f32; VARYINGS]) -> Fragment {
+        Fragment {
+            visibility: varyings[0],
+            colour: [varyings[1], varyings[2], varyings[3], varyings[4]],
+            tex_coords: [varyings[5], varyings[6]],
+        }
+    }
+}
+
+impl Rasterizer {
+    pub fn new(dimensions: (u32, u32)) -> Rasterizer {
+        let (width, height) = dimensions;
+        Rasterizer {
+            image: RgbaImage::new(width, height),
+            depth: vec![1.0; (width * height) as usize],
+            depth_mode: DepthMode::Standard,
+        }
+    }
+
+    pub fn into_image(self) -> RgbaImage {
+        self.image
+    }
+
+    fn to_screen(&self, clip: ClipVertex) -> ScreenVertex {
+        let (width, height) = self.image.dimensions();
+        let x = clip[0] / clip[3];
+        let y = clip[1] / clip[3];
+        let z = clip[2] / clip[3];
+        ScreenVertex {
+            x: (x + 1.0) / 2.0 * width as f32,
+            // the first row of the image is the top of the screen
+            y: (1.0 - y) / 2.0 * height as f32,
+            depth: match self.depth_mode {
+                DepthMode::Standard => (z + 1.0) / 2.0,
+                DepthMode::Reversed => z,
+            },
+            inverse_w: 1.0 / clip[3],
+            varyings: std::array::from_fn(|i| clip[4 + i] / clip[3]),
+        }
+    }
+
+    /// Writes the pixel if it passes the depth test
+    fn plot(&mut self, x: i64, y: i64, depth: f32, color: [f32; 4], blend: bool) {
+        let (width, height) = self.image.dimensions();
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 || !(0.0..=1.0).contains(&depth) {
+            return;
+        }
+        let index = (y as u32 * width + x as u32) as usize;
+        let closer = match self.depth_mode {
+            DepthMode::Standard => depth < self.depth[index],
+            DepthMode::Reversed => depth > self.depth[index],
+        };
+        if !closer {
+            return;
+        }
+        if blend {
+            // the depth is not written, like the translucent pass on the GPU
+            let below = self.image.get_pixel(x as u32, y as u32).0.map(|channel| channel as f32 / 255.0);
+            let alpha = color[3];
+            let mixed = std::array::from_fn(|i| color[i] * alpha + below[i] * (1.0 - alpha));
+            self.image.put_pixel(x as u32, y as u32, to_rgba(mixed));
+        } else {
+            self.depth[index] = depth;
+            self.image.put_pixel(x as u32, y as u32, to_rgba(color));
+        }
+    }
+
+    fn fill_triangle(&mut self, [a, b, c]: [ScreenVertex; 3], style: Style, fog: &Fog) {
+        let area = edge(a, b, c.x, c.y);
+        if area == 0.0 {
+            return;
+        }
+
+        let (width, height) = self.image.dimensions();
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as i64;
+        let max_x = a.x.max(b.x).max(c.x).ceil().min(width as f32) as i64;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as i64;
+        let max_y = a.y.max(b.y).max(c.y).ceil().min(height as f32) as i64;
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // sample at the center of the pixel
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let wa = edge(b, c, px, py) / area;
+                let wb = edge(c, a, px, py) / area;
+                let wc = edge(a, b, px, py) / area;
+                if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                    continue;
+                }
+                let depth = wa * a.depth + wb * b.depth + wc * c.depth;
+                let w = 1.0 / (wa * a.inverse_w + wb * b.inverse_w + wc * c.inverse_w);
+                let fragment = Fragment::new(std::array::from_fn(|i| {
+                    (wa * a.varyings[i] + wb * b.varyings[i] + wc * c.varyings[i]) * w
+                }));
+                let (tint, blend) = match style {
+                    Style::Cutout(tint) => {
+                        if !backend::cutout_visible(fragment.colour[3], fragment.tex_coords) {
+                            continue;
+                        }
+                        ([tint[0], tint[1], tint[2], 1.0], false)
+                    }
+                    Style::Blend(tint) => (tint, true),
+                    Style::Fill(tint) | Style::Wireframe(tint) => (tint, false),
+                };
+                let colour = std::array::from_fn(|i| match style {
+                    Style::Cutout(_) if i == 3 => tint[3],
+                    _ => tint[i] * fragment.colour[i],
+                });
+                self.plot(x, y, depth, fog.apply(colour, fragment.visibility), blend);
+            }
+        }
+    }
+
+    fn draw_line(&mut self, a: ScreenVertex, b: ScreenVertex, color: [f32; 4], fog: &Fog) {
+        let steps = (b.x - a.x).abs().max((b.y - a.y).abs()).ceil().max(1.0) as i64;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let x = a.x + (b.x - a.x) * t;
+            let y = a.y + (b.y - a.y) * t;
+            let depth = a.depth + (b.depth - a.depth) * t;
+            let depth = match self.depth_mode {
+                DepthMode::Standard => depth - LINE_DEPTH_BIAS,
+                DepthMode::Reversed => depth + LINE_DEPTH_BIAS,
+            };
+            let inverse_w = a.inverse_w + (b.inverse_w - a.inverse_w) * t;
+            let visibility = (a.varyings[0] + (b.varyings[0] - a.varyings[0]) * t) / inverse_w;
+            self.plot(x.floor() as i64, y.floor() as i64, depth, fog.apply(color, visibility), false);
+        }
+    }
+}
+
+impl RenderBackend for Rasterizer {
+    fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn clear(&mut self, color: [f32; 4], depth: DepthMode) {
+        let color = to_rgba(color);
+        for pixel in self.image.pixels_mut() {
+            *pixel = color;
+        }
+        self.depth.fill(depth.clear_value());
+        self.depth_mode = depth;
+    }
+
+    fn draw_sky(&mut self, sky: &Sky, rays: &Rays) {
+        let (width, height) = self.image.dimensions();
+        for (x, y, pixel) in self.image.enumerate_pixels_mut() {
+            // same sample position as the fragment shader
+            let ndc_x = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let ndc_y = 1.0 - (y as f32 + 0.5) / height as f32 * 2.0;
+            *pixel = to_rgba(sky.colour(rays.at(ndc_x, ndc_y)));
+        }
+    }
+
+    fn draw_glyphs(&mut self, font: &Font, glyphs: &[Glyph]) {
+        let (width, height) = self.image.dimensions();
+        let (atlas_width, atlas_height) = font.atlas.dimensions();
+        for glyph in glyphs {
+            let [left, top, right, bottom] = glyph.screen;
+            let [u0, v0, u1, v1] = glyph.atlas;
+            // pixels whose center is inside, like the GPU
+            let columns = (left - 0.5).ceil().max(0.0) as u32..((right - 0.5).ceil().max(0.0) as u32).min(width);
+            let rows = (top - 0.5).ceil().max(0.0) as u32..((bottom - 0.5).ceil().max(0.0) as u32).min(height);
+            for y in rows {
+                let v = v0 + (y as f32 + 0.5 - top) / (bottom - top) * (v1 - v0);
+                let texel_y = ((v * atlas_height as f32) as u32).min(atlas_height - 1);
+                for x in columns.clone() {
+                    let u = u0 + (x as f32 + 0.5 - left) / (right - left) * (u1 - u0);
+                    let texel_x = ((u * atlas_width as f32) as u32).min(atlas_width - 1);
+                    let texel = font.atlas.get_pixel(texel_x, texel_y).0;
+                    let colour: [f32; 4] = std::array::from_fn(|i| glyph.colour[i] * texel[i] as f32 / 255.0);
+                    let below = self.image.get_pixel(x, y).0.map(|channel| channel as f32 / 255.0);
+                    let alpha = colour[3];
+                    let mixed = std::array::from_fn(|i| colour[i] * alpha + below[i] * (1.0 - alpha));
+                    self.image.put_pixel(x, y, to_rgba(mixed));
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, object: &Object, uniforms: &Uniforms, style: Style) {
+        let modelview: Matrix = matrix::multiply(&uniforms.view, &uniforms.model);
+        let clip: Vec<ClipVertex> = object.vertices.iter().enumerate()
+            .map(|(i, vertex)| {
+                let (x, y, z) = vertex.position;
+                let view_position = matrix::transform(&modelview, [x, y, z, 1.0]);
+                let [x, y, z, _] = view_position;
+                let visibility = uniforms.fog.visibility((x * x + y * y + z * z).sqrt());
+                let [cx, cy, cz, cw] = matrix::transform(&uniforms.perspective, view_position);
+                // missing colours are white, like in the glium path
+                let [r, g, b, a] = object.colours.get(i).map_or([1.0; 4], |colour| colour.colour);
+                let (u, v) = object.tex_coords.get(i).map_or((0.0, 0.0), |uv| uv.tex_coords);
+                [cx, cy, cz, cw, visibility, r, g, b, a, u, v]
+            })
+            .collect();
+
+        for triangle in object.indices.chunks_exact(3) {
+            let corners = [clip[triangle[0] as usize], clip[triangle[1] as usize], clip[triangle[2] as usize]];
+            match style {
+                Style::Fill(_) | Style::Cutout(_) | Style::Blend(_) => {
+                    let polygon: Vec<ScreenVertex> = clip_polygon(&corners, uniforms.depth).into_iter()
+                        .map(|vertex| self.to_screen(vertex))
+                        .collect();
+                    // the clipped polygon is convex, draw it as a fan
+                    for i in 1..polygon.len().saturating_sub(1) {
+                        self.fill_triangle([polygon[0], polygon[i], polygon[i + 1]], style, &uniforms.fog);
+                    }
+                }
+                Style::Wireframe(color) => {
+                    for i in 0..3 {
+                        if let Some((a, b)) = clip_segment(corners[i], corners[(i + 1) % 3], uniforms.depth) {
+                            let (a, b) = (self.to_screen(a), self.to_screen(b));
+                            self.draw_line(a, b, color, &uniforms.fog);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn to_rgba(color: [f32; 4]) -> Rgba<u8> {
+    Rgba(color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+/// Twice the signed area of the triangle (a, b, p)
+fn edge(a: ScreenVertex, b: ScreenVertex, px: f32, py: f32) -> f32 {
+    (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x)
+}
+
+/// Signed distance to the near plane in clip space, positive when visible
+fn near_distance(vertex: ClipVertex, depth: DepthMode) -> f32 {
+    match depth {
+        DepthMode::Standard => vertex[3] + vertex[2],
+        DepthMode::Reversed => vertex[3] - vertex[2],
+    }
+}
+
+fn lerp(a: ClipVertex, b: ClipVertex, t: f32) -> ClipVertex {
+    core::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// Sutherland-Hodgman against the near plane only, the other planes are handled by
+/// clamping the rasterized area to the image
+fn clip_polygon(corners: &[ClipVertex], depth: DepthMode) -> Vec<ClipVertex> {
+    let mut result = vec![];
+    for i in 0..corners.len() {
+        let current = corners[i];
+        let next = corners[(i + 1) % corners.len()];
+        let (d_current, d_next) = (near_distance(current, depth), near_distance(next, depth));
+
+        if d_current >= 0.0 {
+            result.push(current);
+        }
+        if (d_current >= 0.0) != (d_next >= 0.0) {
+            result.push(lerp(current, next, d_current / (d_current - d_next)));
+        }
+    }
+    result
+}
+
+fn clip_segment(a: ClipVertex, b: ClipVertex, depth: DepthMode) -> Option<(ClipVertex, ClipVertex)> {
+    let (d_a, d_b) = (near_distance(a, depth), near_distance(b, depth));
+    match (d_a >= 0.0, d_b >= 0.0) {
+        (true, true) => Some((a, b)),
+        (false, false) => None,
+        (true, false) => Some((a, lerp(a, b, d_a / (d_a - d_b)))),
+        (false, true) => Some((lerp(a, b, d_a / (d_a - d_b)), b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::object::Vertex;
+    use crate::render::projection::Projection;
+    use crate::settings::FogMode;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+    const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+    fn uniforms(perspective: Matrix, depth: DepthMode) -> Uniforms {
+        Uniforms {
+            model: matrix::IDENTITY,
+            view: matrix::IDENTITY,
+            perspective,
+            depth,
+            fog: Fog { mode: FogMode::Off, colour: [0.0; 3], start: 0.0, end: 1.0 },
+        }
+    }
+
+    fn triangles(positions: &[(f32, f32, f32)]) -> Object {
+        let vertices = positions.iter().map(|&position| Vertex { position }).collect();
+        Object::new(vertices, vec![], (0..positions.len() as u32).collect())
+    }
+
+    /// One triangle over the whole screen at the depth `z`, with the identity as projection
+    fn screen(z: f32) -> Object {
+        triangles(&[(-1.0, -1.0, z), (3.0, -1.0, z), (-1.0, 3.0, z)])
+    }
+
+    #[test]
+    fn fills_the_pixels_inside_the_triangle() {
+        let mut rasterizer = Rasterizer::new((8, 8));
+        rasterizer.clear([0.0, 0.0, 0.0, 1.0], DepthMode::Standard);
+        let triangle = triangles(&[(-1.0, -1.0, 0.0), (1.0, -1.0, 0.0), (-1.0, 1.0, 0.0)]);
+        rasterizer.draw(&triangle, &uniforms(matrix::IDENTITY, DepthMode::Standard), Style::Fill(RED));
+        let image = rasterizer.into_image();
+        // the lower left half, with the pixels centered on the diagonal
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let expected = if y >= x { to_rgba(RED) } else { to_rgba([0.0, 0.0, 0.0, 1.0]) };
+            assert_eq!(*pixel, expected, "pixel {} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn keeps_the_closest_pixels() {
+        for (depth, [far, near, farther, nearest]) in [(DepthMode::Standard, [0.5, -0.5, 0.8, -0.6]), (DepthMode::Reversed, [0.3, 0.6, 0.1, 0.7])] {
+            let mut rasterizer = Rasterizer::new((4, 4));
+            rasterizer.clear([0.0; 4], depth);
+            let uniforms = uniforms(matrix::IDENTITY, depth);
+            rasterizer.draw(&screen(far), &uniforms, Style::Fill(RED));
+            rasterizer.draw(&screen(near), &uniforms, Style::Fill(GREEN));
+            rasterizer.draw(&screen(farther), &uniforms, Style::Fill(BLUE));
+            // blending does not write the depth
+            rasterizer.draw(&screen(nearest), &uniforms, Style::Blend([0.0, 0.0, 1.0, 0.5]));
+            rasterizer.draw(&screen(farther), &uniforms, Style::Fill(BLUE));
+            let expected = to_rgba([0.0, 0.5, 0.5, 0.75]);
+            assert!(rasterizer.into_image().pixels().all(|pixel| *pixel == expected), "{:?}", depth);
+        }
+    }
+
+    #[test]
+    fn clips_the_triangles_behind_the_camera() {
+        for depth in [DepthMode::Standard, DepthMode::Reversed] {
+            let projection = Projection { depth, ..Projection::new() };
+            let uniforms = uniforms(projection.matrix((16, 16)), depth);
+            let mut rasterizer = Rasterizer::new((16, 16));
+            rasterizer.clear([0.0; 4], depth);
+            // entirely behind, then a floor going from behind the camera to far in front of it
+            rasterizer.draw(&triangles(&[(-1.0, -1.0, -2.0), (1.0, -1.0, -2.0), (0.0, 1.0, -2.0)]), &uniforms, Style::Fill(RED));
+            rasterizer.draw(&triangles(&[(-10.0, -1.0, -5.0), (10.0, -1.0, -5.0), (0.0, -1.0, 100.0)]), &uniforms, Style::Fill(GREEN));
+            let image = rasterizer.into_image();
+            // below the horizon only, nothing drawn upside down
+            assert_eq!(*image.get_pixel(8, 15), to_rgba(GREEN), "{:?}", depth);
+            assert!((0..16).all(|x| (0..8).all(|y| image.get_pixel(x, y).0 == [0; 4])), "{:?}", depth);
+            assert!(image.pixels().all(|pixel| *pixel != to_rgba(RED)));
+        }
+    }
+}
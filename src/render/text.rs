@@ -0,0 +1,281 @@
+//! Bitmap fonts: an atlas image with one cell per character, drawn as quads in screen space.
+//! The fonts are monospace, so the layout works in cells and only `Font::glyphs` knows pixels
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+/// Cells of the atlas on each row and each column, for the characters from `FIRST` to `SOLID`
+pub const COLUMNS: u32 = 16;
+pub const ROWS: u32 = 6;
+pub const FIRST: char = ' ';
+/// Its cell is filled, for the backgrounds
+pub const SOLID: char = '\u{7f}';
+/// Drawn for the characters missing from the atlas
+const REPLACEMENT: char = '?';
+
+pub struct Font {
+    /// White characters, their coverage in the alpha channel
+    pub atlas: RgbaImage,
+    /// Size of a cell in pixels
+    pub cell: (u32, u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Where a character of a text goes, in cells from the top left corner of its box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placed {
+    pub character: char,
+    pub column: usize,
+    pub line: usize,
+}
+
+/// A quad to draw, with the colour multiplied by the atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    /// Left, top, right and bottom in pixels, from the top left corner of the screen
+    pub screen: [f32; 4],
+    /// Same corners in the atlas, from 0 to 1 with the first row of the image at 0
+    pub atlas: [f32; 4],
+    pub colour: [f32; 4],
+}
+
+pub struct Text<'a> {
+    pub content: &'a str,
+    /// Top left corner of the box, in pixels
+    pub position: [f32; 2],
+    /// Pixels on the screen for a pixel of the atlas
+    pub scale: f32,
+    pub colour: [f32; 4],
+    pub align: Align,
+    /// Characters per line, None to only break the lines at `\n`
+    pub width: Option<usize>,
+}
+
+impl Font {
+    pub fn load(path: &Path) -> Result<Font, FontError> {
+        let atlas = image::open(path).map_err(|error| FontError::Image { path: path.to_path_buf(), error })?;
+        Font::from_image(atlas.to_rgba8())
+    }
+
+    pub fn from_image(atlas: RgbaImage) -> Result<Font, FontError> {
+        let (width, height) = atlas.dimensions();
+        if width == 0 || height == 0 || width % COLUMNS != 0 || height % ROWS != 0 {
+            return Err(FontError::Size { width, height });
+        }
+        Ok(Font {
+            atlas,
+            cell: (width / COLUMNS, height / ROWS),
+        })
+    }
+
+    /// Size of a text in pixels
+    pub fn size(&self, text: &Text) -> [f32; 2] {
+        let [columns, lines] = measure(text.content, text.width);
+        [(columns as u32 * self.cell.0) as f32 * text.scale, (lines as u32 * self.cell.1) as f32 * text.scale]
+    }
+
+    pub fn glyphs(&self, text: &Text) -> Vec<Glyph> {
+        let (width, height) = (self.cell.0 as f32 * text.scale, self.cell.1 as f32 * text.scale);
+        layout(text.content, text.width, text.align).into_iter()
+            .map(|placed| {
+                let left = text.position[0] + placed.column as f32 * width;
+                let top = text.position[1] + placed.line as f32 * height;
+                Glyph {
+                    screen: [left, top, left + width, top + height],
+                    atlas: self.cell_of(placed.character),
+                    colour: text.colour,
+                }
+            })
+            .collect()
+    }
+
+    /// A filled rectangle, `screen` like `Glyph::screen`
+    pub fn rectangle(&self, screen: [f32; 4], colour: [f32; 4]) -> Glyph {
+        // the center of the cell, so the corners cannot reach the next one
+        let [left, top, right, bottom] = self.cell_of(SOLID);
+        let (u, v) = ((left + right) / 2.0, (top + bottom) / 2.0);
+        Glyph {
+            screen,
+            atlas: [u, v, u, v],
+            colour,
+        }
+    }
+
+    fn cell_of(&self, character: char) -> [f32; 4] {
+        let character = if (FIRST..=SOLID).contains(&character) { character } else { REPLACEMENT };
+        let index = character as u32 - FIRST as u32;
+        let (column, row) = (index % COLUMNS, index / COLUMNS);
+        let (columns, rows) = (COLUMNS as f32, ROWS as f32);
+        [column as f32 / columns, row as f32 / rows, (column + 1) as f32 / columns, (row + 1) as f32 / rows]
+    }
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    Image { path: PathBuf, error: image::ImageError },
+    /// The atlas cannot be cut in `COLUMNS` x `ROWS` cells
+    Size { width: u32, height: u32 },
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::Image { path, error } => write!(f, "could not read {}: {}", path.display(), error),
+            FontError::Size { width, height } => write!(f, "the atlas must be {} cells wide and {} cells high, got {}x{} pixels", COLUMNS, ROWS, width, height),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// Lines of the text. `\n` always starts a new line, and with a width the lines are cut between
+/// words to be at most that many characters: the words are then separated by single spaces,
+/// and the words longer than a line are split
+pub fn wrap(text: &str, width: Option<usize>) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let Some(width) = width.filter(|width| *width > 0) else {
+            lines.push(paragraph.to_string());
+            continue;
+        };
+        let mut line: Vec<char> = vec![];
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            loop {
+                if line.is_empty() && word.len() > width {
+                    lines.push(word.drain(..width).collect());
+                } else if line.is_empty() {
+                    line = word;
+                    break;
+                } else if line.len() + 1 + word.len() <= width {
+                    line.push(' ');
+                    line.extend(word);
+                    break;
+                } else {
+                    lines.push(line.drain(..).collect());
+                }
+            }
+        }
+        lines.push(line.into_iter().collect());
+    }
+    lines
+}
+
+/// Columns and lines taken by the text: the width if there is one, else the longest line
+pub fn measure(text: &str, width: Option<usize>) -> [usize; 2] {
+    let lines = wrap(text, width);
+    let columns = width.unwrap_or_else(|| lines.iter().map(|line| line.chars().count()).max().unwrap_or(0));
+    [columns, lines.len()]
+}
+
+/// Places the characters of the wrapped lines, aligned in a box as wide as `measure` says.
+/// The spaces are left out, there is nothing to draw
+pub fn layout(text: &str, width: Option<usize>, align: Align) -> Vec<Placed> {
+    let [columns, _] = measure(text, width);
+    let mut placed = vec![];
+    for (line, content) in wrap(text, width).iter().enumerate() {
+        let free = columns.saturating_sub(content.chars().count());
+        let start = match align {
+            Align::Left => 0,
+            Align::Center => free / 2,
+            Align::Right => free,
+        };
+        for (column, character) in content.chars().enumerate() {
+            if character != ' ' {
+                placed.push(Placed { character, column: start + column, line });
+            }
+        }
+    }
+    placed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(text: &str, width: Option<usize>, align: Align) -> Vec<(char, usize, usize)> {
+        layout(text, width, align).into_iter().map(|placed| (placed.character, placed.column, placed.line)).collect()
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        assert_eq!(wrap("the quick brown fox", Some(10)), ["the quick", "brown fox"]);
+        assert_eq!(wrap("the   quick", Some(20)), ["the quick"]);
+        assert_eq!(wrap("a b c", Some(1)), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn splits_long_words() {
+        assert_eq!(wrap("abcdefgh ij", Some(3)), ["abc", "def", "gh", "ij"]);
+        assert_eq!(wrap("ab abcdef", Some(4)), ["ab", "abcd", "ef"]);
+    }
+
+    #[test]
+    fn keeps_line_breaks() {
+        assert_eq!(wrap("one\n\ntwo three", Some(5)), ["one", "", "two", "three"]);
+        assert_eq!(wrap("  as is  \nnext", None), ["  as is  ", "next"]);
+        assert_eq!(wrap("", Some(4)), [""]);
+    }
+
+    #[test]
+    fn measures_the_box() {
+        assert_eq!(measure("ab\nabcd", None), [4, 2]);
+        assert_eq!(measure("ab\nabcd", Some(10)), [10, 2]);
+        assert_eq!(measure("abcdef", Some(4)), [4, 2]);
+    }
+
+    #[test]
+    fn aligns_lines_in_the_box() {
+        assert_eq!(positions("ab\nabcd", None, Align::Left), [('a', 0, 0), ('b', 1, 0), ('a', 0, 1), ('b', 1, 1), ('c', 2, 1), ('d', 3, 1)]);
+        assert_eq!(positions("ab\nabcd", None, Align::Right)[..2], [('a', 2, 0), ('b', 3, 0)]);
+        assert_eq!(positions("ab\nabcd", None, Align::Center)[..2], [('a', 1, 0), ('b', 2, 0)]);
+        // the box is the width, even when the lines are shorter
+        assert_eq!(positions("ab", Some(6), Align::Right), [('a', 4, 0), ('b', 5, 0)]);
+        // odd space left: the extra column goes to the right
+        assert_eq!(positions("ab", Some(5), Align::Center), [('a', 1, 0), ('b', 2, 0)]);
+    }
+
+    #[test]
+    fn skips_spaces() {
+        assert_eq!(positions("a b", None, Align::Left), [('a', 0, 0), ('b', 2, 0)]);
+    }
+
+    #[test]
+    fn places_glyphs_in_pixels() {
+        let font = Font::from_image(RgbaImage::new(COLUMNS * 8, ROWS * 16)).unwrap();
+        let text = Text {
+            content: "!\nA",
+            position: [10.0, 20.0],
+            scale: 2.0,
+            colour: [1.0; 4],
+            align: Align::Left,
+            width: None,
+        };
+        let glyphs = font.glyphs(&text);
+        assert_eq!(glyphs[0].screen, [10.0, 20.0, 26.0, 52.0]);
+        // '!' is the second cell of the first row, 'A' the second cell of the third row
+        assert_eq!(glyphs[0].atlas, [1.0 / 16.0, 0.0, 2.0 / 16.0, 1.0 / 6.0]);
+        assert_eq!(glyphs[1].screen, [10.0, 52.0, 26.0, 84.0]);
+        assert_eq!(glyphs[1].atlas, [1.0 / 16.0, 2.0 / 6.0, 2.0 / 16.0, 3.0 / 6.0]);
+        assert_eq!(font.size(&text), [16.0, 64.0]);
+        // unknown characters are drawn as '?'
+        let unknown = Text { content: "é", ..text };
+        assert_eq!(font.glyphs(&unknown)[0].atlas, font.glyphs(&Text { content: "?", ..text })[0].atlas);
+    }
+
+    #[test]
+    fn refuses_atlases_of_the_wrong_size() {
+        assert!(matches!(Font::from_image(RgbaImage::new(100, 96)), Err(FontError::Size { width: 100, height: 96 })));
+        assert!(matches!(Font::from_image(RgbaImage::new(0, 0)), Err(FontError::Size { .. })));
+        assert!(matches!(Font::load(Path::new("assets/fonts/missing.png")), Err(FontError::Image { .. })));
+        assert!(Font::load(Path::new("assets/fonts/font.png")).is_ok());
+    }
+}
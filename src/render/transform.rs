@@ -0,0 +1,88 @@
+use crate::render::matrix::{self, Matrix};
+use crate::render::quaternion::Quaternion;
+
+/// Position, orientation and size of an object, applied as scale, then rotation, then translation
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    pub rotation: Quaternion,
+    pub scale: [f32; 3],
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: [0.0; 3],
+        rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+        scale: [1.0; 3],
+    };
+
+    pub fn from_translation(translation: [f32; 3]) -> Transform {
+        Transform { translation, ..Transform::IDENTITY }
+    }
+
+    pub fn with_rotation(self, rotation: Quaternion) -> Transform {
+        Transform { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: f32) -> Transform {
+        Transform { scale: [scale; 3], ..self }
+    }
+
+    pub fn matrix(&self) -> Matrix {
+        let mut m = self.rotation.to_matrix();
+        for (column, scale) in m.iter_mut().zip(self.scale) {
+            for value in column.iter_mut().take(3) {
+                *value *= scale;
+            }
+        }
+        matrix::multiply(&matrix::translation(self.translation), &m)
+    }
+
+    /// From the world to the space of the object
+    pub fn inverse_matrix(&self) -> Matrix {
+        let Quaternion { w, x, y, z } = self.rotation;
+        // the inverse of a unit quaternion is its conjugate
+        let mut m = Quaternion::new(w, -x, -y, -z).to_matrix();
+        for column in m.iter_mut().take(3) {
+            for (value, scale) in column.iter_mut().zip(self.scale) {
+                *value /= scale;
+            }
+        }
+        matrix::multiply(&m, &matrix::translation(self.translation.map(|coord| -coord)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::vec3d::Vec3d;
+
+    #[test]
+    fn inverts_the_matrix() {
+        let transform = Transform {
+            translation: [3.0, -2.0, 0.5],
+            rotation: Quaternion::from_rotation(Vec3d::new(1.0, 2.0, -1.0).normalize(), 0.7),
+            scale: [2.0, 0.5, 4.0],
+        };
+        for product in [
+            matrix::multiply(&transform.matrix(), &transform.inverse_matrix()),
+            matrix::multiply(&transform.inverse_matrix(), &transform.matrix()),
+        ] {
+            for (column, identity) in product.iter().zip(matrix::IDENTITY) {
+                for (value, expected) in column.iter().zip(identity) {
+                    assert!((value - expected).abs() < 1e-5, "{:?}", product);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scales_then_rotates_then_translates() {
+        let rotation = Quaternion::from_rotation(Vec3d::new(0.0, 0.0, 1.0), std::f32::consts::FRAC_PI_2);
+        let transform = Transform::from_translation([1.0, 0.0, 0.0]).with_rotation(rotation).with_scale(2.0);
+        let [x, y, z, _] = matrix::transform(&transform.matrix(), [1.0, 0.0, 0.0, 1.0]);
+        for (value, expected) in [x, y, z].into_iter().zip([1.0, 2.0, 0.0]) {
+            assert!((value - expected).abs() < 1e-5, "{:?}", [x, y, z]);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Options of the player, read from a `key = value` file
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogMode {
+    Off,
+    /// From `fog_start` of the render distance to the render distance
+    Linear,
+    /// Thickens with the square of the distance, almost opaque at the render distance
+    Exponential,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    /// In chunks, nothing further away is drawn
+    pub render_distance: u32,
+    pub fog: FogMode,
+    /// Fraction of the render distance where the linear fog starts, below 1 so the fog has a length
+    pub fog_start: f32,
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            render_distance: 8,
+            fog: FogMode::Linear,
+            fog_start: 0.6,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Settings, SettingsError> {
+        let source = std::fs::read_to_string(path).map_err(|error| SettingsError::Io { path: path.to_path_buf(), error })?;
+        Settings::parse(&source, path)
+    }
+
+    /// Unknown keys are errors so typos do not go unnoticed, missing ones keep their default
+    pub fn parse(source: &str, path: &Path) -> Result<Settings, SettingsError> {
+        let mut settings = Settings::new();
+
+        for (number, line) in source.lines().enumerate() {
+            let error = |message: String| SettingsError::Parse { path: path.to_path_buf(), line: number + 1, message };
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| error(format!("expected `key = value`, got `{}`", line)))?;
+            settings.set(key.trim(), value.trim()).map_err(error)?;
+        }
+
+        Ok(settings)
+    }
+
+    /// Changes one setting from its text value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "render_distance" => {
+                self.render_distance = value.parse().ok().filter(|&distance| distance > 0)
+                    .ok_or_else(|| format!("invalid render distance `{}`", value))?;
+            }
+            "fog" => {
+                self.fog = match value {
+                    "off" => FogMode::Off,
+                    "linear" => FogMode::Linear,
+                    "exponential" => FogMode::Exponential,
+                    _ => return Err(format!("unknown fog `{}`, expected off, linear or exponential", value)),
+                };
+            }
+            "fog_start" => {
+                self.fog_start = value.parse().ok().filter(|start| (0.0..1.0).contains(start))
+                    .ok_or_else(|| format!("invalid fog start `{}`, expected a number from 0 to below 1", value))?;
+            }
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io { path: PathBuf, error: std::io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Io { path, error } => write!(f, "could not read {}: {}", path.display(), error),
+            SettingsError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_settings_and_keeps_the_defaults() {
+        let source = "# comment\nrender_distance = 12\n\nfog = exponential # thick\n";
+        let settings = Settings::parse(source, Path::new("settings.txt")).unwrap();
+        assert_eq!(settings.render_distance, 12);
+        assert_eq!(settings.fog, FogMode::Exponential);
+        assert_eq!(settings.fog_start, Settings::new().fog_start);
+    }
+
+    #[test]
+    fn reports_the_line_of_the_errors() {
+        let error = |source: &str| Settings::parse(source, Path::new("settings.txt")).unwrap_err().to_string();
+        assert_eq!(error("fog = off\nrender_distance = 0"), "settings.txt:2: invalid render distance `0`");
+        assert_eq!(error("fog_start = 1.5"), "settings.txt:1: invalid fog start `1.5`, expected a number from 0 to below 1");
+        assert_eq!(error("fog_start = 1.0"), "settings.txt:1: invalid fog start `1.0`, expected a number from 0 to below 1");
+        assert_eq!(error("fog"), "settings.txt:1: expected `key = value`, got `fog`");
+        assert_eq!(error("\nfov = 90"), "settings.txt:2: unknown setting `fov`");
+    }
+}
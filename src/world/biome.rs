@@ -0,0 +1,106 @@
+//! Regions of the terrain with their own blocks, relief, trees and sky, chosen from the
+//! temperature and humidity of each column
+
+use super::block::BlockType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Mountains,
+    Swamp,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [Biome::Plains, Biome::Forest, Biome::Desert, Biome::Mountains, Biome::Swamp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Biome::Plains => "plains",
+            Biome::Forest => "forest",
+            Biome::Desert => "desert",
+            Biome::Mountains => "mountains",
+            Biome::Swamp => "swamp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Biome> {
+        Biome::ALL.into_iter().find(|biome| biome.name() == name)
+    }
+
+    /// Both in 0..1
+    pub fn from_climate(temperature: f32, humidity: f32) -> Biome {
+        if temperature < 0.33 {
+            Biome::Mountains
+        } else if temperature > 0.6 && humidity < 0.45 {
+            Biome::Desert
+        } else if humidity > 0.58 && temperature > 0.55 {
+            Biome::Swamp
+        } else if humidity > 0.52 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    /// Top block of the columns
+    pub fn surface(self) -> BlockType {
+        match self {
+            Biome::Desert => BlockType::Sand,
+            Biome::Mountains => BlockType::Stone,
+            _ => BlockType::Grass,
+        }
+    }
+
+    /// Blocks between the surface and the stone
+    pub fn filler(self) -> BlockType {
+        match self {
+            Biome::Desert => BlockType::Sand,
+            Biome::Mountains => BlockType::Gravel,
+            _ => BlockType::Dirt,
+        }
+    }
+
+    /// Lowest height of the ground
+    pub fn base_height(self) -> f32 {
+        match self {
+            Biome::Swamp => 3.0,
+            Biome::Mountains => 6.0,
+            _ => 4.0,
+        }
+    }
+
+    /// Height added by the terrain noise at most
+    pub fn height_variation(self) -> f32 {
+        match self {
+            Biome::Plains => 6.0,
+            Biome::Forest => 8.0,
+            Biome::Desert => 5.0,
+            Biome::Mountains => 18.0,
+            Biome::Swamp => 2.0,
+        }
+    }
+
+    /// Average number of trees per chunk, at most `generation::MAX_TREES`
+    pub fn trees(self) -> u64 {
+        match self {
+            Biome::Plains => 1,
+            Biome::Forest => 6,
+            Biome::Desert => 0,
+            Biome::Mountains => 1,
+            Biome::Swamp => 3,
+        }
+    }
+
+    /// Multiplies the colours of the sky and the fog
+    pub fn sky_tint(self) -> [f32; 3] {
+        match self {
+            Biome::Plains => [1.0, 1.0, 1.0],
+            Biome::Forest => [0.9, 1.0, 0.9],
+            Biome::Desert => [1.1, 1.0, 0.85],
+            Biome::Mountains => [0.95, 0.97, 1.05],
+            Biome::Swamp => [0.75, 0.85, 0.7],
+        }
+    }
+}
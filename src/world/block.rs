@@ -1,27 +1,212 @@
-use crate::models::{cube::{self, SIZE}, object::{self, Object}};
+use crate::models::cube::SIZE;
+use crate::world::plants;
+use crate::world::state::{BlockState, Facing, Property};
 
-#[derive(PartialEq)]
+/// Render pass drawing the block, in the order of the passes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layer {
+    Opaque,
+    /// Either fully opaque or fully transparent pixels, without blending (leaves, glass)
+    Cutout,
+    /// Blended with what is behind (water, stained glass), drawn last from back to front
+    Translucent,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BlockType {
     Void,
     Bedrock,
+    Glass,
+    Leaves,
+    Water,
+    StainedGlass,
+    Lava,
+    Stone,
+    Sand,
+    Gravel,
+    Wheat,
+    Log,
+    Furnace,
+    Trapdoor,
+    Dirt,
+    Grass,
+    Cobblestone,
+    Planks,
+    CoalOre,
+    IronOre,
 }
 
+impl BlockType {
+    pub const ALL: [BlockType; 20] = [
+        BlockType::Void,
+        BlockType::Bedrock,
+        BlockType::Glass,
+        BlockType::Leaves,
+        BlockType::Water,
+        BlockType::StainedGlass,
+        BlockType::Lava,
+        BlockType::Stone,
+        BlockType::Sand,
+        BlockType::Gravel,
+        BlockType::Wheat,
+        BlockType::Log,
+        BlockType::Furnace,
+        BlockType::Trapdoor,
+        BlockType::Dirt,
+        BlockType::Grass,
+        BlockType::Cobblestone,
+        BlockType::Planks,
+        BlockType::CoalOre,
+        BlockType::IronOre,
+    ];
+
+    /// Name used by the commands
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockType::Void => "void",
+            BlockType::Bedrock => "bedrock",
+            BlockType::Glass => "glass",
+            BlockType::Leaves => "leaves",
+            BlockType::Water => "water",
+            BlockType::StainedGlass => "stained_glass",
+            BlockType::Lava => "lava",
+            BlockType::Stone => "stone",
+            BlockType::Sand => "sand",
+            BlockType::Gravel => "gravel",
+            BlockType::Wheat => "wheat",
+            BlockType::Log => "log",
+            BlockType::Furnace => "furnace",
+            BlockType::Trapdoor => "trapdoor",
+            BlockType::Dirt => "dirt",
+            BlockType::Grass => "grass",
+            BlockType::Cobblestone => "cobblestone",
+            BlockType::Planks => "planks",
+            BlockType::CoalOre => "coal_ore",
+            BlockType::IronOre => "iron_ore",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BlockType> {
+        BlockType::ALL.into_iter().find(|block_type| block_type.name() == name)
+    }
+
+    /// None for blocks that are not drawn
+    pub fn layer(self) -> Option<Layer> {
+        match self {
+            BlockType::Void => None,
+            BlockType::Bedrock | BlockType::Lava | BlockType::Stone | BlockType::Sand | BlockType::Gravel
+                | BlockType::Log | BlockType::Furnace | BlockType::Dirt | BlockType::Grass | BlockType::Cobblestone
+                | BlockType::Planks | BlockType::CoalOre | BlockType::IronOre => Some(Layer::Opaque),
+            // a trapdoor does not fill its cell, the blocks around it must stay visible
+            BlockType::Glass | BlockType::Leaves | BlockType::Wheat | BlockType::Trapdoor => Some(Layer::Cutout),
+            BlockType::Water | BlockType::StainedGlass => Some(Layer::Translucent),
+        }
+    }
+
+    /// For the cutout blocks the alpha is the part of the face kept inside its border,
+    /// for the translucent ones it is their opacity
+    pub fn colour(self) -> [f32; 4] {
+        match self {
+            BlockType::Void => [0.0; 4],
+            BlockType::Bedrock => [1.0, 0.0, 0.0, 1.0],
+            BlockType::Glass => [0.85, 0.95, 1.0, 0.0],
+            BlockType::Leaves => [0.2, 0.6, 0.15, 0.7],
+            BlockType::Water => [0.15, 0.35, 0.9, 0.6],
+            BlockType::StainedGlass => [0.9, 0.3, 0.6, 0.5],
+            BlockType::Lava => [1.0, 0.4, 0.0, 1.0],
+            BlockType::Stone => [0.5, 0.5, 0.5, 1.0],
+            BlockType::Sand => [0.9, 0.85, 0.55, 1.0],
+            BlockType::Gravel => [0.55, 0.5, 0.48, 1.0],
+            BlockType::Wheat => [0.3, 0.75, 0.2, 0.6],
+            BlockType::Log => [0.45, 0.3, 0.15, 1.0],
+            BlockType::Furnace => [0.4, 0.4, 0.42, 1.0],
+            BlockType::Trapdoor => [0.6, 0.45, 0.25, 1.0],
+            BlockType::Dirt => [0.45, 0.3, 0.2, 1.0],
+            BlockType::Grass => [0.35, 0.6, 0.25, 1.0],
+            BlockType::Cobblestone => [0.42, 0.42, 0.42, 1.0],
+            BlockType::Planks => [0.7, 0.55, 0.3, 1.0],
+            BlockType::CoalOre => [0.25, 0.25, 0.27, 1.0],
+            BlockType::IronOre => [0.7, 0.55, 0.45, 1.0],
+        }
+    }
+
+    /// Properties stored in the state of the block, in the order they are packed
+    pub fn properties(self) -> &'static [Property] {
+        match self {
+            BlockType::Water | BlockType::Lava => &[Property::Level, Property::Falling],
+            BlockType::Wheat => &[Property::Level],
+            BlockType::Log => &[Property::Axis],
+            BlockType::Furnace => &[Property::Facing],
+            BlockType::Trapdoor => &[Property::Facing, Property::Open],
+            _ => &[],
+        }
+    }
+
+    /// Falls when the block below is empty
+    pub fn has_gravity(self) -> bool {
+        matches!(self, BlockType::Sand | BlockType::Gravel)
+    }
+
+    /// Falling blocks go through it and replace it when they land
+    pub fn is_replaceable(self) -> bool {
+        matches!(self, BlockType::Void | BlockType::Water | BlockType::Lava)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Block {
-    pub render: Object, // the 3d representation of the block
     pub coordinates: [i64; 3],
-    pub block_type: BlockType
+    pub block_type: BlockType,
+    /// Packed values of the properties, see `BlockState`
+    pub properties: u16,
 }
 
 impl Block {
-    pub fn new(blocktype: BlockType, coordinates: [i64; 3]) -> Block {
+    pub fn new(state: BlockState, coordinates: [i64; 3]) -> Block {
         Block {
-            render: cube::new([
-                (coordinates[0]) as f32 * SIZE * 2., 
-                (coordinates[1]) as f32 * SIZE * 2., 
-                (coordinates[2]) as f32 * SIZE * 2.]
-            ),
-            coordinates: coordinates,
-            block_type: blocktype,
+            coordinates,
+            block_type: state.block_type,
+            properties: state.properties,
+        }
+    }
+
+    pub fn state(&self) -> BlockState {
+        BlockState { block_type: self.block_type, properties: self.properties }
+    }
+
+    /// Colour of the type, changed by the level for the blocks that have one
+    pub fn colour(&self) -> [f32; 4] {
+        let colour = self.block_type.colour();
+        match self.block_type {
+            // from green to ripe
+            BlockType::Wheat => {
+                let ripe = [0.85, 0.7, 0.25, colour[3]];
+                let t = self.state().level() as f32 / plants::MAX_STAGE as f32;
+                std::array::from_fn(|i| colour[i] + (ripe[i] - colour[i]) * t)
+            }
+            _ => colour,
+        }
+    }
+
+    /// Colour of one side, for the blocks that are not the same all around
+    pub fn face_colour(&self, side: Facing) -> [f32; 4] {
+        let colour = self.colour();
+        let state = self.state();
+        let shade = |factor: f32| [colour[0] * factor, colour[1] * factor, colour[2] * factor, colour[3]];
+        match self.block_type {
+            // the rings at both ends of the log
+            BlockType::Log if state.axis() == Some(side.axis()) => [0.75, 0.6, 0.4, 1.0],
+            BlockType::Furnace if state.facing() == Some(side) => shade(0.4),
+            _ => colour,
         }
     }
-}
\ No newline at end of file
+
+    /// Center of the 3d representation of the block
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.coordinates[0]) as f32 * SIZE * 2.,
+            (self.coordinates[1]) as f32 * SIZE * 2.,
+            (self.coordinates[2]) as f32 * SIZE * 2.,
+        ]
+    }
+}
@@ -0,0 +1,646 @@
+//! Text commands typed in the console, such as `/time set night`, `/setblock ~ ~-1 ~ log[axis=x]`
+//! or `/fill 0 1 0 4 3 4 planks hollow`. Each command lists its syntaxes, the arguments are parsed
+//! and checked before it runs, so the parsing needs no window
+
+use std::path::Path;
+
+use super::block::BlockType;
+use super::edit::{EditError, Orientation, Region, Summary};
+use super::schematic::{self, BlockMapping};
+use super::state::{Axis, BlockState};
+use super::time::{self, TICKS_PER_DAY};
+use super::world::{GameMode, World};
+
+/// What a command expects at one place of the line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arg {
+    /// A fixed word, for the subcommands such as `time set`
+    Literal(&'static str),
+    Int(&'static str),
+    /// `x y z` in blocks, each can be `~` or `~N` relative to the player
+    Position(&'static str),
+    /// `x z` in blocks, relative like `Position`
+    Column(&'static str),
+    Block(&'static str),
+    /// One of the words
+    Choice(&'static str, &'static [&'static str]),
+    /// Any word, the suggestions are only for the completion
+    Word(&'static str, &'static [&'static str]),
+    /// The name of a command
+    Command,
+}
+
+impl Arg {
+    /// Words of the line it takes
+    fn width(self) -> usize {
+        match self {
+            Arg::Position(_) => 3,
+            Arg::Column(_) => 2,
+            _ => 1,
+        }
+    }
+
+    fn usage(self) -> String {
+        match self {
+            Arg::Literal(word) => word.to_string(),
+            Arg::Choice(_, words) => format!("<{}>", words.join("|")),
+            Arg::Int(name) | Arg::Position(name) | Arg::Column(name) | Arg::Block(name) | Arg::Word(name, _) => format!("<{}>", name),
+            Arg::Command => "<command>".to_string(),
+        }
+    }
+
+    /// Values for a word of the argument, starting with `prefix`
+    fn suggestions(self, prefix: &str) -> Vec<String> {
+        let words: Vec<String> = match self {
+            Arg::Literal(word) => vec![word.to_string()],
+            Arg::Choice(_, words) | Arg::Word(_, words) => words.iter().map(|word| word.to_string()).collect(),
+            Arg::Block(_) => BlockType::ALL.iter().map(|block_type| block_type.name().to_string()).collect(),
+            Arg::Command => COMMANDS.iter().map(|command| command.name.to_string()).collect(),
+            Arg::Position(_) | Arg::Column(_) => vec!["~".to_string()],
+            Arg::Int(_) => vec![],
+        };
+        words.into_iter().filter(|word| word.starts_with(prefix)).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Word(String),
+    Int(i64),
+    Position([i64; 3]),
+    Column([i64; 2]),
+    Block(BlockState),
+}
+
+/// Values of the arguments of a syntax, in order. The accessors panic if the syntax does not
+/// have that kind of argument there
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arguments(pub Vec<Value>);
+
+impl Arguments {
+    pub fn word(&self, index: usize) -> &str {
+        match &self.0[index] {
+            Value::Word(word) => word,
+            other => panic!("expected a word, got {:?}", other),
+        }
+    }
+
+    pub fn int(&self, index: usize) -> i64 {
+        match self.0[index] {
+            Value::Int(value) => value,
+            ref other => panic!("expected an integer, got {:?}", other),
+        }
+    }
+
+    pub fn position(&self, index: usize) -> [i64; 3] {
+        match self.0[index] {
+            Value::Position(position) => position,
+            ref other => panic!("expected a position, got {:?}", other),
+        }
+    }
+
+    pub fn column(&self, index: usize) -> [i64; 2] {
+        match self.0[index] {
+            Value::Column(column) => column,
+            ref other => panic!("expected a column, got {:?}", other),
+        }
+    }
+
+    pub fn block(&self, index: usize) -> BlockState {
+        match self.0[index] {
+            Value::Block(state) => state,
+            ref other => panic!("expected a block, got {:?}", other),
+        }
+    }
+}
+
+type Run = fn(&mut World, &Arguments) -> Result<String, String>;
+
+pub struct Syntax {
+    pub arguments: &'static [Arg],
+    run: Run,
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub syntaxes: &'static [Syntax],
+}
+
+impl Command {
+    /// One line per syntax
+    pub fn usage(&self) -> String {
+        let lines: Vec<String> = self.syntaxes.iter()
+            .map(|syntax| {
+                let arguments: Vec<String> = syntax.arguments.iter().map(|arg| arg.usage()).collect();
+                format!("/{} {}", self.name, arguments.join(" ")).trim_end().to_string()
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+const fn syntax(arguments: &'static [Arg], run: Run) -> Syntax {
+    Syntax { arguments, run }
+}
+
+/// Blocks a building command can go through at once, fewer than `edit::MAX_VOLUME` so a mistyped
+/// corner does not freeze the game
+pub const MAX_BLOCKS: i128 = 32768;
+
+const TIMES: &[&str] = &["day", "sunrise", "noon", "sunset", "night", "midnight"];
+const ROTATIONS: &[&str] = &["0", "90", "180", "270"];
+const MIRRORS: &[&str] = &["x", "z"];
+const MODES: &[&str] = &["survival", "creative", "spectator"];
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        help: "Lists the commands, or shows how to use one",
+        syntaxes: &[syntax(&[], help_all), syntax(&[Arg::Command], help)],
+    },
+    Command {
+        name: "tp",
+        help: "Moves the player to the block",
+        syntaxes: &[syntax(&[Arg::Position("position")], teleport)],
+    },
+    Command {
+        name: "setblock",
+        help: "Changes one block, with properties such as log[axis=x]",
+        syntaxes: &[syntax(&[Arg::Position("position"), Arg::Block("block")], set_block)],
+    },
+    Command {
+        name: "fill",
+        help: "Fills the box between two corners, or only its faces with hollow",
+        syntaxes: &[
+            syntax(&[Arg::Position("from"), Arg::Position("to"), Arg::Block("block")], fill),
+            syntax(&[Arg::Position("from"), Arg::Position("to"), Arg::Block("block"), Arg::Literal("hollow")], fill),
+        ],
+    },
+    Command {
+        name: "replace",
+        help: "Changes the blocks of a type in the box between two corners",
+        syntaxes: &[syntax(&[Arg::Position("from"), Arg::Position("to"), Arg::Block("old"), Arg::Block("new")], replace)],
+    },
+    Command {
+        name: "sphere",
+        help: "Makes a ball of blocks, or only its shell with hollow",
+        syntaxes: &[
+            syntax(&[Arg::Position("center"), Arg::Int("radius"), Arg::Block("block")], sphere),
+            syntax(&[Arg::Position("center"), Arg::Int("radius"), Arg::Block("block"), Arg::Literal("hollow")], sphere),
+        ],
+    },
+    Command {
+        name: "copy",
+        help: "Copies the box between two corners to the clipboard",
+        syntaxes: &[syntax(&[Arg::Position("from"), Arg::Position("to")], copy)],
+    },
+    Command {
+        name: "paste",
+        help: "Pastes the clipboard, turned by degrees and mirrored along an axis",
+        syntaxes: &[
+            syntax(&[Arg::Position("position")], paste),
+            syntax(&[Arg::Position("position"), Arg::Choice("rotation", ROTATIONS)], paste),
+            syntax(&[Arg::Position("position"), Arg::Choice("rotation", ROTATIONS), Arg::Choice("mirror", MIRRORS)], paste),
+        ],
+    },
+    Command {
+        name: "undo",
+        help: "Undoes the last building command",
+        syntaxes: &[syntax(&[], undo)],
+    },
+    Command {
+        name: "redo",
+        help: "Makes the last undone building command again",
+        syntaxes: &[syntax(&[], redo)],
+    },
+    Command {
+        name: "schematic",
+        help: "Loads a .schem or .vox file into the clipboard, or saves the clipboard to one",
+        syntaxes: &[
+            syntax(&[Arg::Literal("load"), Arg::Word("file", &[])], load_schematic),
+            syntax(&[Arg::Literal("save"), Arg::Word("file", &[])], save_schematic),
+        ],
+    },
+    Command {
+        name: "time",
+        help: "Changes or shows the time of the day",
+        syntaxes: &[
+            syntax(&[Arg::Literal("set"), Arg::Word("time", TIMES)], set_time),
+            syntax(&[Arg::Literal("add"), Arg::Int("ticks")], add_time),
+            syntax(&[Arg::Literal("query")], query_time),
+        ],
+    },
+    Command {
+        name: "gamemode",
+        help: "Changes what the player can do: build in creative, build nearby in survival, only look in spectator",
+        syntaxes: &[syntax(&[], query_game_mode), syntax(&[Arg::Choice("mode", MODES)], set_game_mode)],
+    },
+    Command {
+        name: "seed",
+        help: "Shows the seed the map was generated from",
+        syntaxes: &[syntax(&[], seed)],
+    },
+    Command {
+        name: "biome",
+        help: "Shows the biome of the player or of a column",
+        syntaxes: &[syntax(&[], biome_here), syntax(&[Arg::Column("column")], biome)],
+    },
+];
+
+/// Runs a command, returns the message to show to the player. The relative coordinates are
+/// relative to the block of the camera
+pub fn execute(world: &mut World, line: &str) -> Result<String, String> {
+    let origin = world.camera_block().map(|c| c.round() as i64);
+    let (syntax, arguments) = parse(line, origin)?;
+    (syntax.run)(world, &arguments)
+}
+
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// The first syntax of the command matching the line, with the values of its arguments
+pub fn parse(line: &str, origin: [i64; 3]) -> Result<(&'static Syntax, Arguments), String> {
+    let line = line.trim();
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((name, words)) = words.split_first() else {
+        return Err("empty command".to_string());
+    };
+    let command = find(name).ok_or_else(|| format!("unknown command `{}`, see /help", name))?;
+
+    let mut error = None;
+    for syntax in command.syntaxes {
+        match parse_arguments(syntax.arguments, words, origin) {
+            Ok(arguments) => return Ok((syntax, arguments)),
+            Err(message) => error = error.or(message),
+        }
+    }
+    Err(error.unwrap_or_else(|| format!("usage: {}", command.usage())))
+}
+
+/// Err(None) if the words do not have the shape of the syntax, Err(Some) if a value is invalid
+fn parse_arguments(arguments: &[Arg], words: &[&str], origin: [i64; 3]) -> Result<Arguments, Option<String>> {
+    if arguments.iter().map(|arg| arg.width()).sum::<usize>() != words.len() {
+        return Err(None);
+    }
+    // the literals first, they tell the syntaxes apart
+    let mut words = words;
+    let mut spans = vec![];
+    for arg in arguments {
+        let (span, rest) = words.split_at(arg.width());
+        if let Arg::Literal(literal) = arg && span[0] != *literal {
+            return Err(None);
+        }
+        spans.push(span);
+        words = rest;
+    }
+
+    let values = arguments.iter().zip(spans).map(|(arg, span)| parse_value(*arg, span, origin));
+    Ok(Arguments(values.collect::<Result<_, _>>().map_err(Some)?))
+}
+
+fn parse_value(arg: Arg, span: &[&str], origin: [i64; 3]) -> Result<Value, String> {
+    Ok(match arg {
+        Arg::Literal(word) => Value::Word(word.to_string()),
+        Arg::Word(..) | Arg::Command => Value::Word(span[0].to_string()),
+        Arg::Int(name) => Value::Int(span[0].parse().map_err(|_| format!("invalid {} `{}`", name, span[0]))?),
+        Arg::Position(_) => Value::Position([
+            parse_coordinate(span[0], origin[0])?,
+            parse_coordinate(span[1], origin[1])?,
+            parse_coordinate(span[2], origin[2])?,
+        ]),
+        Arg::Column(_) => Value::Column([parse_coordinate(span[0], origin[0])?, parse_coordinate(span[1], origin[2])?]),
+        Arg::Block(_) => Value::Block(BlockState::parse(span[0])?),
+        Arg::Choice(name, words) => {
+            if !words.contains(&span[0]) {
+                return Err(format!("invalid {} `{}`, expected {}", name, span[0], words.join(", ")));
+            }
+            Value::Word(span[0].to_string())
+        }
+    })
+}
+
+/// `N`, or `~` and `~N` relative to `origin`
+fn parse_coordinate(word: &str, origin: i64) -> Result<i64, String> {
+    let invalid = || format!("invalid coordinate `{}`", word);
+    match word.strip_prefix('~') {
+        Some("") => Ok(origin),
+        Some(offset) => origin.checked_add(offset.parse::<i64>().map_err(|_| invalid())?).ok_or_else(invalid),
+        None => word.parse().map_err(|_| invalid()),
+    }
+}
+
+/// Ways to finish the last word of the line, from every syntax the line can still match
+pub fn complete(line: &str) -> Vec<String> {
+    let line = line.trim_start();
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    // the line ends with a space: a new word is started
+    if line.is_empty() || line.ends_with(char::is_whitespace) {
+        words.push("");
+    }
+    let (last, before) = words.split_last().unwrap();
+    let Some((name, before)) = before.split_first() else {
+        return COMMANDS.iter().map(|command| command.name.to_string()).filter(|name| name.starts_with(last)).collect();
+    };
+    let Some(command) = find(name) else {
+        return vec![];
+    };
+
+    let mut suggestions: Vec<String> = vec![];
+    for syntax in command.syntaxes {
+        // finds the argument of the word being typed
+        let mut start = 0;
+        let mut matches = true;
+        for arg in syntax.arguments {
+            let end = start + arg.width();
+            if before.len() < end {
+                if matches {
+                    suggestions.extend(arg.suggestions(last));
+                }
+                break;
+            }
+            if let Arg::Literal(literal) = arg && before[start] != *literal {
+                matches = false;
+            }
+            start = end;
+        }
+    }
+    let mut unique = vec![];
+    for suggestion in suggestions {
+        if !unique.contains(&suggestion) {
+            unique.push(suggestion);
+        }
+    }
+    unique
+}
+
+fn help_all(_: &mut World, _: &Arguments) -> Result<String, String> {
+    let lines: Vec<String> = COMMANDS.iter().map(|command| format!("/{}: {}", command.name, command.help)).collect();
+    Ok(lines.join("\n"))
+}
+
+fn help(_: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let name = arguments.word(0);
+    let command = find(name).ok_or_else(|| format!("unknown command `{}`", name))?;
+    Ok(format!("{}\n{}", command.help, command.usage()))
+}
+
+fn teleport(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let position = arguments.position(0);
+    world.teleport(position.map(|c| c as f32));
+    Ok(format!("Teleported to {:?}", position))
+}
+
+fn set_block(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let (coordinates, state) = (arguments.position(0), arguments.block(1));
+    world.map.set_state(coordinates, state).map_err(|error| error.to_string())?;
+    Ok(format!("Placed {} at {:?}", state, coordinates))
+}
+
+fn fill(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let region = Region::new(arguments.position(0), arguments.position(1));
+    check_volume(region.volume())?;
+    let state = arguments.block(2);
+    if arguments.0.len() == 4 {
+        changed(world.edit.hollow_box(&mut world.map, region, state))
+    } else {
+        changed(world.edit.fill(&mut world.map, region, state))
+    }
+}
+
+fn replace(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let region = Region::new(arguments.position(0), arguments.position(1));
+    check_volume(region.volume())?;
+    changed(world.edit.replace(&mut world.map, region, arguments.block(2).block_type, arguments.block(3)))
+}
+
+fn sphere(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let hollow = arguments.0.len() == 4;
+    // the box around the ball, a negative radius is refused by the tool
+    let side = 2 * arguments.int(1).max(0) as i128 + 1;
+    check_volume(side.checked_pow(3).unwrap_or(i128::MAX))?;
+    changed(world.edit.sphere(&mut world.map, arguments.position(0), arguments.int(1), arguments.block(2), hollow))
+}
+
+fn copy(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let region = Region::new(arguments.position(0), arguments.position(1));
+    check_volume(region.volume())?;
+    let clipboard = world.edit.copy(&world.map, region).map_err(|error| error.to_string())?;
+    Ok(format!("Copied {} blocks", clipboard.states.len()))
+}
+
+fn paste(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let clipboard = world.edit.clipboard.as_ref().ok_or("the clipboard is empty, use /copy first")?;
+    check_volume(clipboard.states.len() as i128)?;
+    let mut orientation = Orientation::default();
+    if arguments.0.len() > 1 {
+        orientation.turns = ROTATIONS.iter().position(|rotation| *rotation == arguments.word(1)).unwrap() as u32;
+    }
+    if arguments.0.len() > 2 {
+        orientation.mirror = Some(if arguments.word(2) == "x" { Axis::X } else { Axis::Z });
+    }
+    changed(world.edit.paste(&mut world.map, arguments.position(0), orientation))
+}
+
+fn undo(world: &mut World, _: &Arguments) -> Result<String, String> {
+    changed(world.edit.undo(&mut world.map).ok_or("nothing to undo")?)
+}
+
+fn redo(world: &mut World, _: &Arguments) -> Result<String, String> {
+    changed(world.edit.redo(&mut world.map).ok_or("nothing to redo")?)
+}
+
+fn load_schematic(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let import = schematic::load(Path::new(arguments.word(1)), &mapping(world)?).map_err(|error| error.to_string())?;
+    let [x, y, z] = import.clipboard.size;
+    let mut message = format!("Loaded {}x{}x{} blocks into the clipboard", x, y, z);
+    if !import.unmapped.is_empty() {
+        let unmapped: Vec<String> = import.unmapped.iter().map(|(name, count)| format!("{} ({})", name, count)).collect();
+        message += &format!(", no mapping for {}", unmapped.join(", "));
+    }
+    world.edit.clipboard = Some(import.clipboard);
+    Ok(message)
+}
+
+fn save_schematic(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let file = arguments.word(1);
+    let clipboard = world.edit.clipboard.as_ref().ok_or("the clipboard is empty, use /copy first")?;
+    schematic::save(Path::new(file), clipboard, &mapping(world)?).map_err(|error| error.to_string())?;
+    Ok(format!("Saved the clipboard to {}", file))
+}
+
+fn set_time(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    world.time.set_time_of_day(parse_time(arguments.word(1))?);
+    Ok(format!("Set the time to {}", world.time.time_of_day()))
+}
+
+fn add_time(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let ticks = u64::try_from(arguments.int(1)).map_err(|_| "the ticks cannot be negative".to_string())?;
+    world.time.ticks = world.time.ticks.checked_add(ticks)
+        .filter(|ticks| *ticks <= time::MAX_TICKS)
+        .ok_or_else(|| format!("the time cannot go past {} ticks", time::MAX_TICKS))?;
+    Ok(format!("Added {} ticks, the time is now {}", ticks, world.time.time_of_day()))
+}
+
+fn query_time(world: &mut World, _: &Arguments) -> Result<String, String> {
+    Ok(format!("Day {}, time {}", world.time.day(), world.time.time_of_day()))
+}
+
+fn query_game_mode(world: &mut World, _: &Arguments) -> Result<String, String> {
+    Ok(format!("Game mode: {}", world.game_mode.name()))
+}
+
+fn set_game_mode(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    world.game_mode = GameMode::from_name(arguments.word(0)).unwrap();
+    Ok(format!("Game mode set to {}", world.game_mode.name()))
+}
+
+fn seed(world: &mut World, _: &Arguments) -> Result<String, String> {
+    Ok(format!("Seed: {}", world.generator.seed))
+}
+
+fn biome_here(world: &mut World, _: &Arguments) -> Result<String, String> {
+    let [x, _, z] = world.camera_block().map(|c| c.round() as i64);
+    biome_at(world, x, z)
+}
+
+fn biome(world: &mut World, arguments: &Arguments) -> Result<String, String> {
+    let [x, z] = arguments.column(0);
+    biome_at(world, x, z)
+}
+
+/// The mapping file of the world on top of the default mapping, if there is one
+fn mapping(world: &World) -> Result<BlockMapping, String> {
+    match &world.mapping_path {
+        Some(path) if path.exists() => BlockMapping::load(path).map_err(|error| error.to_string()),
+        _ => Ok(BlockMapping::new()),
+    }
+}
+
+fn check_volume(volume: i128) -> Result<(), String> {
+    if volume > MAX_BLOCKS {
+        return Err(format!("too many blocks, at most {} can be changed at once", MAX_BLOCKS));
+    }
+    Ok(())
+}
+
+fn changed(summary: Result<Summary, EditError>) -> Result<String, String> {
+    let summary = summary.map_err(|error| error.to_string())?;
+    Ok(format!("Changed {} blocks in {} chunks", summary.blocks, summary.chunks.len()))
+}
+
+fn biome_at(world: &World, x: i64, z: i64) -> Result<String, String> {
+    let biome = world.map.biome(x, z).ok_or_else(|| format!("no chunk loaded at {} {}", x, z))?;
+    Ok(format!("Biome at {} {}: {}", x, z, biome.name()))
+}
+
+/// A named moment of the day or a number of ticks since sunrise
+fn parse_time(value: &str) -> Result<u64, String> {
+    match value {
+        "day" | "sunrise" => Ok(time::SUNRISE),
+        "noon" => Ok(time::NOON),
+        "sunset" => Ok(time::SUNSET),
+        "night" | "midnight" => Ok(time::MIDNIGHT),
+        _ => {
+            let ticks: u64 = value.parse().map_err(|_| format!("invalid time `{}`", value))?;
+            Ok(ticks % TICKS_PER_DAY)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::{ChunkColumn, Map};
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        world.teleport([5.0, 10.0, 5.0]);
+        world
+    }
+
+    fn error(line: &str) -> String {
+        parse(line, [0; 3]).err().unwrap()
+    }
+
+    #[test]
+    fn parses_the_arguments() {
+        let (_, arguments) = parse("/setblock 1 -2 3 log[axis=x]", [0; 3]).unwrap();
+        let log = BlockState::parse("log[axis=x]").unwrap();
+        assert_eq!(arguments, Arguments(vec![Value::Position([1, -2, 3]), Value::Block(log)]));
+        // the literals choose the syntax
+        let (_, arguments) = parse("time add 20", [0; 3]).unwrap();
+        assert_eq!(arguments, Arguments(vec![Value::Word("add".to_string()), Value::Int(20)]));
+        assert!(error("").contains("empty"));
+        assert!(error("/dance").contains("unknown command"));
+        assert!(error("/time set").starts_with("usage: /time set <time>"));
+        assert!(parse("/setblock 1 2 3 cheese", [0; 3]).is_err());
+        assert!(error("/gamemode flying").contains("invalid mode"));
+    }
+
+    #[test]
+    fn resolves_relative_coordinates() {
+        let (_, arguments) = parse("/tp ~ ~-1 ~10", [5, 6, 7]).unwrap();
+        assert_eq!(arguments.position(0), [5, 5, 17]);
+        let (_, arguments) = parse("/biome ~2 ~", [5, 6, 7]).unwrap();
+        assert_eq!(arguments.column(0), [7, 7]);
+        assert_eq!(parse_coordinate("~9223372036854775807", 1), Err("invalid coordinate `~9223372036854775807`".to_string()));
+        assert_eq!(parse_coordinate("~-9223372036854775808", -1), Err("invalid coordinate `~-9223372036854775808`".to_string()));
+        assert!(parse_coordinate("~x", 0).is_err());
+        assert_eq!(parse_coordinate("-4", 100), Ok(-4));
+    }
+
+    #[test]
+    fn completes_the_last_word() {
+        assert_eq!(complete("/ti"), ["time"]);
+        assert_eq!(complete("time "), ["set", "add", "query"]);
+        assert_eq!(complete("time set n"), ["noon", "night"]);
+        assert_eq!(complete("/setblock ~ ~ ~ pl"), ["planks"]);
+        assert_eq!(complete("/setblock ~"), ["~"]);
+        assert!(complete("/dance ").is_empty());
+    }
+
+    #[test]
+    fn runs_the_commands() {
+        let mut world = world();
+        assert_eq!(execute(&mut world, "/setblock ~ ~-1 ~ stone"), Ok("Placed stone at [5, 9, 5]".to_string()));
+        assert_eq!(world.map.get_block([5, 9, 5]).unwrap().block_type, BlockType::Stone);
+        assert_eq!(execute(&mut world, "/fill 0 0 0 2 2 2 planks"), Ok("Changed 27 blocks in 1 chunks".to_string()));
+        assert_eq!(execute(&mut world, "/undo"), Ok("Changed 27 blocks in 1 chunks".to_string()));
+        assert_eq!(world.map.get_block([1, 1, 1]).unwrap().block_type, BlockType::Void);
+        assert_eq!(execute(&mut world, "/redo"), Ok("Changed 27 blocks in 1 chunks".to_string()));
+        assert_eq!(execute(&mut world, "/redo"), Err("nothing to redo".to_string()));
+        assert_eq!(execute(&mut world, "/gamemode spectator"), Ok("Game mode set to spectator".to_string()));
+        assert_eq!(world.game_mode, GameMode::Spectator);
+    }
+
+    #[test]
+    fn refuses_too_many_blocks() {
+        let mut world = world();
+        let refused = Err(format!("too many blocks, at most {} can be changed at once", MAX_BLOCKS));
+        assert_eq!(execute(&mut world, "/fill 0 0 0 31 63 31 stone"), refused);
+        assert_eq!(execute(&mut world, "/replace 0 0 0 31 63 31 void stone"), refused);
+        assert_eq!(execute(&mut world, "/copy 0 0 0 31 63 31"), refused);
+        assert_eq!(execute(&mut world, "/sphere ~ ~ ~ 9223372036854775807 stone"), refused);
+        assert_eq!(execute(&mut world, "/sphere ~ ~ ~ 20 stone"), refused);
+        assert_eq!(world.map.get_block([5, 10, 5]).unwrap().block_type, BlockType::Void);
+        assert!(execute(&mut world, "/sphere ~ ~ ~ 2 stone").is_ok());
+    }
+
+    #[test]
+    fn changes_the_time() {
+        let mut world = world();
+        assert_eq!(execute(&mut world, "/time set night"), Ok(format!("Set the time to {}", time::MIDNIGHT)));
+        assert_eq!(execute(&mut world, "/time add 6000"), Ok("Added 6000 ticks, the time is now 0".to_string()));
+        assert_eq!(world.time.day(), 1);
+        assert_eq!(execute(&mut world, "/time query"), Ok("Day 1, time 0".to_string()));
+        assert!(execute(&mut world, "/time add -1").is_err());
+        let ticks = world.time.ticks;
+        assert!(execute(&mut world, "/time add 9223372036854775807").is_err());
+        assert_eq!(world.time.ticks, ticks);
+    }
+}
@@ -0,0 +1,143 @@
+//! Line typed by the player, opened with T for the chat or `/` for a command, and the messages
+//! shown to them. Only text: the keys are turned into calls by the world
+
+use super::command;
+
+/// Messages kept, the oldest ones are forgotten
+pub const LOG_SIZE: usize = 100;
+
+pub struct Message {
+    pub text: String,
+    /// Ticks of the console when it was printed
+    pub tick: u64,
+}
+
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    /// Chat messages and results of the commands, the oldest first
+    pub log: Vec<Message>,
+    /// Ticks counted since the start, for the age of the messages
+    ticks: u64,
+    /// Lines sent, for the arrow keys
+    history: Vec<String>,
+    /// Line of the history shown while browsing it
+    browsing: Option<usize>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            open: false,
+            input: String::new(),
+            log: vec![],
+            ticks: 0,
+            history: vec![],
+            browsing: None,
+        }
+    }
+
+    /// Starts a new line with `prefix`, `/` for a command
+    pub fn open(&mut self, prefix: &str) {
+        self.open = true;
+        self.input = prefix.to_string();
+        self.browsing = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.input.clear();
+    }
+
+    /// Control characters such as the tab are ignored
+    pub fn type_text(&mut self, text: &str) {
+        self.input.extend(text.chars().filter(|c| !c.is_control()));
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Goes back in the lines sent
+    pub fn previous(&mut self) {
+        let index = match self.browsing {
+            Some(index) => index.saturating_sub(1),
+            None if !self.history.is_empty() => self.history.len() - 1,
+            None => return,
+        };
+        self.browsing = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// Goes forward in the lines sent, to an empty line after the last one
+    pub fn next(&mut self) {
+        let Some(index) = self.browsing else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.browsing = Some(index + 1);
+            self.input = self.history[index + 1].clone();
+        } else {
+            self.browsing = None;
+            self.input.clear();
+        }
+    }
+
+    /// Finishes the last word of a command. With several ways to finish it, goes as far as they
+    /// agree and lists them
+    pub fn complete(&mut self) {
+        if !self.input.starts_with('/') {
+            return;
+        }
+        let suggestions = command::complete(&self.input);
+        let Some(first) = suggestions.first() else {
+            return;
+        };
+        let common = suggestions.iter().fold(first.as_str(), |common, suggestion| {
+            let length = common.chars().zip(suggestion.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a.len_utf8()).sum();
+            &common[..length]
+        });
+        let start = self.input.rfind(char::is_whitespace).map_or(1, |space| space + 1);
+        let mut input = format!("{}{}", &self.input[..start], common);
+        if suggestions.len() == 1 {
+            input.push(' ');
+        } else {
+            self.print(suggestions.join(" "));
+        }
+        self.input = input;
+    }
+
+    /// Closes the console and returns the line, None if it is empty
+    pub fn submit(&mut self) -> Option<String> {
+        let line = self.input.trim().to_string();
+        self.close();
+        if line.is_empty() {
+            return None;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        Some(line)
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    /// Messages printed during the last `ticks` ticks
+    pub fn recent(&self, ticks: u64) -> &[Message] {
+        let start = self.log.partition_point(|message| message.tick + ticks < self.ticks);
+        &self.log[start..]
+    }
+
+    /// Adds a message to the log, also written to the standard output
+    pub fn print(&mut self, message: String) {
+        for line in message.lines() {
+            println!("{}", line);
+            self.log.push(Message { text: line.to_string(), tick: self.ticks });
+        }
+        if self.log.len() > LOG_SIZE {
+            self.log.drain(..self.log.len() - LOG_SIZE);
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Building tools changing many blocks at once: filling and replacing in a box, hollow boxes,
+//! spheres, copying to a clipboard and pasting it turned or mirrored. Every operation is written
+//! to the map in one batch and kept in a history to undo and redo it
+
+use std::fmt;
+
+use super::block::BlockType;
+use super::map::{Map, MapError};
+use super::state::{Axis, BlockState};
+
+/// Operations kept for undo, the oldest ones are forgotten
+pub const HISTORY: usize = 32;
+/// Blocks an operation can go through at most, once its region is cut to the loaded columns
+pub const MAX_VOLUME: i64 = 1 << 20;
+
+/// Box between two corners, both included
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub min: [i64; 3],
+    pub max: [i64; 3],
+}
+
+impl Region {
+    /// The corners can be given in any order
+    pub fn new(a: [i64; 3], b: [i64; 3]) -> Region {
+        Region {
+            min: std::array::from_fn(|i| a[i].min(b[i])),
+            max: std::array::from_fn(|i| a[i].max(b[i])),
+        }
+    }
+
+    /// Blocks along each axis
+    pub fn size(self) -> [i64; 3] {
+        std::array::from_fn(|i| self.max[i] - self.min[i] + 1)
+    }
+
+    /// Number of blocks, `i128::MAX` if there are even more
+    pub fn volume(self) -> i128 {
+        (0..3)
+            .try_fold(1i128, |volume, i| volume.checked_mul(self.max[i] as i128 - self.min[i] as i128 + 1))
+            .unwrap_or(i128::MAX)
+    }
+
+    /// The part of the region inside the box, None if they do not overlap
+    pub fn intersection(self, other: Region) -> Option<Region> {
+        let region = Region {
+            min: std::array::from_fn(|i| self.min[i].max(other.min[i])),
+            max: std::array::from_fn(|i| self.max[i].min(other.max[i])),
+        };
+        (0..3).all(|i| region.min[i] <= region.max[i]).then_some(region)
+    }
+
+    /// Every cell, x then y then z
+    pub fn cells(self) -> impl Iterator<Item = [i64; 3]> {
+        let (min, max) = (self.min, self.max);
+        (min[0]..=max[0]).flat_map(move |x| {
+            (min[1]..=max[1]).flat_map(move |y| (min[2]..=max[2]).map(move |z| [x, y, z]))
+        })
+    }
+
+    /// Whether the cell is on one of the six faces
+    pub fn is_border(self, c: [i64; 3]) -> bool {
+        (0..3).any(|i| c[i] == self.min[i] || c[i] == self.max[i])
+    }
+}
+
+/// Blocks copied from a region, relative to its lowest corner
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clipboard {
+    pub size: [i64; 3],
+    /// x then y then z, as `Region::cells`
+    pub states: Vec<BlockState>,
+}
+
+impl Clipboard {
+    /// Only empty blocks
+    pub fn empty(size: [i64; 3]) -> Clipboard {
+        Clipboard { size, states: vec![BlockState::new(BlockType::Void); (size[0] * size[1] * size[2]) as usize] }
+    }
+
+    /// `c` is relative to the lowest corner
+    pub fn get(&self, c: [i64; 3]) -> BlockState {
+        self.states[self.index(c)]
+    }
+
+    pub fn set(&mut self, c: [i64; 3], state: BlockState) {
+        let index = self.index(c);
+        self.states[index] = state;
+    }
+
+    fn index(&self, c: [i64; 3]) -> usize {
+        ((c[0] * self.size[1] + c[1]) * self.size[2] + c[2]) as usize
+    }
+}
+
+/// How the clipboard is turned when pasted
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    /// Quarter turns around the y axis, from east towards south
+    pub turns: u32,
+    /// Reflected across the plane perpendicular to the axis, before turning
+    pub mirror: Option<Axis>,
+}
+
+/// Blocks changed by an operation, before then after
+struct Edit {
+    blocks: Vec<([i64; 3], BlockState, BlockState)>,
+}
+
+/// Result of an operation: the blocks changed and the chunks they were in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub blocks: usize,
+    pub chunks: Vec<[i64; 3]>,
+}
+
+#[derive(Default)]
+pub struct WorldEdit {
+    pub clipboard: Option<Clipboard>,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl WorldEdit {
+    pub fn new() -> WorldEdit {
+        WorldEdit::default()
+    }
+
+    /// The part of the region outside of the loaded columns is left out
+    pub fn fill(&mut self, map: &mut Map, region: Region, state: BlockState) -> Result<Summary, EditError> {
+        let Some(region) = limit(map, region)? else {
+            return Ok(Summary::default());
+        };
+        let blocks: Vec<_> = region.cells().map(|c| (c, state)).collect();
+        self.apply(map, blocks)
+    }
+
+    /// Only the blocks of type `from` are changed, whatever their properties
+    pub fn replace(&mut self, map: &mut Map, region: Region, from: BlockType, to: BlockState) -> Result<Summary, EditError> {
+        let Some(region) = limit(map, region)? else {
+            return Ok(Summary::default());
+        };
+        let blocks: Vec<_> = region.cells()
+            .filter(|c| map.get_block(*c).is_some_and(|block| block.block_type == from))
+            .map(|c| (c, to))
+            .collect();
+        self.apply(map, blocks)
+    }
+
+    /// Only the faces of the box, the inside is left as it was. The faces outside of the loaded
+    /// columns are left out
+    pub fn hollow_box(&mut self, map: &mut Map, region: Region, state: BlockState) -> Result<Summary, EditError> {
+        let Some(inside) = limit(map, region)? else {
+            return Ok(Summary::default());
+        };
+        let blocks: Vec<_> = inside.cells().filter(|c| region.is_border(*c)).map(|c| (c, state)).collect();
+        self.apply(map, blocks)
+    }
+
+    /// Ball of the blocks whose centers are within `radius` of the center, only its shell if `hollow`
+    pub fn sphere(&mut self, map: &mut Map, center: [i64; 3], radius: i64, state: BlockState, hollow: bool) -> Result<Summary, EditError> {
+        if radius < 0 {
+            return Err(EditError::NegativeRadius(radius));
+        }
+        // the distances are squared in i128, they cannot overflow
+        let inside = |c: [i64; 3], radius: i64| {
+            (0..3).map(|i| (c[i] as i128 - center[i] as i128).pow(2)).sum::<i128>() <= (radius as i128).pow(2)
+        };
+        // past the end of the coordinates, the box would be bigger than any map
+        let corner = |offset: i64| -> Option<[i64; 3]> {
+            Some([center[0].checked_add(offset)?, center[1].checked_add(offset)?, center[2].checked_add(offset)?])
+        };
+        let (Some(min), Some(max)) = (corner(-radius), corner(radius)) else {
+            return Err(EditError::TooBig);
+        };
+        let Some(region) = limit(map, Region::new(min, max))? else {
+            return Ok(Summary::default());
+        };
+        let blocks: Vec<_> = region.cells()
+            .filter(|c| inside(*c, radius) && !(hollow && inside(*c, radius - 1)))
+            .map(|c| (c, state))
+            .collect();
+        self.apply(map, blocks)
+    }
+
+    /// Puts the blocks of the region in the clipboard, the empty ones too
+    pub fn copy(&mut self, map: &Map, region: Region) -> Result<&Clipboard, EditError> {
+        if region.volume() > MAX_VOLUME as i128 {
+            return Err(EditError::TooBig);
+        }
+        let states = region.cells()
+            .map(|c| map.try_get_block(c).map(|block| block.state()))
+            .collect::<Result<_, _>>()?;
+        Ok(self.clipboard.insert(Clipboard { size: region.size(), states }))
+    }
+
+    /// Pastes the clipboard with its lowest corner at `origin` once transformed. Does nothing if
+    /// the clipboard is empty
+    pub fn paste(&mut self, map: &mut Map, origin: [i64; 3], orientation: Orientation) -> Result<Summary, EditError> {
+        let Some(clipboard) = &self.clipboard else {
+            return Ok(Summary::default());
+        };
+        if clipboard.states.len() as i64 > MAX_VOLUME {
+            return Err(EditError::TooBig);
+        }
+        let turned = |mut c: [i64; 3]| {
+            if let Some(axis) = orientation.mirror {
+                c[axis.index()] = clipboard.size[axis.index()] - 1 - c[axis.index()];
+            }
+            for _ in 0..orientation.turns % 4 {
+                c = [-c[2], c[1], c[0]];
+            }
+            c
+        };
+        let corners = Region::new([0; 3], clipboard.size.map(|s| s - 1));
+        let shift: [i64; 3] = std::array::from_fn(|i| {
+            [corners.min, corners.max].iter().map(|c| turned(*c)[i]).min().unwrap()
+        });
+        let blocks: Vec<_> = corners.cells()
+            .map(|c| {
+                let mut state = clipboard.get(c);
+                if let Some(axis) = orientation.mirror {
+                    state = state.mirrored(axis);
+                }
+                let target = turned(c);
+                // past the end of the coordinates nothing is loaded anyway
+                (std::array::from_fn(|i| origin[i].saturating_add(target[i] - shift[i])), state.rotated(orientation.turns))
+            })
+            .collect();
+        self.apply(map, blocks)
+    }
+
+    /// Puts back the blocks changed by the last operation, None if there is nothing to undo
+    pub fn undo(&mut self, map: &mut Map) -> Option<Result<Summary, EditError>> {
+        let edit = self.undo.pop()?;
+        let blocks: Vec<_> = edit.blocks.iter().rev().map(|(c, before, _)| (*c, *before)).collect();
+        let result = write(map, &blocks, edit.blocks.len());
+        match result {
+            Ok(_) => self.redo.push(edit),
+            Err(_) => self.undo.push(edit),
+        }
+        Some(result)
+    }
+
+    /// Makes again the last operation undone, None if there is nothing to redo
+    pub fn redo(&mut self, map: &mut Map) -> Option<Result<Summary, EditError>> {
+        let edit = self.redo.pop()?;
+        let blocks: Vec<_> = edit.blocks.iter().map(|(c, _, after)| (*c, *after)).collect();
+        let result = write(map, &blocks, edit.blocks.len());
+        match result {
+            Ok(_) => self.undo.push(edit),
+            Err(_) => self.redo.push(edit),
+        }
+        Some(result)
+    }
+
+    /// Writes the blocks that change, and keeps them in the history
+    fn apply(&mut self, map: &mut Map, blocks: Vec<([i64; 3], BlockState)>) -> Result<Summary, EditError> {
+        let mut edit = Edit { blocks: vec![] };
+        for (c, state) in blocks {
+            let before = map.try_get_block(c)?.state();
+            if before != state {
+                edit.blocks.push((c, before, state));
+            }
+        }
+        let blocks: Vec<_> = edit.blocks.iter().map(|(c, _, after)| (*c, *after)).collect();
+        let summary = write(map, &blocks, edit.blocks.len())?;
+        if !edit.blocks.is_empty() {
+            self.undo.push(edit);
+            if self.undo.len() > HISTORY {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+        Ok(summary)
+    }
+}
+
+fn write(map: &mut Map, blocks: &[([i64; 3], BlockState)], count: usize) -> Result<Summary, EditError> {
+    let chunks = map.set_states(blocks)?;
+    Ok(Summary { blocks: count, chunks })
+}
+
+/// The part of the region inside the loaded columns, refused before going through its blocks if
+/// there are too many. None if nothing is left
+fn limit(map: &Map, region: Region) -> Result<Option<Region>, EditError> {
+    let Some((min, max)) = map.bounds() else {
+        return Ok(None);
+    };
+    let Some(region) = region.intersection(Region { min, max }) else {
+        return Ok(None);
+    };
+    if region.volume() > MAX_VOLUME as i128 {
+        return Err(EditError::TooBig);
+    }
+    Ok(Some(region))
+}
+
+/// Why an operation could not be done, nothing is changed then
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditError {
+    /// More than `MAX_VOLUME` blocks
+    TooBig,
+    NegativeRadius(i64),
+    Map(MapError),
+}
+
+impl From<MapError> for EditError {
+    fn from(error: MapError) -> EditError {
+        EditError::Map(error)
+    }
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::TooBig => write!(f, "too many blocks, at most {} can be changed at once", MAX_VOLUME),
+            EditError::NegativeRadius(radius) => write!(f, "the radius must be positive, got {}", radius),
+            EditError::Map(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::ChunkColumn;
+
+    fn map() -> Map {
+        Map::from_columns(vec![ChunkColumn::new([0, 0]), ChunkColumn::new([1, 0])])
+    }
+
+    fn state(name: &str) -> BlockState {
+        BlockState::parse(name).unwrap()
+    }
+
+    fn at(map: &Map, c: [i64; 3]) -> BlockState {
+        map.get_block(c).unwrap().state()
+    }
+
+    #[test]
+    fn fills_and_undoes() {
+        let (mut map, mut edit) = (map(), WorldEdit::new());
+        let region = Region::new([17, 2, 3], [14, 0, 0]);
+        let summary = edit.fill(&mut map, region, state("stone")).unwrap();
+        assert_eq!(summary.blocks, 4 * 3 * 4);
+        assert_eq!(summary.chunks, [[0, 0, 0], [1, 0, 0]]);
+        assert!(region.cells().all(|c| at(&map, c) == state("stone")));
+        // nothing changes, nothing to undo
+        assert_eq!(edit.fill(&mut map, region, state("stone")).unwrap().blocks, 0);
+
+        assert_eq!(edit.undo(&mut map).unwrap().unwrap().blocks, 48);
+        assert!(region.cells().all(|c| at(&map, c) == state("void")));
+        assert!(edit.undo(&mut map).is_none());
+        assert_eq!(edit.redo(&mut map).unwrap().unwrap().blocks, 48);
+        assert_eq!(at(&map, [14, 0, 0]), state("stone"));
+        assert!(edit.redo(&mut map).is_none());
+    }
+
+    #[test]
+    fn replaces_whatever_the_properties() {
+        let (mut map, mut edit) = (map(), WorldEdit::new());
+        map.set_state([1, 1, 1], state("log[axis=x]")).unwrap();
+        map.set_state([2, 1, 1], state("log[axis=z]")).unwrap();
+        map.set_state([3, 1, 1], state("stone")).unwrap();
+        let summary = edit.replace(&mut map, Region::new([0, 0, 0], [5, 5, 5]), BlockType::Log, state("planks")).unwrap();
+        assert_eq!(summary.blocks, 2);
+        assert_eq!(at(&map, [1, 1, 1]), state("planks"));
+        assert_eq!(at(&map, [2, 1, 1]), state("planks"));
+        assert_eq!(at(&map, [3, 1, 1]), state("stone"));
+    }
+
+    #[test]
+    fn makes_hollow_shapes() {
+        let (mut map, mut edit) = (map(), WorldEdit::new());
+        assert_eq!(edit.hollow_box(&mut map, Region::new([0, 0, 0], [2, 2, 2]), state("stone")).unwrap().blocks, 26);
+        assert_eq!(at(&map, [1, 1, 1]), state("void"));
+        assert_eq!(edit.sphere(&mut map, [8, 10, 8], 1, state("stone"), false).unwrap().blocks, 7);
+        // 33 blocks within 2, less the 7 within 1
+        assert_eq!(edit.sphere(&mut map, [8, 30, 8], 2, state("stone"), true).unwrap().blocks, 26);
+        assert_eq!(at(&map, [8, 30, 8]), state("void"));
+        assert_eq!(at(&map, [8, 32, 8]), state("stone"));
+    }
+
+    #[test]
+    fn pastes_turned_and_mirrored() {
+        let (mut map, mut edit) = (map(), WorldEdit::new());
+        map.set_state([0, 5, 0], state("stone")).unwrap();
+        map.set_state([1, 5, 0], state("planks")).unwrap();
+        map.set_state([2, 5, 0], state("log[axis=x]")).unwrap();
+        let clipboard = edit.copy(&map, Region::new([0, 5, 0], [2, 5, 0])).unwrap();
+        assert_eq!(clipboard.size, [3, 1, 1]);
+
+        // a quarter turn puts +x towards +z
+        let turned = Orientation { turns: 1, mirror: None };
+        assert_eq!(edit.paste(&mut map, [5, 5, 5], turned).unwrap().blocks, 3);
+        assert_eq!(at(&map, [5, 5, 5]), state("stone"));
+        assert_eq!(at(&map, [5, 5, 6]), state("planks"));
+        assert_eq!(at(&map, [5, 5, 7]), state("log[axis=z]"));
+
+        let mirrored = Orientation { turns: 0, mirror: Some(Axis::X) };
+        edit.paste(&mut map, [5, 8, 5], mirrored).unwrap();
+        assert_eq!(at(&map, [5, 8, 5]), state("log[axis=x]"));
+        assert_eq!(at(&map, [6, 8, 5]), state("planks"));
+        assert_eq!(at(&map, [7, 8, 5]), state("stone"));
+
+        edit.undo(&mut map).unwrap().unwrap();
+        assert_eq!(at(&map, [5, 8, 5]), state("void"));
+        assert_eq!(at(&map, [5, 5, 7]), state("log[axis=z]"));
+    }
+
+    #[test]
+    fn cuts_the_regions_to_the_loaded_columns() {
+        let (mut map, mut edit) = (map(), WorldEdit::new());
+        let everything = Region::new([i64::MIN; 3], [i64::MAX; 3]);
+        let summary = edit.fill(&mut map, everything, state("stone")).unwrap();
+        assert_eq!(summary.blocks, 2 * 16 * 16 * 64);
+        let summary = edit.sphere(&mut map, [i64::MAX - 10, 0, 0], 3, state("dirt"), false).unwrap();
+        assert_eq!(summary.blocks, 0);
+    }
+
+    #[test]
+    fn refuses_too_many_blocks() {
+        let columns = (-4..5).flat_map(|x| (-4..5).map(move |z| ChunkColumn::new([x, z]))).collect();
+        let (mut map, mut edit) = (Map::from_columns(columns), WorldEdit::new());
+        let everything = Region::new([i64::MIN; 3], [i64::MAX; 3]);
+        assert_eq!(edit.fill(&mut map, everything, state("stone")), Err(EditError::TooBig));
+        assert_eq!(edit.copy(&map, everything).unwrap_err(), EditError::TooBig);
+        assert_eq!(edit.sphere(&mut map, [i64::MAX - 1, 0, 0], 2, state("stone"), false), Err(EditError::TooBig));
+        assert_eq!(edit.sphere(&mut map, [0, 0, 0], -1, state("stone"), false), Err(EditError::NegativeRadius(-1)));
+        assert_eq!(map.get_block([0, 0, 0]).unwrap().block_type, BlockType::Void);
+    }
+}
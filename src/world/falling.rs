@@ -0,0 +1,214 @@
+//! Blocks such as sand that fall when nothing holds them, as entities between two cells
+
+use super::block::BlockType;
+use super::map::{self, Map};
+
+/// Blocks per tick added to the speed
+pub const GRAVITY: f32 = 0.04;
+pub const TERMINAL_VELOCITY: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fall {
+    Falling,
+    /// Cell where the block solidifies
+    Landed([i64; 3]),
+    OutOfWorld,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FallingBlock {
+    pub block_type: BlockType,
+    /// Center of the block in block coordinates, only y is not an integer
+    pub position: [f32; 3],
+    /// Blocks per tick, downwards
+    pub velocity: f32,
+}
+
+impl FallingBlock {
+    pub fn new(block_type: BlockType, coordinates: [i64; 3]) -> FallingBlock {
+        FallingBlock {
+            block_type,
+            position: coordinates.map(|c| c as f32),
+            velocity: 0.0,
+        }
+    }
+
+    /// Moves the block by one tick, it lands on the first cell it cannot go through. Over a
+    /// column that is not loaded it stops where it is until the column comes back
+    pub fn tick(&mut self, map: &Map) -> Fall {
+        self.velocity = (self.velocity + GRAVITY).min(TERMINAL_VELOCITY);
+        let next = self.position[1] - self.velocity;
+
+        let [x, y, z] = self.position;
+        let (x, z) = (x as i64, z as i64);
+        // every cell passed during the tick, from the top
+        let mut cell = y.round() as i64 - 1;
+        while cell as f32 >= next.round() {
+            match map.get_block([x, cell, z]) {
+                Some(block) if !block.block_type.is_replaceable() => return Fall::Landed([x, cell + 1, z]),
+                None if map::is_in_bounds(cell) => {
+                    self.position[1] = (cell + 1) as f32;
+                    self.velocity = 0.0;
+                    return Fall::Falling;
+                }
+                _ => cell -= 1,
+            }
+        }
+
+        self.position[1] = next;
+        // below the bottom of the world
+        if next < map::MIN_Y as f32 {
+            Fall::OutOfWorld
+        } else {
+            Fall::Falling
+        }
+    }
+}
+
+/// Puts the block where it landed, or on top of the blocks already there such as another one
+/// landed during the same tick. Returns the cell, None if they go up to the top of the world
+pub fn land(map: &mut Map, block_type: BlockType, cell: [i64; 3]) -> Option<[i64; 3]> {
+    let [x, y, z] = cell;
+    let cell = (y..map::MIN_Y + map::HEIGHT)
+        .map(|y| [x, y, z])
+        .find(|cell| map.get_block(*cell).is_some_and(|block| block.block_type.is_replaceable()))?;
+    map.set_block(cell, block_type).ok()?;
+    Some(cell)
+}
+
+/// Ticks between a change next to a gravity block and the check of its support
+pub const DELAY: u64 = 2;
+
+/// Turns the gravity block into a falling block when the cell below it is replaceable
+pub fn update(map: &mut Map, coordinates: [i64; 3]) -> Option<FallingBlock> {
+    let block_type = map.get_block(coordinates)?.block_type;
+    let below = [coordinates[0], coordinates[1] - 1, coordinates[2]];
+    let unsupported = map.get_block(below).is_some_and(|block| block.block_type.is_replaceable());
+    if !block_type.has_gravity() || !unsupported {
+        return None;
+    }
+    map.set_block(coordinates, BlockType::Void).ok()?;
+    Some(FallingBlock::new(block_type, coordinates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::ChunkColumn;
+    use crate::world::world::World;
+
+    /// Stone at y = 10
+    fn map() -> Map {
+        let mut map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        map.set_block([2, 10, 2], BlockType::Stone).unwrap();
+        map
+    }
+
+    /// A floor of stone at y = 10, for the blocks falling with the ticks of the world
+    fn world() -> World {
+        let mut world = World::new();
+        world.map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        for x in 0..16 {
+            for z in 0..16 {
+                world.map.set_block([x, 10, z], BlockType::Stone).unwrap();
+            }
+        }
+        world
+    }
+
+    fn fall(block: &mut FallingBlock, map: &Map) -> (Fall, u32) {
+        let mut ticks = 1;
+        loop {
+            match block.tick(map) {
+                Fall::Falling => ticks += 1,
+                fall => return (fall, ticks),
+            }
+        }
+    }
+
+    #[test]
+    fn falls_faster_and_faster() {
+        let map = map();
+        let mut block = FallingBlock::new(BlockType::Sand, [2, 30, 2]);
+        block.tick(&map);
+        assert_eq!(block.velocity, GRAVITY);
+        assert_eq!(block.position[1], 30.0 - GRAVITY);
+        block.tick(&map);
+        assert_eq!(block.velocity, 2.0 * GRAVITY);
+        // on top of the stone, whatever the speed
+        assert_eq!(fall(&mut block, &map).0, Fall::Landed([2, 11, 2]));
+        let mut fast = FallingBlock { velocity: TERMINAL_VELOCITY, ..FallingBlock::new(BlockType::Sand, [2, 12, 2]) };
+        assert_eq!(fast.tick(&map), Fall::Landed([2, 11, 2]));
+    }
+
+    #[test]
+    fn leaves_the_world_without_ground() {
+        let map = map();
+        let mut block = FallingBlock::new(BlockType::Gravel, [3, 5, 3]);
+        assert_eq!(fall(&mut block, &map).0, Fall::OutOfWorld);
+    }
+
+    #[test]
+    fn waits_over_the_columns_not_loaded() {
+        let mut map = map();
+        let mut block = FallingBlock::new(BlockType::Sand, [20, 30, 2]);
+        for _ in 0..100 {
+            assert_eq!(block.tick(&map), Fall::Falling);
+        }
+        assert_eq!(block.position, [20.0, 30.0, 2.0]);
+        map.insert_column(ChunkColumn::new([1, 0]));
+        map.set_block([20, 10, 2], BlockType::Stone).unwrap();
+        assert_eq!(fall(&mut block, &map).0, Fall::Landed([20, 11, 2]));
+    }
+
+    #[test]
+    fn stacks_on_blocks_landed_first() {
+        let mut map = map();
+        assert_eq!(land(&mut map, BlockType::Sand, [2, 11, 2]), Some([2, 11, 2]));
+        assert_eq!(land(&mut map, BlockType::Gravel, [2, 11, 2]), Some([2, 12, 2]));
+        assert_eq!(map.get_block([2, 12, 2]).unwrap().block_type, BlockType::Gravel);
+        // nothing left above
+        map.set_block([2, 63, 2], BlockType::Stone).unwrap();
+        assert_eq!(land(&mut map, BlockType::Sand, [2, 63, 2]), None);
+    }
+
+    #[test]
+    fn drops_unsupported_blocks() {
+        let mut world = world();
+        world.map.set_block([3, 20, 3], BlockType::Sand).unwrap();
+        world.map.set_block([3, 21, 3], BlockType::Gravel).unwrap();
+        for _ in 0..100 {
+            world.tick();
+        }
+        assert!(world.falling.is_empty());
+        assert_eq!(world.map.get_block([3, 11, 3]).unwrap().block_type, BlockType::Sand);
+        assert_eq!(world.map.get_block([3, 12, 3]).unwrap().block_type, BlockType::Gravel);
+        assert_eq!(world.map.get_block([3, 20, 3]).unwrap().block_type, BlockType::Void);
+    }
+
+    #[test]
+    fn stacks_blocks_landing_together() {
+        let mut world = world();
+        // the upper one moves first, it goes down to the floor before the lower one lands there
+        for y in [12, 11] {
+            world.falling.push(FallingBlock { velocity: TERMINAL_VELOCITY, ..FallingBlock::new(BlockType::Sand, [3, y, 3]) });
+        }
+        world.tick();
+        assert!(world.falling.is_empty());
+        assert_eq!(world.map.get_block([3, 11, 3]).unwrap().block_type, BlockType::Sand);
+        assert_eq!(world.map.get_block([3, 12, 3]).unwrap().block_type, BlockType::Sand);
+    }
+
+    #[test]
+    fn only_falls_without_support() {
+        let mut map = map();
+        map.set_block([2, 11, 2], BlockType::Sand).unwrap();
+        assert!(update(&mut map, [2, 11, 2]).is_none());
+        map.set_block([2, 20, 2], BlockType::Sand).unwrap();
+        let block = update(&mut map, [2, 20, 2]).unwrap();
+        assert_eq!(block.position, [2.0, 20.0, 2.0]);
+        assert_eq!(map.get_block([2, 20, 2]).unwrap().block_type, BlockType::Void);
+        map.set_block([2, 30, 2], BlockType::Stone).unwrap();
+        assert!(update(&mut map, [2, 30, 2]).is_none());
+    }
+}
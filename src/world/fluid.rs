@@ -0,0 +1,289 @@
+//! Water and lava, flowing through scheduled block updates: a block next to a change is
+//! updated `Fluid::rate` ticks later, and updates its neighbours in turn when it changes
+
+use super::block::BlockType;
+use super::map::Map;
+use super::state::{BlockState, Property};
+
+/// Level of a source block, it never dries up
+pub const SOURCE: u8 = 0;
+/// Weakest flowing level, the fluid does not spread further
+pub const MAX_LEVEL: u8 = 7;
+
+const HORIZONTAL: [[i64; 3]; 4] = [[1, 0, 0], [-1, 0, 0], [0, 0, 1], [0, 0, -1]];
+const NEIGHBOURS: [[i64; 3]; 6] = [[1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    pub const ALL: [Fluid; 2] = [Fluid::Water, Fluid::Lava];
+
+    pub fn of(block_type: BlockType) -> Option<Fluid> {
+        match block_type {
+            BlockType::Water => Some(Fluid::Water),
+            BlockType::Lava => Some(Fluid::Lava),
+            _ => None,
+        }
+    }
+
+    pub fn block_type(self) -> BlockType {
+        match self {
+            Fluid::Water => BlockType::Water,
+            Fluid::Lava => BlockType::Lava,
+        }
+    }
+
+    /// Ticks between two steps of the flow
+    pub fn rate(self) -> u64 {
+        match self {
+            Fluid::Water => 5,
+            Fluid::Lava => 30,
+        }
+    }
+
+    /// Levels lost for each block flowed sideways, lava does not go as far as water
+    fn decay(self) -> u8 {
+        match self {
+            Fluid::Water => 1,
+            Fluid::Lava => 2,
+        }
+    }
+
+    /// Two sources next to each other create a new one between them
+    fn is_infinite(self) -> bool {
+        self == Fluid::Water
+    }
+}
+
+/// Level and direction of the fluid in a block
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Flow {
+    level: u8,
+    /// Flowing down from the block above, spreads sideways like a source once it lands
+    falling: bool,
+}
+
+impl Flow {
+    fn is_source(self) -> bool {
+        self.level == SOURCE && !self.falling
+    }
+
+    fn state(self, fluid: Fluid) -> BlockState {
+        BlockState::new(fluid.block_type())
+            .with(Property::Level, self.level)
+            .with(Property::Falling, u8::from(self.falling))
+    }
+}
+
+/// Ticks before an update of the block, when something changed next to it. None if no fluid
+/// can flow there
+pub fn delay(map: &Map, coordinates: [i64; 3]) -> Option<u64> {
+    fluid_at(map, coordinates).map(Fluid::rate)
+}
+
+/// Flows into or out of the block, returns true if it changed
+pub fn update(map: &mut Map, coordinates: [i64; 3]) -> bool {
+    let Some(block) = map.get_block(coordinates) else {
+        return false;
+    };
+    let block_type = block.block_type;
+
+    // lava touching water turns into stone
+    if block_type == BlockType::Lava {
+        let touches_water = NEIGHBOURS.iter()
+            .any(|offset| flow(map, add(coordinates, *offset), Fluid::Water).is_some());
+        if touches_water {
+            return map.set_block(coordinates, BlockType::Stone).is_ok();
+        }
+    }
+
+    let Some(fluid) = fluid_at(map, coordinates) else {
+        return false;
+    };
+    let current = flow(map, coordinates, fluid);
+    if current.is_some_and(Flow::is_source) {
+        return false;
+    }
+    let next = next_flow(map, coordinates, fluid);
+    if next == current {
+        return false;
+    }
+    let result = match next {
+        Some(flow) => map.set_state(coordinates, flow.state(fluid)),
+        None => map.set_block(coordinates, BlockType::Void),
+    };
+    result.is_ok()
+}
+
+/// Fluid of the block, or of the first fluid able to flow into it when it is empty
+fn fluid_at(map: &Map, coordinates: [i64; 3]) -> Option<Fluid> {
+    let block = map.get_block(coordinates)?;
+    if let Some(fluid) = Fluid::of(block.block_type) {
+        return Some(fluid);
+    }
+    if block.block_type != BlockType::Void {
+        return None;
+    }
+    let sides = HORIZONTAL.iter().chain([[0, 1, 0]].iter());
+    Fluid::ALL.into_iter().find(|&fluid| {
+        sides.clone().any(|offset| flow(map, add(coordinates, *offset), fluid).is_some())
+    })
+}
+
+/// Flow of an empty or flowing block after its update, None if it has no fluid
+fn next_flow(map: &Map, coordinates: [i64; 3], fluid: Fluid) -> Option<Flow> {
+    if flow(map, add(coordinates, [0, 1, 0]), fluid).is_some() {
+        return Some(Flow { level: SOURCE, falling: true });
+    }
+
+    if fluid.is_infinite() {
+        let sources = HORIZONTAL.iter()
+            .filter(|offset| flow(map, add(coordinates, **offset), fluid).is_some_and(Flow::is_source))
+            .count();
+        let below = add(coordinates, [0, -1, 0]);
+        let supported = flow(map, below, fluid).is_some_and(Flow::is_source) || is_solid(map, below);
+        if sources >= 2 && supported {
+            return Some(Flow { level: SOURCE, falling: false });
+        }
+    }
+
+    HORIZONTAL.iter()
+        .filter_map(|offset| {
+            let neighbour = add(coordinates, *offset);
+            let other = flow(map, neighbour, fluid)?;
+            spreads_sideways(map, neighbour, other, fluid).then_some(other)
+        })
+        .map(|other| if other.falling { SOURCE } else { other.level } + fluid.decay())
+        .min()
+        .filter(|&level| level <= MAX_LEVEL)
+        .map(|level| Flow { level, falling: false })
+}
+
+/// Sources always spread, flowing blocks only once they cannot go down anymore
+fn spreads_sideways(map: &Map, coordinates: [i64; 3], current: Flow, fluid: Fluid) -> bool {
+    if current.is_source() {
+        return true;
+    }
+    let below = add(coordinates, [0, -1, 0]);
+    flow(map, below, fluid).is_none() && !is_empty(map, below)
+}
+
+fn flow(map: &Map, coordinates: [i64; 3], fluid: Fluid) -> Option<Flow> {
+    let block = map.get_block(coordinates)?;
+    let state = block.state();
+    (Fluid::of(block.block_type) == Some(fluid))
+        .then(|| Flow { level: state.level(), falling: state.get(Property::Falling) == Some(1) })
+}
+
+/// Blocks outside of the loaded chunks are walls
+fn is_empty(map: &Map, coordinates: [i64; 3]) -> bool {
+    map.get_block(coordinates).is_some_and(|block| block.block_type == BlockType::Void)
+}
+
+/// Neither empty nor a fluid
+fn is_solid(map: &Map, coordinates: [i64; 3]) -> bool {
+    map.get_block(coordinates).is_none_or(|block| block.block_type != BlockType::Void && Fluid::of(block.block_type).is_none())
+}
+
+fn add(a: [i64; 3], b: [i64; 3]) -> [i64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::ChunkColumn;
+    use crate::world::world::World;
+
+    /// A floor of stone at y = 10, the fluids spread on top of it with the ticks of the world
+    fn world() -> World {
+        let mut world = World::new();
+        world.map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        for x in 0..16 {
+            for z in 0..16 {
+                world.map.set_block([x, 10, z], BlockType::Stone).unwrap();
+            }
+        }
+        world
+    }
+
+    fn state(world: &World, coordinates: [i64; 3]) -> String {
+        world.map.get_block(coordinates).unwrap().state().to_string()
+    }
+
+    fn run(world: &mut World, ticks: u32) {
+        for _ in 0..ticks {
+            world.tick();
+        }
+    }
+
+    #[test]
+    fn spreads_fluids_around_their_sources() {
+        let mut world = world();
+        world.map.set_block([3, 11, 3], BlockType::Water).unwrap();
+        world.map.set_block([12, 11, 12], BlockType::Lava).unwrap();
+        run(&mut world, 400);
+        // the sources never change, water loses a level for each block and lava two
+        assert_eq!(state(&world, [3, 11, 3]), "water[level=0,falling=false]");
+        assert_eq!(state(&world, [4, 11, 3]), "water[level=1,falling=false]");
+        assert_eq!(state(&world, [5, 11, 5]), "water[level=4,falling=false]");
+        assert_eq!(state(&world, [10, 11, 3]), "water[level=7,falling=false]");
+        assert_eq!(state(&world, [11, 11, 3]), "void");
+        assert_eq!(state(&world, [12, 11, 12]), "lava[level=0,falling=false]");
+        assert_eq!(state(&world, [13, 11, 12]), "lava[level=2,falling=false]");
+        assert_eq!(state(&world, [12, 11, 9]), "lava[level=6,falling=false]");
+        assert_eq!(state(&world, [12, 11, 8]), "void");
+    }
+
+    #[test]
+    fn falls_and_spreads_where_it_lands() {
+        let mut world = world();
+        // walled in, it can only go down
+        for wall in [[2, 15, 3], [4, 15, 3], [3, 15, 2], [3, 15, 4]] {
+            world.map.set_block(wall, BlockType::Stone).unwrap();
+        }
+        world.map.set_block([3, 15, 3], BlockType::Water).unwrap();
+        run(&mut world, 200);
+        for y in 11..15 {
+            assert_eq!(state(&world, [3, y, 3]), "water[level=0,falling=true]");
+        }
+        assert_eq!(state(&world, [4, 14, 3]), "void");
+        assert_eq!(state(&world, [4, 11, 3]), "water[level=1,falling=false]");
+        assert_eq!(state(&world, [3, 11, 10]), "water[level=7,falling=false]");
+        assert_eq!(state(&world, [3, 11, 11]), "void");
+    }
+
+    #[test]
+    fn dries_up_without_its_source() {
+        let mut world = world();
+        world.map.set_block([3, 11, 3], BlockType::Water).unwrap();
+        run(&mut world, 200);
+        world.map.set_block([3, 11, 3], BlockType::Void).unwrap();
+        run(&mut world, 400);
+        assert!((0..16).all(|x| (0..16).all(|z| state(&world, [x, 11, z]) == "void")));
+    }
+
+    #[test]
+    fn creates_water_sources_between_two_others() {
+        let mut world = world();
+        world.map.set_block([3, 11, 3], BlockType::Water).unwrap();
+        world.map.set_block([5, 11, 3], BlockType::Water).unwrap();
+        run(&mut world, 100);
+        assert_eq!(state(&world, [4, 11, 3]), "water[level=0,falling=false]");
+        assert_eq!(state(&world, [4, 11, 4]), "water[level=1,falling=false]");
+    }
+
+    #[test]
+    fn turns_lava_touching_water_into_stone() {
+        let mut world = world();
+        world.map.set_block([3, 11, 3], BlockType::Lava).unwrap();
+        world.map.set_block([3, 11, 5], BlockType::Water).unwrap();
+        run(&mut world, 200);
+        assert_eq!(state(&world, [3, 11, 3]), "stone");
+        assert_eq!(state(&world, [3, 11, 5]), "water[level=0,falling=false]");
+    }
+}
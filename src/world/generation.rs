@@ -0,0 +1,478 @@
+//! Terrain of the new chunks, shaped by their biomes and carved by caves, and decorated with
+//! features: trees, boulders, ore veins and small structures. Everything comes from the seed and
+//! the coordinates, so the chunks are the same whatever the order they are generated in. Whole
+//! columns are generated at once, the blocks of a feature crossing into a column not generated yet
+//! wait in a queue until that column is generated
+
+use std::collections::{HashMap, HashSet};
+
+use super::biome::Biome;
+use super::block::BlockType;
+use super::map::{is_in_bounds, Chunk, ChunkColumn, Map, CHUNK_SIZE, MIN_Y, SECTIONS};
+use super::noise;
+use super::random::{self, Random};
+use super::state::{Axis, BlockState, Property};
+
+/// Blocks between two values of the height noise
+const TERRAIN_SCALE: f32 = 24.0;
+/// Blocks between two values of the temperature and humidity noises
+const CLIMATE_SCALE: f32 = 96.0;
+/// Side of the squares the relief of the biomes is interpolated across
+const BLEND_SIZE: i64 = 8;
+/// Filler blocks between the surface and the stone
+const FILLER_DEPTH: i64 = 3;
+
+/// Blocks between two values of the overhang noise
+const OVERHANG_SCALE: f32 = 12.0;
+/// Part of the height variation of the biome the ground can move up or down, making overhangs
+const OVERHANG: f32 = 0.4;
+/// Large caverns ("cheese") where the cavern noise is above the threshold
+const CAVERN_SCALE: [f32; 3] = [24.0, 12.0, 24.0];
+const CAVERN_THRESHOLD: f32 = 0.68;
+/// Narrow tunnels ("spaghetti") where two noises are both close to the middle
+const TUNNEL_SCALE: f32 = 20.0;
+const TUNNEL_WIDTH: f32 = 0.04;
+/// Ground kept above the caves, so they do not riddle the surface with holes
+const CAVE_ROOF: f32 = 3.0;
+
+pub const MAX_TREES: u64 = 8;
+const BOULDER_CHANCE: u64 = 3;
+const VEINS_PER_CHUNK: u32 = 6;
+const STRUCTURE_CHANCE: u64 = 8;
+
+/// Layers of the hut from the floor up, rows along z: `#` cobblestone, `=` planks,
+/// `.` emptied, and ` ` left as it was
+const HUT: [[&str; 5]; 5] = [
+    ["#####", "#####", "#####", "#####", "#####"],
+    ["#===#", "=...=", "=....", "=...=", "#===#"],
+    ["#===#", "=...=", "=....", "=...=", "#===#"],
+    ["#===#", "=...=", "=...=", "=...=", "#===#"],
+    ["     ", " === ", " === ", " === ", "     "],
+];
+
+/// What the terrain must have in a cell for a feature to put its block there
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Replace {
+    Air,
+    Stone,
+    /// Anything but bedrock, for the structures that level the ground
+    Solid,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    coordinates: [i64; 3],
+    state: BlockState,
+    replace: Replace,
+}
+
+/// When two features want the same cell the lowest rank wins: the features of the chunk itself,
+/// then those of the neighbours by coordinates, then by order in the feature
+type Rank = (bool, [i64; 3], usize);
+
+pub struct Generator {
+    pub seed: u64,
+    /// Blocks of the features waiting for their chunk
+    pending: HashMap<[i64; 3], Vec<(Placement, Rank)>>,
+    /// Rank of the feature owning each decorated cell, by column. Forgotten once the column and
+    /// its neighbours are generated, no feature can reach it anymore
+    claims: HashMap<[i64; 2], HashMap<[i64; 3], Rank>>,
+    generated: HashSet<[i64; 2]>,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator {
+            seed,
+            pending: HashMap::new(),
+            claims: HashMap::new(),
+            generated: HashSet::new(),
+        }
+    }
+
+    pub fn biome(&self, x: i64, z: i64) -> Biome {
+        let (x, z) = (x as f32 / CLIMATE_SCALE, z as f32 / CLIMATE_SCALE);
+        let temperature = noise::fractal_2d(self.seed.wrapping_add(100), x, z, 2);
+        let humidity = noise::fractal_2d(self.seed.wrapping_add(200), x, z, 2);
+        Biome::from_climate(temperature, humidity)
+    }
+
+    /// Relief of the column. The base height and variation of the biomes at the corners of
+    /// the `BLEND_SIZE` square around the column are interpolated, so there are no cliffs
+    /// at the borders of the biomes
+    fn column(&self, x: i64, z: i64) -> Column {
+        let (x0, z0) = (x.div_euclid(BLEND_SIZE) * BLEND_SIZE, z.div_euclid(BLEND_SIZE) * BLEND_SIZE);
+        let tx = (x - x0) as f32 / BLEND_SIZE as f32;
+        let tz = (z - z0) as f32 / BLEND_SIZE as f32;
+        let relief = |dx: i64, dz: i64| {
+            let biome = self.biome(x0 + dx * BLEND_SIZE, z0 + dz * BLEND_SIZE);
+            [biome.base_height(), biome.height_variation()]
+        };
+        let corners = [relief(0, 0), relief(1, 0), relief(0, 1), relief(1, 1)];
+        let [base, variation]: [f32; 2] = std::array::from_fn(|i| {
+            let near = corners[0][i] + (corners[1][i] - corners[0][i]) * tx;
+            let far = corners[2][i] + (corners[3][i] - corners[2][i]) * tx;
+            near + (far - near) * tz
+        });
+        let noise = noise::fractal_2d(self.seed, x as f32 / TERRAIN_SCALE, z as f32 / TERRAIN_SCALE, 3);
+        Column {
+            height: base + noise * variation,
+            overhang: variation * OVERHANG,
+            biome: self.biome(x, z),
+        }
+    }
+
+    /// Whether the density at the block makes it ground, the height map moved up or down by
+    /// a 3d noise
+    fn is_ground(&self, column: &Column, coordinates: [i64; 3]) -> bool {
+        let [x, y, z] = coordinates.map(|c| c as f32);
+        let density = noise::fractal_3d(self.seed.wrapping_add(300), x / OVERHANG_SCALE, y / OVERHANG_SCALE, z / OVERHANG_SCALE, 2);
+        y <= column.height + (density - 0.5) * 2.0 * column.overhang
+    }
+
+    fn is_cave(&self, coordinates: [i64; 3]) -> bool {
+        let [x, y, z] = coordinates.map(|c| c as f32);
+        let cavern = noise::fractal_3d(self.seed.wrapping_add(400), x / CAVERN_SCALE[0], y / CAVERN_SCALE[1], z / CAVERN_SCALE[2], 2);
+        if cavern > CAVERN_THRESHOLD {
+            return true;
+        }
+        let tunnel = |seed: u64| (noise::value_3d(seed, x / TUNNEL_SCALE, y / TUNNEL_SCALE, z / TUNNEL_SCALE) - 0.5).abs();
+        tunnel(self.seed.wrapping_add(500)) < TUNNEL_WIDTH && tunnel(self.seed.wrapping_add(600)) < TUNNEL_WIDTH
+    }
+
+    /// `count` blocks of the column from `bottom` up, before the features are added
+    fn column_blocks(&self, x: i64, z: i64, bottom: i64, count: usize) -> Vec<BlockType> {
+        let column = self.column(x, z);
+        // the ground above each block decides between the surface, the filler and the stone
+        let ground: Vec<bool> = (0..count + FILLER_DEPTH as usize)
+            .map(|i| self.is_ground(&column, [x, bottom + i as i64, z]))
+            .collect();
+        let blocks = (0..count).map(|i| {
+            let y = bottom + i as i64;
+            if y == MIN_Y {
+                return BlockType::Bedrock;
+            }
+            if y < MIN_Y || !ground[i] || ((y as f32) < column.height - CAVE_ROOF && self.is_cave([x, y, z])) {
+                return BlockType::Void;
+            }
+            match ground[i + 1..].iter().take_while(|ground| **ground).count() as i64 {
+                0 => column.biome.surface(),
+                depth if depth < FILLER_DEPTH => column.biome.filler(),
+                _ => BlockType::Stone,
+            }
+        });
+        blocks.collect()
+    }
+
+    /// Block before the features are added
+    pub fn terrain(&self, coordinates: [i64; 3]) -> BlockType {
+        let [x, y, z] = coordinates;
+        self.column_blocks(x, z, y, 1)[0]
+    }
+
+    /// Highest ground of the column, the features stand on it
+    pub fn surface(&self, x: i64, z: i64) -> i64 {
+        let column = self.column(x, z);
+        let top = (column.height + column.overhang).ceil() as i64;
+        (MIN_Y..=top).rev().find(|y| self.is_ground(&column, [x, *y, z])).unwrap_or(MIN_Y)
+    }
+
+    /// Chunk with only the terrain and its caves
+    pub fn terrain_chunk(&self, coordinates: [i64; 3]) -> Chunk {
+        let mut chunk = Chunk::new(coordinates);
+        let origin = chunk.origin();
+        for x in 0..CHUNK_SIZE as usize {
+            for z in 0..CHUNK_SIZE as usize {
+                let blocks = self.column_blocks(origin[0] + x as i64, origin[2] + z as i64, origin[1], CHUNK_SIZE as usize);
+                for (y, block_type) in blocks.into_iter().enumerate() {
+                    if block_type != BlockType::Void {
+                        chunk.set([x, y, z], BlockState::new(block_type));
+                    }
+                }
+            }
+        }
+        chunk
+    }
+
+    /// Generates the columns within `radius` chunks of the origin
+    pub fn generate_area(&mut self, map: &mut Map, radius: i64) {
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                self.generate(map, [x, z]);
+            }
+        }
+    }
+
+    /// Column with only the terrain and its biomes, from the bottom to the top of the world
+    pub fn terrain_column(&self, coordinates: [i64; 2]) -> ChunkColumn {
+        let bottom = MIN_Y.div_euclid(CHUNK_SIZE);
+        let sections = (bottom..bottom + SECTIONS)
+            .map(|y| self.terrain_chunk([coordinates[0], y, coordinates[1]]))
+            .collect();
+        let mut column = ChunkColumn::from_sections(coordinates, sections).unwrap();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let biome = self.biome(coordinates[0] * CHUNK_SIZE + x, coordinates[1] * CHUNK_SIZE + z);
+                column.set_biome([x as usize, z as usize], biome);
+            }
+        }
+        column
+    }
+
+    /// Adds the column to the map with its features, nothing happens if it already exists
+    pub fn generate(&mut self, map: &mut Map, coordinates: [i64; 2]) {
+        if map.column(coordinates).is_some() || !self.generated.insert(coordinates) {
+            return;
+        }
+        map.insert_column(self.terrain_column(coordinates));
+
+        // columns whose heightmap is out of date
+        let mut changed = HashSet::from([coordinates]);
+        let bottom = MIN_Y.div_euclid(CHUNK_SIZE);
+        for y in bottom..bottom + SECTIONS {
+            let section = [coordinates[0], y, coordinates[1]];
+            // blocks of the neighbours generated before
+            for (placement, rank) in self.pending.remove(&section).unwrap_or_default() {
+                self.apply(map, placement, rank);
+            }
+            for (index, placement) in self.features(section).into_iter().enumerate() {
+                if !is_in_bounds(placement.coordinates[1]) {
+                    continue;
+                }
+                let target = chunk_of(placement.coordinates);
+                let rank = (target != section, section, index);
+                if self.generated.contains(&[target[0], target[2]]) {
+                    changed.insert([target[0], target[2]]);
+                    self.apply(map, placement, rank);
+                } else {
+                    self.pending.entry(target).or_default().push((placement, rank));
+                }
+            }
+        }
+        for column in changed {
+            map.refresh_heightmap(column);
+        }
+
+        let around = |[x, z]: [i64; 2]| (-1..=1).flat_map(move |dx| (-1..=1).map(move |dz| [x + dx, z + dz]));
+        for column in around(coordinates) {
+            if around(column).all(|neighbour| self.generated.contains(&neighbour)) {
+                self.claims.remove(&column);
+            }
+        }
+    }
+
+    /// Puts the block if the terrain allows it and no feature with a lower rank is there
+    fn apply(&mut self, map: &mut Map, placement: Placement, rank: Rank) {
+        let coordinates = placement.coordinates;
+        let terrain = self.terrain(coordinates);
+        let allowed = match placement.replace {
+            Replace::Air => terrain == BlockType::Void,
+            Replace::Stone => terrain == BlockType::Stone,
+            Replace::Solid => terrain != BlockType::Bedrock,
+        };
+        let claims = self.claims.entry([coordinates[0].div_euclid(CHUNK_SIZE), coordinates[2].div_euclid(CHUNK_SIZE)]).or_default();
+        if !allowed || claims.get(&coordinates).is_some_and(|claim| *claim <= rank) {
+            return;
+        }
+        claims.insert(coordinates, rank);
+        let Some(chunk) = map.chunk_mut(chunk_of(coordinates)) else {
+            return;
+        };
+        chunk.set(coordinates.map(|c| c.rem_euclid(CHUNK_SIZE) as usize), placement.state);
+    }
+
+    /// Blocks of the features starting in the chunk, they may go into its neighbours
+    fn features(&self, coordinates: [i64; 3]) -> Vec<Placement> {
+        let mut random = Random::new(random::hash(self.seed, coordinates));
+        let origin = coordinates.map(|c| c * CHUNK_SIZE);
+        let mut placements = vec![];
+
+        // on the surface, only in the chunk containing it
+        let surface = |random: &mut Random| {
+            let x = origin[0] + random.below(CHUNK_SIZE as u64) as i64;
+            let z = origin[2] + random.below(CHUNK_SIZE as u64) as i64;
+            let y = self.surface(x, z) + 1;
+            (chunk_of([x, y, z]) == coordinates).then_some([x, y, z])
+        };
+        for _ in 0..MAX_TREES {
+            let base = surface(&mut random);
+            let chance = random.below(MAX_TREES);
+            if let Some(base) = base && chance < self.biome(base[0], base[2]).trees() {
+                tree(&mut placements, &mut random, base);
+            }
+        }
+        if random.one_in(BOULDER_CHANCE) && let Some(center) = surface(&mut random) {
+            boulder(&mut placements, &mut random, center);
+        }
+        if random.one_in(STRUCTURE_CHANCE) && let Some(corner) = surface(&mut random) {
+            hut(&mut placements, corner);
+        }
+        for _ in 0..VEINS_PER_CHUNK {
+            let start = origin.map(|c| c + random.below(CHUNK_SIZE as u64) as i64);
+            vein(&mut placements, &mut random, start);
+        }
+        placements
+    }
+}
+
+/// Relief of a column before the 3d noises
+#[derive(Clone, Copy, Debug)]
+struct Column {
+    height: f32,
+    /// How far the ground can go above or below `height`
+    overhang: f32,
+    biome: Biome,
+}
+
+fn chunk_of(coordinates: [i64; 3]) -> [i64; 3] {
+    coordinates.map(|c| c.div_euclid(CHUNK_SIZE))
+}
+
+fn place(placements: &mut Vec<Placement>, coordinates: [i64; 3], state: BlockState, replace: Replace) {
+    placements.push(Placement { coordinates, state, replace });
+}
+
+/// A trunk with a round crown, the trunk first so the leaves never cut it
+fn tree(placements: &mut Vec<Placement>, random: &mut Random, base: [i64; 3]) {
+    let [x, y, z] = base;
+    let height = random.between(4, 6);
+    let log = BlockState::new(BlockType::Log).with(Property::Axis, Axis::Y as u8);
+    for dy in 0..height {
+        place(placements, [x, y + dy, z], log, Replace::Air);
+    }
+    let top = y + height - 1;
+    for dy in -2..=1 {
+        let radius: i64 = if dy < 0 { 2 } else { 1 };
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                // rounded corners
+                if dx.abs() == radius && dz.abs() == radius && (dy == 1 || random.one_in(2)) {
+                    continue;
+                }
+                place(placements, [x + dx, top + dy, z + dz], BlockState::new(BlockType::Leaves), Replace::Air);
+            }
+        }
+    }
+}
+
+/// A ball of stone half sunk in the ground
+fn boulder(placements: &mut Vec<Placement>, random: &mut Random, center: [i64; 3]) {
+    let radius = random.between(1, 2);
+    for dx in -radius..=radius {
+        for dy in -radius..=radius {
+            for dz in -radius..=radius {
+                if dx * dx + dy * dy + dz * dz > radius * radius + 1 {
+                    continue;
+                }
+                let block_type = if random.one_in(3) { BlockType::Stone } else { BlockType::Cobblestone };
+                let coordinates = [center[0] + dx, center[1] + dy, center[2] + dz];
+                place(placements, coordinates, BlockState::new(block_type), Replace::Air);
+            }
+        }
+    }
+}
+
+/// A random walk of ore through the stone
+fn vein(placements: &mut Vec<Placement>, random: &mut Random, start: [i64; 3]) {
+    let block_type = if random.one_in(3) { BlockType::IronOre } else { BlockType::CoalOre };
+    let mut coordinates = start;
+    for _ in 0..random.between(3, 8) {
+        place(placements, coordinates, BlockState::new(block_type), Replace::Stone);
+        let axis = random.below(3) as usize;
+        coordinates[axis] += if random.one_in(2) { 1 } else { -1 };
+    }
+}
+
+/// `HUT` with its floor just below `corner`
+fn hut(placements: &mut Vec<Placement>, corner: [i64; 3]) {
+    for (layer, rows) in HUT.iter().enumerate() {
+        for (dz, row) in rows.iter().enumerate() {
+            for (dx, cell) in row.chars().enumerate() {
+                let block_type = match cell {
+                    '#' => BlockType::Cobblestone,
+                    '=' => BlockType::Planks,
+                    '.' => BlockType::Void,
+                    _ => continue,
+                };
+                let coordinates = [corner[0] + dx as i64, corner[1] - 1 + layer as i64, corner[2] + dz as i64];
+                place(placements, coordinates, BlockState::new(block_type), Replace::Solid);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every block of the column
+    fn blocks(map: &Map, coordinates: [i64; 2]) -> Vec<BlockState> {
+        map.column(coordinates).unwrap().sections().iter().flat_map(|chunk| chunk.blocks().map(|block| block.state())).collect()
+    }
+
+    #[test]
+    fn features_are_the_same_whatever_the_order() {
+        let (a, b) = ([0, 0], [1, 0]);
+        // a seed with a feature of the first column reaching into the second
+        let seed = (0..64)
+            .find(|seed| {
+                let mut generator = Generator::new(*seed);
+                generator.generate(&mut Map::new(), a);
+                generator.pending.keys().any(|chunk| [chunk[0], chunk[2]] == b)
+            })
+            .unwrap();
+
+        let mut forward = (Generator::new(seed), Map::new());
+        forward.0.generate(&mut forward.1, a);
+        forward.0.generate(&mut forward.1, b);
+        let mut backward = (Generator::new(seed), Map::new());
+        backward.0.generate(&mut backward.1, b);
+        backward.0.generate(&mut backward.1, a);
+        for column in [a, b] {
+            assert!(blocks(&forward.1, column) == blocks(&backward.1, column), "column {:?} differs", column);
+        }
+        assert!(blocks(&forward.1, b) != blocks(&Map::from_columns(vec![forward.0.terrain_column(b)]), b));
+    }
+
+    #[test]
+    fn keeps_the_bedrock_under_the_caves() {
+        let mut map = Map::new();
+        let mut generator = Generator::new(1);
+        generator.generate_area(&mut map, 1);
+        for x in -CHUNK_SIZE..2 * CHUNK_SIZE {
+            for z in -CHUNK_SIZE..2 * CHUNK_SIZE {
+                assert_eq!(map.get_block([x, MIN_Y, z]).unwrap().block_type, BlockType::Bedrock, "column {} {}", x, z);
+            }
+        }
+    }
+
+    #[test]
+    fn carves_the_caves_inside_their_column() {
+        // a seed with a cave at the bottom of the first column, where the bedrock is
+        let (seed, cave) = (0..64)
+            .find_map(|seed| {
+                let generator = Generator::new(seed);
+                let cave = (0..CHUNK_SIZE).flat_map(|x| (0..CHUNK_SIZE).map(move |z| [x, MIN_Y, z]))
+                    .find(|coordinates| generator.is_cave(*coordinates))?;
+                Some((seed, cave))
+            })
+            .unwrap();
+        let mut map = Map::new();
+        Generator::new(seed).generate(&mut map, [0, 0]);
+        assert_eq!(map.get_block(cave).unwrap().block_type, BlockType::Bedrock);
+        // nothing of the neighbours was made, whatever the caves along the sides
+        assert_eq!(map.columns().count(), 1);
+        assert!(map.get_block([-1, MIN_Y + 1, 0]).is_none() && map.get_block([CHUNK_SIZE, MIN_Y + 1, 0]).is_none());
+    }
+
+    #[test]
+    fn forgets_the_claims_once_the_neighbours_are_generated() {
+        let mut generator = Generator::new(3);
+        let mut map = Map::new();
+        generator.generate_area(&mut map, 1);
+        assert!(!generator.claims.contains_key(&[0, 0]));
+        assert!(generator.claims.keys().all(|column| column[0].abs() <= 2 && column[1].abs() <= 2));
+        generator.generate_area(&mut map, 2);
+        assert!(generator.claims.keys().all(|column| column[0].abs() >= 2 || column[1].abs() >= 2));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::models::instance::Instance;
+use crate::render::matrix::{self, Matrix};
+use crate::render::transform::Transform;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Something positioned in the world outside of the voxel grid: a prop, a part of an entity,
+/// or just a pivot for its children
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// Relative to the parent node
+    pub transform: Transform,
+    /// Index in `World::models`
+    pub model: Option<usize>,
+    pub colour: [f32; 4],
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    pub fn new(transform: Transform) -> Node {
+        Node {
+            transform,
+            model: None,
+            colour: [1.0; 4],
+            parent: None,
+            children: vec![],
+        }
+    }
+
+    pub fn with_model(self, model: usize, colour: [f32; 4]) -> Node {
+        Node { model: Some(model), colour, ..self }
+    }
+}
+
+/// Hierarchy of nodes, the transform of a node is relative to its parent
+pub struct SceneGraph {
+    /// Removed nodes leave a hole so the ids stay valid
+    nodes: Vec<Option<Node>>,
+}
+
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        SceneGraph { nodes: vec![] }
+    }
+
+    pub fn add(&mut self, mut node: Node, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = parent;
+        node.children.clear();
+        self.nodes.push(Some(node));
+        if let Some(parent) = parent {
+            self.get_mut(parent).expect("parent node does not exist").children.push(id);
+        }
+        id
+    }
+
+    /// Removes the node and all its descendants
+    pub fn remove(&mut self, id: NodeId) {
+        let Some(node) = self.nodes.get_mut(id.0).and_then(Option::take) else {
+            return;
+        };
+        if let Some(parent) = node.parent.and_then(|parent| self.get_mut(parent)) {
+            parent.children.retain(|&child| child != id);
+        }
+        for child in node.children {
+            self.remove(child);
+        }
+    }
+
+    /// Moves the node and its descendants under another parent, the transform of the node
+    /// stays relative to its parent. A node cannot go under one of its descendants
+    #[allow(unused)]
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut ancestor = parent;
+        while let Some(current) = ancestor {
+            assert!(current != id, "a node cannot be its own ancestor");
+            ancestor = self.get(current).expect("parent node does not exist").parent;
+        }
+        let Some(node) = self.get_mut(id) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut node.parent, parent);
+        if let Some(previous) = previous.and_then(|previous| self.get_mut(previous)) {
+            previous.children.retain(|&child| child != id);
+        }
+        if let Some(parent) = parent {
+            self.get_mut(parent).unwrap().children.push(id);
+        }
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
+
+    /// Every node with a model, with its transform in the world
+    pub fn instances(&self) -> Vec<(usize, Instance)> {
+        let mut instances = vec![];
+        let roots = self.nodes.iter().enumerate()
+            .filter(|(_, node)| node.as_ref().is_some_and(|node| node.parent.is_none()))
+            .map(|(index, _)| NodeId(index));
+        for root in roots {
+            self.collect_instances(root, &matrix::IDENTITY, &mut instances);
+        }
+        instances
+    }
+
+    fn collect_instances(&self, id: NodeId, parent: &Matrix, instances: &mut Vec<(usize, Instance)>) {
+        let Some(node) = self.get(id) else {
+            return;
+        };
+        let transform = matrix::multiply(parent, &node.transform.matrix());
+        if let Some(model) = node.model {
+            instances.push((model, Instance { transform, colour: node.colour }));
+        }
+        for &child in &node.children {
+            self.collect_instances(child, &transform, instances);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prop(translation: [f32; 3], model: usize) -> Node {
+        Node::new(Transform::from_translation(translation).with_scale(2.0)).with_model(model, [1.0; 4])
+    }
+
+    /// Where the model puts the origin of its space
+    fn origin(graph: &SceneGraph, model: usize) -> [f32; 3] {
+        let (_, instance) = graph.instances().into_iter().find(|(other, _)| *other == model).unwrap();
+        let [x, y, z, _] = matrix::transform(&instance.transform, [0.0, 0.0, 0.0, 1.0]);
+        [x, y, z]
+    }
+
+    #[test]
+    fn composes_the_transforms_of_the_parents() {
+        let mut graph = SceneGraph::new();
+        let parent = graph.add(prop([1.0, 0.0, 0.0], 0), None);
+        let child = graph.add(prop([0.0, 3.0, 0.0], 1), Some(parent));
+        // scaled by its parent
+        assert_eq!(origin(&graph, 1), [1.0, 6.0, 0.0]);
+        let [x, y, z, _] = matrix::transform(&graph.instances()[1].1.transform, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!([x, y, z], [5.0, 6.0, 0.0]);
+
+        graph.get_mut(parent).unwrap().transform.translation = [0.0, 0.0, -1.0];
+        assert_eq!(origin(&graph, 1), [0.0, 6.0, -1.0]);
+
+        graph.remove(parent);
+        assert!(graph.get(child).is_none());
+        assert_eq!(graph.len(), 0);
+    }
+
+    #[test]
+    fn moves_the_children_with_their_new_parent() {
+        let mut graph = SceneGraph::new();
+        let first = graph.add(prop([1.0, 0.0, 0.0], 0), None);
+        let second = graph.add(prop([0.0, 0.0, 5.0], 1), None);
+        let child = graph.add(prop([0.0, 1.0, 0.0], 2), Some(first));
+        assert_eq!(origin(&graph, 2), [1.0, 2.0, 0.0]);
+
+        graph.set_parent(child, Some(second));
+        assert_eq!(origin(&graph, 2), [0.0, 2.0, 5.0]);
+        graph.remove(first);
+        assert_eq!(graph.len(), 2);
+
+        graph.set_parent(child, None);
+        assert_eq!(origin(&graph, 2), [0.0, 1.0, 0.0]);
+        graph.remove(second);
+        assert!(graph.get(child).is_some());
+    }
+}
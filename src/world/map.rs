@@ -1,83 +1,509 @@
-use crate::models::object::Object;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::models::cube::SIZE;
+use crate::world::biome::Biome;
 use crate::world::block::Block;
-use crate::world::block::BlockType;
+use crate::world::block::{BlockType, Layer};
+use crate::world::palette::{Palette, VOLUME};
+use crate::world::state::BlockState;
+
+/// Number of blocks along each side of a chunk
+pub const CHUNK_SIZE: i64 = 16;
 
 pub struct Chunk {
     coordonates: [i64; 3],
-    blocks: [[[Block; 16]; 16]; 16],
+    blocks: Palette,
 }
 
 impl Chunk {
-    fn new(coords: [i64; 3]) -> Chunk {
-        let blocks = core::array::from_fn(|x| {
-            core::array::from_fn(|y| {
-                core::array::from_fn(|z| {
-                    let world_x = coords[0] * 16 + x as i64;
-                    let world_y = coords[1] * 16 + y as i64;
-                    let world_z = coords[2] * 16 + z as i64;
-                    Block::new(BlockType::Void, [world_x, world_y, world_z])
-                })
-            })
-        });
-
+    pub fn new(coords: [i64; 3]) -> Chunk {
         Chunk {
             coordonates: coords,
-            blocks,
+            blocks: Palette::filled(BlockState::new(BlockType::Void)),
         }
     }
 
-    fn floor(coords: [i64; 3]) -> Chunk {
-        let blocks = core::array::from_fn(|x| {
-            core::array::from_fn(|y| {
-                core::array::from_fn(|z| {
-                    let world_x = coords[0] * 16 + x as i64;
-                    let world_y = coords[1] * 16 + y as i64;
-                    let world_z = coords[2] * 16 + z as i64;
-                    let block_type = if y == 0 {
-                        BlockType::Bedrock
-                    } else {
-                        BlockType::Void
-                    };
-                    Block::new(block_type, [world_x, world_y, world_z])
-                })
-            })
-        });
-
+    /// From blocks already packed, as read from a save
+    pub fn from_palette(coords: [i64; 3], blocks: Palette) -> Chunk {
         Chunk {
             coordonates: coords,
             blocks,
         }
     }
 
-    pub fn get_show(&self) -> Vec<Object> {
+    /// Coordinates of the chunk, in chunks
+    pub fn coordinates(&self) -> [i64; 3] {
+        self.coordonates
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.blocks
+    }
+
+    /// Whether every block is empty, such chunks are not meshed
+    pub fn is_empty(&self) -> bool {
+        self.blocks.used().all(|state| state.block_type == BlockType::Void)
+    }
+
+    /// Box containing every block of the chunk, in the same space as the block meshes
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let min = self.coordonates.map(|c| (c * CHUNK_SIZE) as f32 * SIZE * 2. - SIZE);
+        let max = self.coordonates.map(|c| (c * CHUNK_SIZE + CHUNK_SIZE - 1) as f32 * SIZE * 2. + SIZE);
+        (min, max)
+    }
+
+    /// World coordinates of the block in the lowest corner
+    pub fn origin(&self) -> [i64; 3] {
+        self.coordonates.map(|c| c * CHUNK_SIZE)
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = Block> + '_ {
+        (0..VOLUME).map(|i| self.get(local(i)))
+    }
+
+    /// `local` is relative to the corner of the chunk, each coordinate in 0..16
+    pub fn get(&self, local: [usize; 3]) -> Block {
+        let origin = self.origin();
+        let coordinates = std::array::from_fn(|i| origin[i] + local[i] as i64);
+        Block::new(self.blocks.get(index(local)), coordinates)
+    }
+
+    pub fn set(&mut self, local: [usize; 3], state: BlockState) {
+        self.blocks.set(index(local), state);
+    }
+}
+
+/// Position of the block in the palette, x then y then z
+fn index(local: [usize; 3]) -> usize {
+    (local[0] * 16 + local[1]) * 16 + local[2]
+}
+
+fn local(index: usize) -> [usize; 3] {
+    [index / 256, index / 16 % 16, index % 16]
+}
+
+/// Lowest y of the world, the floor of bedrock
+pub const MIN_Y: i64 = 0;
+/// Blocks between the bottom and the top of the world
+pub const HEIGHT: i64 = 64;
+/// Chunks stacked in a column
+pub const SECTIONS: i64 = HEIGHT / CHUNK_SIZE;
+
+/// Every chunk from the bottom to the top of the world at the same x and z
+pub struct ChunkColumn {
+    /// x and z in chunks
+    coordinates: [i64; 2],
+    /// From the bottom
+    sections: Vec<Chunk>,
+    /// Highest block that is not empty in each column of blocks, x then z, `MIN_Y - 1` if none
+    heightmap: [[i64; 16]; 16],
+    /// Biome of each column of blocks, x then z, the same from the bottom to the top
+    biomes: [[Biome; 16]; 16],
+}
+
+impl ChunkColumn {
+    /// Nothing but empty blocks
+    #[cfg(test)]
+    pub fn new(coordinates: [i64; 2]) -> ChunkColumn {
+        let sections = (0..SECTIONS)
+            .map(|y| Chunk::new([coordinates[0], MIN_Y.div_euclid(CHUNK_SIZE) + y, coordinates[1]]))
+            .collect();
+        ChunkColumn::from_sections(coordinates, sections).unwrap()
+    }
+
+    /// None unless there is one section for each height, in order
+    pub fn from_sections(coordinates: [i64; 2], sections: Vec<Chunk>) -> Option<ChunkColumn> {
+        let valid = sections.len() == SECTIONS as usize && sections.iter().enumerate().all(|(y, section)| {
+            section.coordinates() == [coordinates[0], MIN_Y.div_euclid(CHUNK_SIZE) + y as i64, coordinates[1]]
+        });
+        if !valid {
+            return None;
+        }
+        let mut column = ChunkColumn {
+            coordinates,
+            sections,
+            heightmap: [[MIN_Y - 1; 16]; 16],
+            biomes: [[Biome::Plains; 16]; 16],
+        };
+        column.refresh_heightmap();
+        Some(column)
+    }
+
+    pub fn coordinates(&self) -> [i64; 2] {
+        self.coordinates
+    }
 
-        let mut to_render: Vec<Object> = vec![];
+    pub fn sections(&self) -> &[Chunk] {
+        &self.sections
+    }
 
-        for (_x, plane) in self.blocks.iter().enumerate() {
-            for (_y, row) in plane.iter().enumerate() {
-                for (_z, block) in row.iter().enumerate() {
-                    // `block` est &Block
-                    if block.block_type == BlockType::Void {
-                        continue;
-                    }
+    /// `column` is x and z relative to the corner of the chunk
+    pub fn height(&self, column: [usize; 2]) -> i64 {
+        self.heightmap[column[0]][column[1]]
+    }
+
+    /// `column` is x and z relative to the corner of the chunk
+    pub fn biome(&self, column: [usize; 2]) -> Biome {
+        self.biomes[column[0]][column[1]]
+    }
 
-                    to_render.push(block.render.clone());
-                }
+    pub fn set_biome(&mut self, column: [usize; 2], biome: Biome) {
+        self.biomes[column[0]][column[1]] = biome;
+    }
+
+    fn refresh_heightmap(&mut self) {
+        for x in 0..16 {
+            for z in 0..16 {
+                self.update_height([x, z], MIN_Y + HEIGHT - 1);
             }
         }
+    }
+
+    /// Finds the highest block again, looking down from `top`
+    fn update_height(&mut self, column: [usize; 2], top: i64) {
+        let [x, z] = column;
+        let height = (MIN_Y..=top).rev().find(|&y| {
+            let section = &self.sections[(y - MIN_Y).div_euclid(CHUNK_SIZE) as usize];
+            section.get([x, y.rem_euclid(CHUNK_SIZE) as usize, z]).block_type != BlockType::Void
+        });
+        self.heightmap[x][z] = height.unwrap_or(MIN_Y - 1);
+    }
+}
+
+/// Why a block could not be changed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapError {
+    /// Below the bottom or above the top of the world
+    OutOfBounds([i64; 3]),
+    /// Inside the world, but its column is not loaded
+    NotLoaded([i64; 3]),
+}
 
-        to_render
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::OutOfBounds(coordinates) => {
+                write!(f, "{:?} is outside of the world, y must be in {}..{}", coordinates, MIN_Y, MIN_Y + HEIGHT)
+            }
+            MapError::NotLoaded(coordinates) => write!(f, "{:?} is outside of the loaded chunks", coordinates),
+        }
     }
 }
 
+impl std::error::Error for MapError {}
+
 pub struct Map {
-    pub chunks: Vec<Chunk>,
+    /// By x and z in chunks
+    columns: HashMap<[i64; 2], ChunkColumn>,
+    /// Blocks set since the last `take_changes`, in the order they were set
+    changes: Vec<[i64; 3]>,
+    /// Chunks whose meshes are out of date since the last `take_dirty`, because one of their
+    /// blocks or a block against them changed
+    dirty: HashSet<[i64; 3]>,
 }
 
 impl Map {
+    /// Without any column, they are generated or loaded
     pub fn new() -> Map {
+        Map::from_columns(vec![])
+    }
+
+    /// Two columns at the same place: the last one is kept
+    pub fn from_columns(columns: Vec<ChunkColumn>) -> Map {
+        let columns: HashMap<[i64; 2], ChunkColumn> = columns.into_iter().map(|column| (column.coordinates, column)).collect();
+        let dirty = columns.values().flat_map(|column| column.sections()).map(|chunk| chunk.coordinates()).collect();
         Map {
-            chunks: vec![Chunk::floor([0, 0, 0]), Chunk::floor([1, 0, 1])],
+            columns,
+            changes: vec![],
+            dirty,
         }
     }
-}
\ No newline at end of file
+
+    /// Every section of every column, in the order of `columns`
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.columns().flat_map(|column| column.sections())
+    }
+
+    /// Sorted by coordinates, so going through them is always in the same order
+    pub fn columns(&self) -> impl Iterator<Item = &ChunkColumn> {
+        let mut columns: Vec<&ChunkColumn> = self.columns.values().collect();
+        columns.sort_by_key(|column| column.coordinates);
+        columns.into_iter()
+    }
+
+    /// Lowest and highest corners of the box around the loaded columns, in blocks. None if there
+    /// is no column
+    pub fn bounds(&self) -> Option<([i64; 3], [i64; 3])> {
+        let coordinates = || self.columns.keys();
+        let min = [coordinates().map(|c| c[0]).min()?, coordinates().map(|c| c[1]).min()?];
+        let max = [coordinates().map(|c| c[0]).max()?, coordinates().map(|c| c[1]).max()?];
+        Some((
+            [min[0] * CHUNK_SIZE, MIN_Y, min[1] * CHUNK_SIZE],
+            [max[0] * CHUNK_SIZE + CHUNK_SIZE - 1, MIN_Y + HEIGHT - 1, max[1] * CHUNK_SIZE + CHUNK_SIZE - 1],
+        ))
+    }
+
+    /// `coordinates` are x and z in chunks
+    pub fn column(&self, coordinates: [i64; 2]) -> Option<&ChunkColumn> {
+        self.columns.get(&coordinates)
+    }
+
+    fn column_mut(&mut self, coordinates: [i64; 2]) -> Option<&mut ChunkColumn> {
+        self.columns.get_mut(&coordinates)
+    }
+
+    /// Does nothing if a column is already loaded there
+    pub fn insert_column(&mut self, column: ChunkColumn) {
+        let [x, z] = column.coordinates;
+        if self.columns.contains_key(&[x, z]) {
+            return;
+        }
+        self.columns.insert([x, z], column);
+        // the faces of the neighbours against the new column were drawn or hidden
+        for [dx, dz] in [[0, 0], [-1, 0], [1, 0], [0, -1], [0, 1]] {
+            for y in 0..SECTIONS {
+                self.dirty.insert([x + dx, MIN_Y.div_euclid(CHUNK_SIZE) + y, z + dz]);
+            }
+        }
+    }
+
+    /// `coordinates` in chunks
+    pub fn chunk(&self, coordinates: [i64; 3]) -> Option<&Chunk> {
+        let section = coordinates[1] - MIN_Y.div_euclid(CHUNK_SIZE);
+        self.column([coordinates[0], coordinates[2]])?.sections.get(usize::try_from(section).ok()?)
+    }
+
+    /// Changes made through the chunk do not update the heightmap nor notify the neighbours,
+    /// call `refresh_heightmap` after. The chunk and the ones around it are meshed again
+    pub fn chunk_mut(&mut self, coordinates: [i64; 3]) -> Option<&mut Chunk> {
+        self.section_mut(coordinates)?;
+        self.dirty.insert(coordinates);
+        for axis in 0..3 {
+            for side in [-1, 1] {
+                let mut neighbour = coordinates;
+                neighbour[axis] += side;
+                self.dirty.insert(neighbour);
+            }
+        }
+        self.section_mut(coordinates)
+    }
+
+    fn section_mut(&mut self, coordinates: [i64; 3]) -> Option<&mut Chunk> {
+        let section = coordinates[1] - MIN_Y.div_euclid(CHUNK_SIZE);
+        self.column_mut([coordinates[0], coordinates[2]])?.sections.get_mut(usize::try_from(section).ok()?)
+    }
+
+    /// Block at the given world coordinates, None if it is outside of the loaded chunks
+    pub fn get_block(&self, coordinates: [i64; 3]) -> Option<Block> {
+        let chunk = self.chunk(coordinates.map(|c| c.div_euclid(CHUNK_SIZE)))?;
+        Some(chunk.get(coordinates.map(|c| c.rem_euclid(CHUNK_SIZE) as usize)))
+    }
+
+    /// Same as `get_block`, with the reason when there is no block
+    pub fn try_get_block(&self, coordinates: [i64; 3]) -> Result<Block, MapError> {
+        if !is_in_bounds(coordinates[1]) {
+            return Err(MapError::OutOfBounds(coordinates));
+        }
+        self.get_block(coordinates).ok_or(MapError::NotLoaded(coordinates))
+    }
+
+    /// Changes the block at the given world coordinates
+    pub fn set_block(&mut self, coordinates: [i64; 3], block_type: BlockType) -> Result<(), MapError> {
+        self.set_state(coordinates, BlockState::new(block_type))
+    }
+
+    pub fn set_state(&mut self, coordinates: [i64; 3], state: BlockState) -> Result<(), MapError> {
+        if !is_in_bounds(coordinates[1]) {
+            return Err(MapError::OutOfBounds(coordinates));
+        }
+        let [x, y, z] = coordinates;
+        let column = self.column_mut([x.div_euclid(CHUNK_SIZE), z.div_euclid(CHUNK_SIZE)])
+            .ok_or(MapError::NotLoaded(coordinates))?;
+        let local = coordinates.map(|c| c.rem_euclid(CHUNK_SIZE) as usize);
+        column.sections[(y - MIN_Y).div_euclid(CHUNK_SIZE) as usize].set(local, state);
+
+        let height = column.heightmap[local[0]][local[2]];
+        if state.block_type != BlockType::Void && y > height {
+            column.heightmap[local[0]][local[2]] = y;
+        } else if state.block_type == BlockType::Void && y == height {
+            column.update_height([local[0], local[2]], y);
+        }
+        self.changes.push(coordinates);
+        self.mark_dirty(coordinates);
+        Ok(())
+    }
+
+    /// Changes many blocks at once: nothing is changed if one of them cannot be, and the heightmap
+    /// of each column is computed once at the end. Returns the chunks changed
+    pub fn set_states(&mut self, blocks: &[([i64; 3], BlockState)]) -> Result<Vec<[i64; 3]>, MapError> {
+        for (coordinates, _) in blocks {
+            self.try_get_block(*coordinates)?;
+        }
+        let mut chunks = vec![];
+        for (coordinates, state) in blocks {
+            let chunk = coordinates.map(|c| c.div_euclid(CHUNK_SIZE));
+            if !chunks.contains(&chunk) {
+                chunks.push(chunk);
+            }
+            let local = coordinates.map(|c| c.rem_euclid(CHUNK_SIZE) as usize);
+            self.section_mut(chunk).unwrap().set(local, *state);
+            self.changes.push(*coordinates);
+            self.mark_dirty(*coordinates);
+        }
+        let mut columns: Vec<[i64; 2]> = chunks.iter().map(|chunk| [chunk[0], chunk[2]]).collect();
+        columns.sort();
+        columns.dedup();
+        for column in columns {
+            self.refresh_heightmap(column);
+        }
+        Ok(chunks)
+    }
+
+    /// Computes the heightmap of the column again, after its chunks were changed directly
+    pub fn refresh_heightmap(&mut self, coordinates: [i64; 2]) {
+        if let Some(column) = self.column_mut(coordinates) {
+            column.refresh_heightmap();
+        }
+    }
+
+    /// Highest block that is not empty, `MIN_Y - 1` if there is none. None if the column
+    /// is not loaded
+    pub fn height(&self, x: i64, z: i64) -> Option<i64> {
+        let column = self.column([x.div_euclid(CHUNK_SIZE), z.div_euclid(CHUNK_SIZE)])?;
+        Some(column.height([x.rem_euclid(CHUNK_SIZE) as usize, z.rem_euclid(CHUNK_SIZE) as usize]))
+    }
+
+    /// Coordinates of the blocks set since the last call, so the world can react to them
+    pub fn take_changes(&mut self) -> Vec<[i64; 3]> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Chunks to mesh again, see `dirty`
+    pub fn take_dirty(&mut self) -> HashSet<[i64; 3]> {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// The chunk of the block, and its neighbours when the block is on their side
+    fn mark_dirty(&mut self, coordinates: [i64; 3]) {
+        let chunk = coordinates.map(|c| c.div_euclid(CHUNK_SIZE));
+        self.dirty.insert(chunk);
+        for axis in 0..3 {
+            let side = match coordinates[axis].rem_euclid(CHUNK_SIZE) {
+                0 => -1,
+                local if local == CHUNK_SIZE - 1 => 1,
+                _ => continue,
+            };
+            let mut neighbour = chunk;
+            neighbour[axis] += side;
+            self.dirty.insert(neighbour);
+        }
+    }
+
+    /// Biome of the column, None if it is not loaded
+    pub fn biome(&self, x: i64, z: i64) -> Option<Biome> {
+        let column = self.column([x.div_euclid(CHUNK_SIZE), z.div_euclid(CHUNK_SIZE)])?;
+        Some(column.biome([x.rem_euclid(CHUNK_SIZE) as usize, z.rem_euclid(CHUNK_SIZE) as usize]))
+    }
+
+    /// Whether the block hides the faces of its neighbours
+    pub fn is_opaque(&self, coordinates: [i64; 3]) -> bool {
+        self.get_block(coordinates).is_some_and(|block| block.block_type.layer() == Some(Layer::Opaque))
+    }
+}
+
+/// Whether the height is inside the world
+pub fn is_in_bounds(y: i64) -> bool {
+    (MIN_Y..MIN_Y + HEIGHT).contains(&y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> Map {
+        Map::from_columns(vec![ChunkColumn::new([0, 0]), ChunkColumn::new([-1, 0])])
+    }
+
+    #[test]
+    fn starts_without_columns() {
+        assert_eq!(Map::new().chunks().count(), 0);
+        assert!(Map::new().get_block([0, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn finds_the_columns() {
+        let map = map();
+        assert!(map.column([0, 0]).is_some());
+        assert!(map.column([-1, 0]).is_some());
+        assert!(map.column([1, 0]).is_none());
+        assert_eq!(map.get_block([-1, 5, 15]).unwrap().coordinates, [-1, 5, 15]);
+        // sorted by coordinates, then from the bottom
+        let chunks: Vec<[i64; 3]> = map.chunks().map(|chunk| chunk.coordinates()).collect();
+        assert_eq!(chunks[0], [-1, 0, 0]);
+        assert_eq!(chunks[SECTIONS as usize], [0, 0, 0]);
+        assert_eq!(chunks.len(), 2 * SECTIONS as usize);
+    }
+
+    #[test]
+    fn keeps_the_first_inserted_column() {
+        let mut map = map();
+        let mut sections: Vec<Chunk> = (0..SECTIONS).map(|y| Chunk::new([0, y, 0])).collect();
+        sections[0].set([0, 0, 0], BlockState::new(BlockType::Stone));
+        map.insert_column(ChunkColumn::from_sections([0, 0], sections).unwrap());
+        assert_eq!(map.get_block([0, 0, 0]).unwrap().block_type, BlockType::Void);
+    }
+
+    #[test]
+    fn reports_why_a_block_cannot_be_changed() {
+        let mut map = map();
+        assert_eq!(map.set_block([0, MIN_Y - 1, 0], BlockType::Stone), Err(MapError::OutOfBounds([0, MIN_Y - 1, 0])));
+        assert_eq!(map.set_block([0, MIN_Y + HEIGHT, 0], BlockType::Stone), Err(MapError::OutOfBounds([0, MIN_Y + HEIGHT, 0])));
+        assert_eq!(map.set_block([16, 3, 0], BlockType::Stone), Err(MapError::NotLoaded([16, 3, 0])));
+        assert_eq!(map.try_get_block([16, 3, 0]).unwrap_err(), MapError::NotLoaded([16, 3, 0]));
+        assert_eq!(map.try_get_block([0, -1, 0]).unwrap_err(), MapError::OutOfBounds([0, -1, 0]));
+        assert!(map.take_changes().is_empty());
+
+        // nothing is written when one of the blocks is invalid
+        let blocks = [([0, 3, 0], BlockState::new(BlockType::Stone)), ([99, 3, 0], BlockState::new(BlockType::Stone))];
+        assert_eq!(map.set_states(&blocks), Err(MapError::NotLoaded([99, 3, 0])));
+        assert_eq!(map.get_block([0, 3, 0]).unwrap().block_type, BlockType::Void);
+    }
+
+    #[test]
+    fn updates_the_heightmap() {
+        let mut map = map();
+        assert_eq!(map.height(3, 4), Some(MIN_Y - 1));
+        assert_eq!(map.height(40, 4), None);
+
+        map.set_block([3, 10, 4], BlockType::Stone).unwrap();
+        map.set_block([3, 40, 4], BlockType::Planks).unwrap();
+        assert_eq!(map.height(3, 4), Some(40));
+        // a block below the top does not change it
+        map.set_block([3, 20, 4], BlockType::Stone).unwrap();
+        assert_eq!(map.height(3, 4), Some(40));
+        // removing the top looks down for the next one, across the sections
+        map.set_block([3, 40, 4], BlockType::Void).unwrap();
+        assert_eq!(map.height(3, 4), Some(20));
+        map.set_block([3, 10, 4], BlockType::Void).unwrap();
+        assert_eq!(map.height(3, 4), Some(20));
+        map.set_block([3, 20, 4], BlockType::Void).unwrap();
+        assert_eq!(map.height(3, 4), Some(MIN_Y - 1));
+
+        // in another column, with negative coordinates
+        map.set_states(&[([-1, 7, 2], BlockState::new(BlockType::Stone)), ([-1, 63, 2], BlockState::new(BlockType::Stone))]).unwrap();
+        assert_eq!(map.height(-1, 2), Some(63));
+        assert_eq!(map.height(3, 4), Some(MIN_Y - 1));
+        assert_eq!(map.take_changes().len(), 8);
+    }
+
+    #[test]
+    fn is_empty_again_once_the_blocks_are_removed() {
+        let mut map = map();
+        assert!(map.chunk([0, 1, 0]).unwrap().is_empty());
+        map.set_block([3, 20, 3], BlockType::Stone).unwrap();
+        assert!(!map.chunk([0, 1, 0]).unwrap().is_empty());
+        map.set_block([3, 20, 3], BlockType::Void).unwrap();
+        assert!(map.chunk([0, 1, 0]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use crate::models::cube;
+use crate::models::mesh::MeshBuilder;
+use crate::models::object::Object;
+use crate::world::block::{Block, BlockType, Layer};
+use crate::world::fluid::Fluid;
+use crate::world::map::{Chunk, Map};
+use crate::world::state::Facing;
+
+/// At most 24 vertices for each of the 4096 blocks of a chunk, far from the limit of `MeshBuilder`
+const CHUNK_VERTICES: &str = "a chunk has far fewer than u32::MAX vertices";
+
+/// Face of a translucent block, kept apart so the faces can be sorted before each frame
+#[derive(Clone, Copy, Debug)]
+pub struct TranslucentFace {
+    /// Same space as the block meshes
+    pub center: [f32; 3],
+    pub block_center: [f32; 3],
+    pub face: usize,
+    pub colour: [f32; 4],
+}
+
+/// Meshes of a chunk, one per render pass
+pub struct ChunkMesh {
+    pub opaque: Object,
+    pub cutout: Object,
+    pub translucent: Vec<TranslucentFace>,
+}
+
+impl ChunkMesh {
+    /// Translucent faces from the farthest to the closest, `eye` in the space of the blocks
+    pub fn translucent_sorted(&self, eye: [f32; 3]) -> Object {
+        let distance = |face: &TranslucentFace| {
+            let delta: [f32; 3] = std::array::from_fn(|i| face.center[i] - eye[i]);
+            delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]
+        };
+        let mut faces = self.translucent.clone();
+        faces.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+
+        let mut builder = MeshBuilder::new();
+        for face in faces {
+            builder.set_colour(face.colour);
+            cube::add_face(&mut builder, face.block_center, face.face).expect(CHUNK_VERTICES);
+        }
+        builder.build()
+    }
+}
+
+/// Meshes of the chunks kept from a frame to the next, a chunk is meshed again only once it
+/// or a block against it changed
+#[derive(Default)]
+pub struct MeshCache {
+    meshes: HashMap<[i64; 3], ChunkMesh>,
+}
+
+impl MeshCache {
+    pub fn new() -> MeshCache {
+        MeshCache::default()
+    }
+
+    /// Forgets the meshes of the chunks changed since the last call and of the chunks unloaded
+    pub fn update(&mut self, map: &mut Map) {
+        for coordinates in map.take_dirty() {
+            self.meshes.remove(&coordinates);
+        }
+        self.meshes.retain(|coordinates, _| map.chunk(*coordinates).is_some());
+    }
+
+    /// Meshes the chunk if its mesh is not kept, returns whether it was meshed
+    pub fn prepare(&mut self, map: &Map, chunk: &Chunk) -> bool {
+        if self.meshes.contains_key(&chunk.coordinates()) {
+            return false;
+        }
+        self.meshes.insert(chunk.coordinates(), mesh_chunk(map, chunk));
+        true
+    }
+
+    /// Mesh kept for the chunk, see `prepare`
+    pub fn get(&self, coordinates: [i64; 3]) -> Option<&ChunkMesh> {
+        self.meshes.get(&coordinates)
+    }
+}
+
+/// Meshes the whole chunk, with only the faces that are not hidden by a neighbour
+pub fn mesh_chunk(map: &Map, chunk: &Chunk) -> ChunkMesh {
+    let mut opaque = MeshBuilder::new();
+    let mut cutout = MeshBuilder::new();
+    let mut translucent = vec![];
+
+    for block in chunk.blocks() {
+        let Some(layer) = block.block_type.layer() else {
+            continue;
+        };
+        let (offset, half) = shape(&block);
+        let center = block.center();
+        let center: [f32; 3] = std::array::from_fn(|i| center[i] + offset[i] * cube::SIZE);
+
+        for (face, (direction, corners)) in cube::FACES.iter().enumerate() {
+            let side = Facing::from_offset(*direction).unwrap();
+            let axis = side.axis().index();
+            // only the faces on the side of the cell can be hidden by the neighbour
+            let on_side = (offset[axis] + direction[axis] as f32 * half[axis]).abs() >= 1.0;
+            let neighbour = [
+                block.coordinates[0] + direction[0],
+                block.coordinates[1] + direction[1],
+                block.coordinates[2] + direction[2],
+            ];
+            if on_side && is_hidden(map, &block, neighbour) {
+                continue;
+            }
+            let colour = block.face_colour(side);
+            match layer {
+                Layer::Opaque => {
+                    opaque.set_colour(colour);
+                    cube::add_box_face(&mut opaque, center, half, face).expect(CHUNK_VERTICES);
+                }
+                Layer::Cutout => {
+                    cutout.set_colour(colour);
+                    cube::add_box_face(&mut cutout, center, half, face).expect(CHUNK_VERTICES);
+                }
+                Layer::Translucent => {
+                    // the center of a face is the mean of its corners
+                    let offset: [f32; 3] = std::array::from_fn(|i| corners.iter().map(|corner| corner[i]).sum::<f32>() / 4.0);
+                    translucent.push(TranslucentFace {
+                        center: std::array::from_fn(|i| center[i] + offset[i] * cube::SIZE),
+                        block_center: center,
+                        face,
+                        colour,
+                    });
+                }
+            }
+        }
+    }
+
+    ChunkMesh {
+        opaque: opaque.build(),
+        cutout: cutout.build(),
+        translucent,
+    }
+}
+
+/// Thickness of a trapdoor, relative to the cube
+const TRAPDOOR_HALF: f32 = 3.0 / 16.0;
+
+/// Offset of the center and size of the model along each axis, relative to the cube
+fn shape(block: &Block) -> ([f32; 3], [f32; 3]) {
+    let state = block.state();
+    match (block.block_type, state.facing()) {
+        // closed it lies on the floor of its cell, open it stands against the side behind it
+        (BlockType::Trapdoor, Some(facing)) => {
+            let side = if state.is_open() { facing.opposite() } else { Facing::Down };
+            let axis = side.axis().index();
+            let mut offset = [0.0; 3];
+            let mut half = [1.0; 3];
+            offset[axis] = side.offset()[axis] as f32 * (1.0 - TRAPDOOR_HALF);
+            half[axis] = TRAPDOOR_HALF;
+            (offset, half)
+        }
+        _ => ([0.0; 3], [1.0; 3]),
+    }
+}
+
+/// Faces are hidden by opaque neighbours, and between two identical transparent blocks.
+/// A fluid is the same whatever its level
+fn is_hidden(map: &Map, block: &Block, neighbour: [i64; 3]) -> bool {
+    if map.is_opaque(neighbour) {
+        return true;
+    }
+    let transparent = block.block_type.layer() != Some(Layer::Opaque);
+    let same = |other: Block| match Fluid::of(block.block_type) {
+        Some(_) => other.block_type == block.block_type,
+        None => other.state() == block.state(),
+    };
+    transparent && map.get_block(neighbour).is_some_and(same)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::ChunkColumn;
+    use crate::world::state::BlockState;
+
+    /// Meshes every chunk, returns how many were meshed
+    fn prepare_all(cache: &mut MeshCache, map: &mut Map) -> usize {
+        cache.update(map);
+        let chunks: Vec<[i64; 3]> = map.chunks().map(|chunk| chunk.coordinates()).collect();
+        chunks.into_iter().filter(|c| cache.prepare(map, map.chunk(*c).unwrap())).count()
+    }
+
+    #[test]
+    fn meshes_only_the_changed_chunks() {
+        let mut map = Map::from_columns(vec![ChunkColumn::new([0, 0]), ChunkColumn::new([1, 0])]);
+        let mut cache = MeshCache::new();
+        assert_eq!(prepare_all(&mut cache, &mut map), 8);
+        assert_eq!(prepare_all(&mut cache, &mut map), 0);
+
+        map.set_block([5, 5, 5], BlockType::Stone).unwrap();
+        assert_eq!(prepare_all(&mut cache, &mut map), 1);
+        assert_eq!(cache.get([0, 0, 0]).unwrap().opaque.indices.len(), 6 * 6);
+
+        // against the chunks next to it and below, their faces against the block may change
+        map.set_block([15, 16, 5], BlockType::Stone).unwrap();
+        let mut dirty: Vec<[i64; 3]> = map.take_dirty().into_iter().collect();
+        dirty.sort();
+        assert_eq!(dirty, [[0, 0, 0], [0, 1, 0], [1, 1, 0]]);
+    }
+
+    #[test]
+    fn meshes_the_neighbours_of_new_columns() {
+        let mut map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        let mut cache = MeshCache::new();
+        prepare_all(&mut cache, &mut map);
+        map.insert_column(ChunkColumn::new([0, 1]));
+        assert_eq!(prepare_all(&mut cache, &mut map), 8);
+    }
+
+    #[test]
+    fn hides_the_faces_between_fluids() {
+        let mut map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        let mut cache = MeshCache::new();
+        map.set_state([5, 5, 5], BlockState::parse("water").unwrap()).unwrap();
+        map.set_state([6, 5, 5], BlockState::parse("water[level=3]").unwrap()).unwrap();
+        map.set_state([5, 6, 5], BlockState::parse("water[falling=true]").unwrap()).unwrap();
+        // other transparent blocks only when they are in the same state
+        map.set_state([9, 5, 5], BlockState::parse("trapdoor[open=true]").unwrap()).unwrap();
+        map.set_state([10, 5, 5], BlockState::parse("trapdoor").unwrap()).unwrap();
+        prepare_all(&mut cache, &mut map);
+        let mesh = cache.get([0, 0, 0]).unwrap();
+        assert_eq!(mesh.translucent.len(), 3 * 6 - 2 * 2);
+        assert_eq!(mesh.cutout.indices.len(), 2 * 6 * 6);
+    }
+}
@@ -1,3 +1,25 @@
+#[allow(clippy::module_inception)]
 pub mod world;
 pub mod map;
+pub mod biome;
 pub mod block;
+pub mod mesher;
+pub mod nbt;
+pub mod graph;
+pub mod time;
+pub mod command;
+pub mod console;
+pub mod edit;
+pub mod fluid;
+pub mod falling;
+pub mod generation;
+pub mod noise;
+pub mod palette;
+pub mod plants;
+pub mod random;
+pub mod raycast;
+pub mod save;
+pub mod schematic;
+pub mod state;
+pub mod updates;
+pub mod vox;
@@ -0,0 +1,286 @@
+//! Named binary tags, the format of the Minecraft files such as the Sponge schematics. Big endian,
+//! the files themselves are usually compressed with gzip
+
+/// Lists and compounds nested deeper than this are refused, so a broken file cannot overflow the stack
+const MAX_DEPTH: usize = 512;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    /// The elements all have the same type
+    List(Vec<Tag>),
+    /// In the order of the file
+    Compound(Vec<(String, Tag)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => 1,
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::Long(_) => 4,
+            Tag::Float(_) => 5,
+            Tag::Double(_) => 6,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+            Tag::LongArray(_) => 12,
+        }
+    }
+
+    /// Element of a compound, None for the other tags
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.iter().find(|(key, _)| key == name).map(|(_, tag)| tag),
+            _ => None,
+        }
+    }
+
+    /// Any integer tag
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(value) => Some(value as i64),
+            Tag::Short(value) => Some(value as i64),
+            Tag::Int(value) => Some(value as i64),
+            Tag::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Tag::ByteArray(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&[(String, Tag)]> {
+        match self {
+            Tag::Compound(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+/// The root tag and its name, the bytes must already be decompressed
+pub fn read(bytes: &[u8]) -> Result<(String, Tag), String> {
+    let mut reader = Reader { bytes, position: 0 };
+    let id = reader.u8()?;
+    if id != 10 {
+        return Err(format!("the root tag must be a compound, found the type {}", id));
+    }
+    let name = reader.string()?;
+    let root = reader.payload(id, 0)?;
+    if reader.position != bytes.len() {
+        return Err("unexpected data after the root tag".to_string());
+    }
+    Ok((name, root))
+}
+
+/// Uncompressed, see `read`
+pub fn write(name: &str, root: &Tag) -> Vec<u8> {
+    let mut bytes = vec![root.id()];
+    write_string(&mut bytes, name);
+    write_payload(&mut bytes, root);
+    bytes
+}
+
+fn write_string(bytes: &mut Vec<u8>, text: &str) {
+    bytes.extend((text.len() as u16).to_be_bytes());
+    bytes.extend(text.as_bytes());
+}
+
+fn write_payload(bytes: &mut Vec<u8>, tag: &Tag) {
+    match tag {
+        Tag::Byte(value) => bytes.extend(value.to_be_bytes()),
+        Tag::Short(value) => bytes.extend(value.to_be_bytes()),
+        Tag::Int(value) => bytes.extend(value.to_be_bytes()),
+        Tag::Long(value) => bytes.extend(value.to_be_bytes()),
+        Tag::Float(value) => bytes.extend(value.to_be_bytes()),
+        Tag::Double(value) => bytes.extend(value.to_be_bytes()),
+        Tag::ByteArray(values) => {
+            bytes.extend((values.len() as i32).to_be_bytes());
+            bytes.extend(values);
+        }
+        Tag::String(value) => write_string(bytes, value),
+        Tag::List(elements) => {
+            // an empty list has the type of the end tag
+            bytes.push(elements.first().map_or(0, Tag::id));
+            bytes.extend((elements.len() as i32).to_be_bytes());
+            for element in elements {
+                write_payload(bytes, element);
+            }
+        }
+        Tag::Compound(entries) => {
+            for (name, element) in entries {
+                bytes.push(element.id());
+                write_string(bytes, name);
+                write_payload(bytes, element);
+            }
+            bytes.push(0);
+        }
+        Tag::IntArray(values) => {
+            bytes.extend((values.len() as i32).to_be_bytes());
+            for value in values {
+                bytes.extend(value.to_be_bytes());
+            }
+        }
+        Tag::LongArray(values) => {
+            bytes.extend((values.len() as i32).to_be_bytes());
+            for value in values {
+                bytes.extend(value.to_be_bytes());
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(length).ok_or_else(|| "invalid length".to_string())?;
+        let slice = self.bytes.get(self.position..end).ok_or_else(|| "unexpected end of the tags".to_string())?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Negative lengths are errors
+    fn length(&mut self) -> Result<usize, String> {
+        let length = i32::from_be_bytes(self.array()?);
+        usize::try_from(length).map_err(|_| format!("invalid length {}", length))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = u16::from_be_bytes(self.array()?) as usize;
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+
+    fn payload(&mut self, id: u8, depth: usize) -> Result<Tag, String> {
+        if depth > MAX_DEPTH {
+            return Err("tags nested too deeply".to_string());
+        }
+        Ok(match id {
+            1 => Tag::Byte(i8::from_be_bytes(self.array()?)),
+            2 => Tag::Short(i16::from_be_bytes(self.array()?)),
+            3 => Tag::Int(i32::from_be_bytes(self.array()?)),
+            4 => Tag::Long(i64::from_be_bytes(self.array()?)),
+            5 => Tag::Float(f32::from_be_bytes(self.array()?)),
+            6 => Tag::Double(f64::from_be_bytes(self.array()?)),
+            7 => {
+                let length = self.length()?;
+                Tag::ByteArray(self.take(length)?.to_vec())
+            }
+            8 => Tag::String(self.string()?),
+            9 => {
+                let element = self.u8()?;
+                let length = self.length()?;
+                let mut elements = vec![];
+                for _ in 0..length {
+                    elements.push(self.payload(element, depth + 1)?);
+                }
+                Tag::List(elements)
+            }
+            10 => {
+                let mut entries = vec![];
+                loop {
+                    let id = self.u8()?;
+                    if id == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    entries.push((name, self.payload(id, depth + 1)?));
+                }
+                Tag::Compound(entries)
+            }
+            11 => {
+                let length = self.length()?;
+                let bytes = self.take(length.checked_mul(4).ok_or_else(|| "invalid length".to_string())?)?;
+                Tag::IntArray(bytes.chunks_exact(4).map(|value| i32::from_be_bytes(value.try_into().unwrap())).collect())
+            }
+            12 => {
+                let length = self.length()?;
+                let bytes = self.take(length.checked_mul(8).ok_or_else(|| "invalid length".to_string())?)?;
+                Tag::LongArray(bytes.chunks_exact(8).map(|value| i64::from_be_bytes(value.try_into().unwrap())).collect())
+            }
+            _ => return Err(format!("unknown tag type {}", id)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A compound holding `depth` lists, each inside the previous one
+    fn nested(depth: usize) -> Tag {
+        let mut tag = Tag::List(vec![]);
+        for _ in 1..depth {
+            tag = Tag::List(vec![tag]);
+        }
+        Tag::Compound(vec![("lists".to_string(), tag)])
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        let root = Tag::Compound(vec![
+            ("byte".to_string(), Tag::Byte(-3)),
+            ("short".to_string(), Tag::Short(300)),
+            ("int".to_string(), Tag::Int(-70000)),
+            ("long".to_string(), Tag::Long(1 << 40)),
+            ("float".to_string(), Tag::Float(0.5)),
+            ("double".to_string(), Tag::Double(-2.25)),
+            ("bytes".to_string(), Tag::ByteArray(vec![1, 200])),
+            ("string".to_string(), Tag::String("é".to_string())),
+            ("list".to_string(), Tag::List(vec![Tag::Short(1), Tag::Short(2)])),
+            ("empty".to_string(), Tag::List(vec![])),
+            ("compound".to_string(), Tag::Compound(vec![("int".to_string(), Tag::Int(7))])),
+            ("ints".to_string(), Tag::IntArray(vec![-1, 2])),
+            ("longs".to_string(), Tag::LongArray(vec![i64::MIN])),
+        ]);
+        assert_eq!(read(&write("Schematic", &root)), Ok(("Schematic".to_string(), root.clone())));
+        assert_eq!(root.get("compound").and_then(|tag| tag.get("int")).and_then(Tag::as_int), Some(7));
+    }
+
+    #[test]
+    fn refuses_malformed_tags() {
+        let bytes = write("", &Tag::Compound(vec![("bytes".to_string(), Tag::ByteArray(vec![1, 2, 3]))]));
+        assert!(read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(read(&[bytes.clone(), vec![0]].concat()).is_err());
+        // the root is not a compound
+        assert!(read(&write("", &Tag::Int(3))).is_err());
+        // an unknown tag type, then a negative length
+        assert!(read(&[10, 0, 0, 13, 0, 0, 0]).is_err());
+        assert!(read(&[10, 0, 0, 7, 0, 0, 0xff, 0xff, 0xff, 0xff, 0]).is_err());
+        // a length past the end of the bytes
+        assert!(read(&[10, 0, 0, 12, 0, 0, 0x7f, 0xff, 0xff, 0xff, 0]).is_err());
+    }
+
+    #[test]
+    fn refuses_tags_nested_too_deeply() {
+        assert!(read(&write("", &nested(MAX_DEPTH))).is_ok());
+        assert_eq!(read(&write("", &nested(MAX_DEPTH + 1))), Err("tags nested too deeply".to_string()));
+    }
+}
@@ -0,0 +1,63 @@
+//! Smooth pseudo-random values for the terrain, the same for the same seed and position
+
+use super::random;
+
+/// Value noise in 0..1: random values on the integer lattice, interpolated smoothly between them
+pub fn value_2d(seed: u64, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+    let corner = |dx: i64, dz: i64| lattice(seed, [x0 as i64 + dx, 0, z0 as i64 + dz]);
+    let near = lerp(corner(0, 0), corner(1, 0), tx);
+    let far = lerp(corner(0, 1), corner(1, 1), tx);
+    lerp(near, far, tz)
+}
+
+/// Sum of `octaves` layers of `value_2d`, each twice as detailed and half as strong. In 0..1
+pub fn fractal_2d(seed: u64, x: f32, z: f32, octaves: u32) -> f32 {
+    let (mut total, mut amplitude, mut frequency, mut weight) = (0.0, 1.0, 1.0, 0.0);
+    for octave in 0..octaves {
+        total += value_2d(seed.wrapping_add(octave as u64), x * frequency, z * frequency) * amplitude;
+        weight += amplitude;
+        amplitude /= 2.0;
+        frequency *= 2.0;
+    }
+    total / weight
+}
+
+/// Same as `value_2d` in 3 dimensions, for the caves and the overhangs
+pub fn value_3d(seed: u64, x: f32, y: f32, z: f32) -> f32 {
+    let origin = [x.floor(), y.floor(), z.floor()];
+    let [tx, ty, tz] = [smooth(x - origin[0]), smooth(y - origin[1]), smooth(z - origin[2])];
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        lattice(seed, [origin[0] as i64 + dx, origin[1] as i64 + dy, origin[2] as i64 + dz])
+    };
+    let layer = |dy: i64| {
+        let near = lerp(corner(0, dy, 0), corner(1, dy, 0), tx);
+        let far = lerp(corner(0, dy, 1), corner(1, dy, 1), tx);
+        lerp(near, far, tz)
+    };
+    lerp(layer(0), layer(1), ty)
+}
+
+pub fn fractal_3d(seed: u64, x: f32, y: f32, z: f32, octaves: u32) -> f32 {
+    let (mut total, mut amplitude, mut frequency, mut weight) = (0.0, 1.0, 1.0, 0.0);
+    for octave in 0..octaves {
+        total += value_3d(seed.wrapping_add(octave as u64), x * frequency, y * frequency, z * frequency) * amplitude;
+        weight += amplitude;
+        amplitude /= 2.0;
+        frequency *= 2.0;
+    }
+    total / weight
+}
+
+fn lattice(seed: u64, coordinates: [i64; 3]) -> f32 {
+    (random::hash(seed, coordinates) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
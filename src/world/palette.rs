@@ -0,0 +1,194 @@
+//! Compact storage of the blocks of a chunk: each distinct state is stored once in a palette,
+//! and every block is an index in it, packed in as few bits as the palette needs
+
+use super::state::BlockState;
+
+/// Blocks in a chunk
+pub const VOLUME: usize = 16 * 16 * 16;
+
+pub struct Palette {
+    states: Vec<BlockState>,
+    /// Blocks in each state, the states left with none are reused before the palette grows
+    counts: Vec<u16>,
+    /// Bits per index, indices never span two words
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl Palette {
+    /// Every block in the same state
+    pub fn filled(state: BlockState) -> Palette {
+        Palette {
+            states: vec![state],
+            counts: vec![VOLUME as u16],
+            bits: 1,
+            words: vec![0; VOLUME.div_ceil(64)],
+        }
+    }
+
+    /// From states already packed, as written by a save. None if they do not fit together
+    pub fn from_parts(states: Vec<BlockState>, bits: u32, words: Vec<u64>) -> Option<Palette> {
+        let valid = !states.is_empty()
+            && (1..=16).contains(&bits)
+            && states.len() <= 1 << bits
+            && words.len() == VOLUME.div_ceil((64 / bits) as usize);
+        let counts = vec![0; states.len()];
+        let mut palette = Palette { states, counts, bits, words };
+        if !valid || (0..VOLUME).any(|i| palette.index(i) >= palette.states.len()) {
+            return None;
+        }
+        for i in 0..VOLUME {
+            let index = palette.index(i);
+            palette.counts[index] += 1;
+        }
+        Some(palette)
+    }
+
+    /// Every state of the palette, some may not be used by any block
+    pub fn states(&self) -> &[BlockState] {
+        &self.states
+    }
+
+    /// States of at least one block
+    pub fn used(&self) -> impl Iterator<Item = &BlockState> {
+        self.states.iter().zip(&self.counts).filter(|(_, count)| **count > 0).map(|(state, _)| state)
+    }
+
+    /// Same blocks with only the states in use, in as few bits as they need
+    pub fn compacted(&self) -> Palette {
+        let mut palette = Palette::filled(self.get(0));
+        for i in 1..VOLUME {
+            palette.set(i, self.get(i));
+        }
+        palette
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn get(&self, i: usize) -> BlockState {
+        self.states[self.index(i)]
+    }
+
+    pub fn set(&mut self, i: usize, state: BlockState) {
+        let previous = self.index(i);
+        self.counts[previous] -= 1;
+        let index = match self.states.iter().position(|other| *other == state) {
+            Some(index) => index,
+            None => match self.counts.iter().position(|count| *count == 0) {
+                Some(unused) => {
+                    self.states[unused] = state;
+                    unused
+                }
+                None => {
+                    if self.states.len() == 1 << self.bits {
+                        self.grow();
+                    }
+                    self.states.push(state);
+                    self.counts.push(0);
+                    self.states.len() - 1
+                }
+            },
+        };
+        self.counts[index] += 1;
+        let (word, shift) = self.slot(i);
+        let mask = (1 << self.bits) - 1;
+        self.words[word] = (self.words[word] & !(mask << shift)) | ((index as u64) << shift);
+    }
+
+    fn index(&self, i: usize) -> usize {
+        let (word, shift) = self.slot(i);
+        ((self.words[word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    /// Word and shift of the index of the block `i`
+    fn slot(&self, i: usize) -> (usize, u32) {
+        let per_word = (64 / self.bits) as usize;
+        (i / per_word, (i % per_word) as u32 * self.bits)
+    }
+
+    /// One more bit per index
+    fn grow(&mut self) {
+        let indices: Vec<usize> = (0..VOLUME).map(|i| self.index(i)).collect();
+        self.bits += 1;
+        self.words = vec![0; VOLUME.div_ceil((64 / self.bits) as usize)];
+        for (i, index) in indices.into_iter().enumerate() {
+            let (word, shift) = self.slot(i);
+            self.words[word] |= (index as u64) << shift;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::BlockType;
+    use crate::world::state::Property;
+
+    fn water(level: u8) -> BlockState {
+        BlockState::new(BlockType::Water).with(Property::Level, level)
+    }
+
+    #[test]
+    fn grows_with_the_states() {
+        let mut palette = Palette::filled(BlockState::new(BlockType::Void));
+        for i in 0..VOLUME {
+            palette.set(i, water((i % 5) as u8));
+        }
+        assert_eq!(palette.bits(), 3);
+        assert!((0..VOLUME).all(|i| palette.get(i) == water((i % 5) as u8)));
+        assert_eq!(palette.used().count(), 5);
+    }
+
+    #[test]
+    fn reuses_the_states_left_unused() {
+        let air = BlockState::new(BlockType::Void);
+        let mut palette = Palette::filled(air);
+        palette.set(7, BlockState::new(BlockType::Stone));
+        palette.set(7, air);
+        assert_eq!(palette.used().collect::<Vec<_>>(), [&air]);
+        palette.set(7, BlockState::new(BlockType::Dirt));
+        assert_eq!(palette.states(), [air, BlockState::new(BlockType::Dirt)]);
+        assert_eq!(palette.bits(), 1);
+    }
+
+    #[test]
+    fn compacts_to_the_states_in_use() {
+        let air = BlockState::new(BlockType::Void);
+        let mut palette = Palette::filled(air);
+        for level in 0..8 {
+            palette.set(level as usize, water(level));
+        }
+        for i in 1..8 {
+            palette.set(i, air);
+        }
+        let compacted = palette.compacted();
+        assert_eq!(palette.bits(), 4);
+        assert_eq!(compacted.bits(), 1);
+        assert_eq!(compacted.states(), [water(0), air]);
+        assert!((0..VOLUME).all(|i| compacted.get(i) == palette.get(i)));
+    }
+
+    #[test]
+    fn checks_the_parts() {
+        let air = BlockState::new(BlockType::Void);
+        let palette = Palette::filled(air);
+        let words = palette.words().to_vec();
+        assert!(Palette::from_parts(vec![air], 1, words.clone()).is_some());
+        assert!(Palette::from_parts(vec![], 1, words.clone()).is_none());
+        assert!(Palette::from_parts(vec![air], 0, words.clone()).is_none());
+        assert!(Palette::from_parts(vec![air], 2, words.clone()).is_none());
+        // an index past the states
+        let mut words = words;
+        words[3] = 1 << 5;
+        assert!(Palette::from_parts(vec![air], 1, words.clone()).is_none());
+        let palette = Palette::from_parts(vec![air, water(0)], 1, words).unwrap();
+        assert_eq!(palette.get(3 * 64 + 5), water(0));
+        assert_eq!(palette.used().count(), 2);
+    }
+}
@@ -0,0 +1,34 @@
+//! Crops growing on random ticks
+
+use super::block::BlockType;
+use super::map::Map;
+use super::state::Property;
+
+/// Last growth stage, stored in the level of the block
+pub const MAX_STAGE: u8 = 7;
+/// Ticks between a change next to a plant and the check of its support
+pub const DELAY: u64 = 1;
+
+/// Grows by one stage while it stands on something
+pub fn random_tick(map: &mut Map, coordinates: [i64; 3]) {
+    let Some(block) = map.get_block(coordinates) else {
+        return;
+    };
+    let stage = block.state().level();
+    if block.block_type == BlockType::Wheat && stage < MAX_STAGE && is_supported(map, coordinates) {
+        map.set_state(coordinates, block.state().with(Property::Level, stage + 1)).unwrap();
+    }
+}
+
+/// Breaks the plant when the block below it is gone
+pub fn update(map: &mut Map, coordinates: [i64; 3]) {
+    let is_plant = map.get_block(coordinates).is_some_and(|block| block.block_type == BlockType::Wheat);
+    if is_plant && !is_supported(map, coordinates) {
+        map.set_block(coordinates, BlockType::Void).unwrap();
+    }
+}
+
+fn is_supported(map: &Map, coordinates: [i64; 3]) -> bool {
+    let below = [coordinates[0], coordinates[1] - 1, coordinates[2]];
+    map.get_block(below).is_some_and(|block| !block.block_type.is_replaceable() && block.block_type != BlockType::Wheat)
+}
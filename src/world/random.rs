@@ -0,0 +1,44 @@
+/// Mixes the seed and the coordinates, neighbouring coordinates give unrelated values
+pub fn hash(seed: u64, coordinates: [i64; 3]) -> u64 {
+    let mut h = seed ^ 0x2545_F491_4F6C_DD1D;
+    for c in coordinates {
+        h = (h ^ c as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        h ^= h >> 29;
+    }
+    h
+}
+
+/// Small deterministic generator (xorshift64*), the same seed always gives the same numbers
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // the state must never be 0
+        Random { state: seed ^ 0x9E37_79B9_7F4A_7C15 | 1 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// In 0..bound
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// In min..=max
+    pub fn between(&mut self, min: i64, max: i64) -> i64 {
+        min + self.below((max - min + 1) as u64) as i64
+    }
+
+    /// True once every `n` times on average
+    pub fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+}
@@ -0,0 +1,59 @@
+//! Finds the block the camera looks at, cell by cell along the ray
+
+use super::map::Map;
+use super::state::Facing;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub block: [i64; 3],
+    /// Side of the block the ray went through
+    pub face: Facing,
+}
+
+impl Hit {
+    /// Cell in front of the face, where a block placed against it goes
+    pub fn adjacent(&self) -> [i64; 3] {
+        let offset = self.face.offset();
+        std::array::from_fn(|i| self.block[i] + offset[i])
+    }
+}
+
+/// First block that is neither empty nor a fluid. `origin` is in blocks, with the centers of
+/// the blocks on integer coordinates, and `distance` is in blocks too
+pub fn raycast(map: &Map, origin: [f32; 3], direction: [f32; 3], distance: f32) -> Option<Hit> {
+    let length = (direction[0] * direction[0] + direction[1] * direction[1] + direction[2] * direction[2]).sqrt();
+    if length == 0.0 {
+        return None;
+    }
+    let direction = direction.map(|d| d / length);
+
+    // the cells go from -0.5 to 0.5 around their center
+    let mut cell = origin.map(|o| (o + 0.5).floor() as i64);
+    let step = direction.map(|d| if d > 0.0 { 1 } else { -1 });
+    // distance along the ray to the next boundary on each axis, and between two boundaries
+    let mut next: [f32; 3] = std::array::from_fn(|i| {
+        if direction[i] == 0.0 {
+            return f32::INFINITY;
+        }
+        let boundary = cell[i] as f32 + 0.5 * step[i] as f32;
+        (boundary - origin[i]) / direction[i]
+    });
+    let delta = direction.map(|d| (1.0 / d).abs());
+
+    loop {
+        let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+        if next[axis] > distance {
+            return None;
+        }
+        cell[axis] += step[axis];
+        next[axis] += delta[axis];
+
+        // the ray goes through the chunks that are not loaded
+        let solid = map.get_block(cell).is_some_and(|block| !block.block_type.is_replaceable());
+        if solid {
+            let mut offset = [0; 3];
+            offset[axis] = -step[axis];
+            return Some(Hit { block: cell, face: Facing::from_offset(offset).unwrap() });
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Saves of the world: its seed and time, then the biomes of every column of the map and the
+//! palette and the packed blocks of its sections, in little endian. States are written as text
+//! (`log[axis=x]`) so the bits of the properties can change without breaking older saves
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::biome::Biome;
+use super::map::{Chunk, ChunkColumn, Map, CHUNK_SIZE, MIN_Y, SECTIONS};
+use super::palette::Palette;
+use super::state::BlockState;
+
+const MAGIC: &[u8; 8] = b"CUBESAVE";
+const VERSION: u32 = 1;
+
+/// A world as read from a save
+pub struct Saved {
+    pub map: Map,
+    /// Seed the map was generated from, for the columns generated later
+    pub seed: u64,
+    /// See `WorldTime::ticks`
+    pub ticks: u64,
+}
+
+pub fn save(map: &Map, seed: u64, ticks: u64, path: &Path) -> Result<(), SaveError> {
+    std::fs::write(path, encode(map, seed, ticks)).map_err(|error| SaveError::Io { path: path.to_path_buf(), error })
+}
+
+pub fn load(path: &Path) -> Result<Saved, SaveError> {
+    let bytes = std::fs::read(path).map_err(|error| SaveError::Io { path: path.to_path_buf(), error })?;
+    decode(&bytes).map_err(|message| SaveError::Format { path: path.to_path_buf(), message })
+}
+
+pub fn encode(map: &Map, seed: u64, ticks: u64) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    bytes.extend(seed.to_le_bytes());
+    bytes.extend(ticks.to_le_bytes());
+    bytes.extend((map.columns().count() as u32).to_le_bytes());
+    for column in map.columns() {
+        for c in column.coordinates() {
+            bytes.extend(c.to_le_bytes());
+        }
+
+        // the biomes by name, then the index of the biome of each column of blocks
+        bytes.push(Biome::ALL.len() as u8);
+        for biome in Biome::ALL {
+            bytes.push(biome.name().len() as u8);
+            bytes.extend(biome.name().as_bytes());
+        }
+        for x in 0..16 {
+            for z in 0..16 {
+                let biome = column.biome([x, z]);
+                bytes.push(Biome::ALL.iter().position(|other| *other == biome).unwrap() as u8);
+            }
+        }
+
+        // without the states no block uses anymore
+        for section in column.sections() {
+            write_palette(&mut bytes, &section.palette().compacted());
+        }
+    }
+    bytes
+}
+
+fn write_palette(bytes: &mut Vec<u8>, palette: &Palette) {
+    bytes.extend((palette.states().len() as u16).to_le_bytes());
+    for state in palette.states() {
+        let name = state.to_string();
+        bytes.extend((name.len() as u16).to_le_bytes());
+        bytes.extend(name.as_bytes());
+    }
+    bytes.push(palette.bits() as u8);
+    bytes.extend((palette.words().len() as u32).to_le_bytes());
+    for word in palette.words() {
+        bytes.extend(word.to_le_bytes());
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Saved, String> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("not a save".to_string());
+    }
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+    let [seed, ticks] = [reader.u64()?, reader.u64()?];
+    let columns = read_columns(&mut reader)?;
+    if reader.position != bytes.len() {
+        return Err("unexpected data after the last chunk".to_string());
+    }
+    Ok(Saved { map: Map::from_columns(columns), seed, ticks })
+}
+
+fn read_columns(reader: &mut Reader) -> Result<Vec<ChunkColumn>, String> {
+    let mut columns = vec![];
+    for _ in 0..reader.u32()? {
+        let [x, z] = [reader.i64()?, reader.i64()?];
+        let biomes = read_biomes(reader)?;
+        let bottom = MIN_Y.div_euclid(CHUNK_SIZE);
+        let mut sections = vec![];
+        for y in bottom..bottom + SECTIONS {
+            let palette = read_palette(reader, [x, y, z])?;
+            sections.push(Chunk::from_palette([x, y, z], palette));
+        }
+        let mut column = ChunkColumn::from_sections([x, z], sections).unwrap();
+        set_biomes(&mut column, &biomes);
+        columns.push(column);
+    }
+    Ok(columns)
+}
+
+fn read_palette(reader: &mut Reader, coordinates: [i64; 3]) -> Result<Palette, String> {
+    let mut states = vec![];
+    for _ in 0..reader.u16()? {
+        let length = reader.u16()? as usize;
+        let name = std::str::from_utf8(reader.take(length)?).map_err(|_| "invalid block name".to_string())?;
+        states.push(BlockState::parse(name)?);
+    }
+    let bits = reader.take(1)?[0] as u32;
+    let mut words = vec![];
+    for _ in 0..reader.u32()? {
+        words.push(reader.u64()?);
+    }
+    Palette::from_parts(states, bits, words).ok_or_else(|| format!("invalid blocks in chunk {:?}", coordinates))
+}
+
+fn read_biomes(reader: &mut Reader) -> Result<[[Biome; 16]; 16], String> {
+    let mut names = vec![];
+    for _ in 0..reader.take(1)?[0] {
+        let length = reader.take(1)?[0] as usize;
+        let name = std::str::from_utf8(reader.take(length)?).map_err(|_| "invalid biome name".to_string())?;
+        names.push(Biome::from_name(name).ok_or_else(|| format!("unknown biome `{}`", name))?);
+    }
+    let mut biomes = [[Biome::Plains; 16]; 16];
+    for biome in biomes.iter_mut().flatten() {
+        let index = reader.take(1)?[0] as usize;
+        *biome = *names.get(index).ok_or_else(|| "invalid biome index".to_string())?;
+    }
+    Ok(biomes)
+}
+
+fn set_biomes(column: &mut ChunkColumn, biomes: &[[Biome; 16]; 16]) {
+    for (x, row) in biomes.iter().enumerate() {
+        for (z, biome) in row.iter().enumerate() {
+            column.set_biome([x, z], *biome);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self.position + length;
+        let slice = self.bytes.get(self.position..end).ok_or_else(|| "unexpected end of the save".to_string())?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io { path: PathBuf, error: std::io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io { path, error } => write!(f, "could not access {}: {}", path.display(), error),
+            SaveError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_biomes_of_the_columns() {
+        let mut column = ChunkColumn::new([2, -1]);
+        column.set_biome([0, 0], Biome::Desert);
+        column.set_biome([15, 3], Biome::Swamp);
+        let map = decode(&encode(&Map::from_columns(vec![column, ChunkColumn::new([0, 0])]), 0, 0)).unwrap().map;
+        assert_eq!(map.biome(32, -16), Some(Biome::Desert));
+        assert_eq!(map.biome(47, -13), Some(Biome::Swamp));
+        assert_eq!(map.biome(33, -16), Some(Biome::Plains));
+        assert_eq!(map.biome(0, 0), Some(Biome::Plains));
+        assert_eq!(map.chunks().count(), 2 * SECTIONS as usize);
+    }
+
+    #[test]
+    fn keeps_the_blocks() {
+        let mut map = Map::from_columns(vec![ChunkColumn::new([0, 0]), ChunkColumn::new([-1, 3])]);
+        let log = BlockState::parse("log[axis=x]").unwrap();
+        let water = BlockState::parse("water[level=3]").unwrap();
+        map.set_state([1, 2, 3], log).unwrap();
+        map.set_state([-5, 40, 60], water).unwrap();
+        map.set_state([15, 63, 15], water).unwrap();
+        // a state no block uses anymore is not saved
+        map.set_state([0, 0, 0], BlockState::parse("stone").unwrap()).unwrap();
+        map.set_state([0, 0, 0], BlockState::parse("void").unwrap()).unwrap();
+        let loaded = decode(&encode(&map, 0, 0)).unwrap().map;
+        for (chunk, other) in map.chunks().zip(loaded.chunks()) {
+            assert_eq!(chunk.coordinates(), other.coordinates());
+            assert!(chunk.blocks().map(|block| block.state()).eq(other.blocks().map(|block| block.state())));
+        }
+        assert_eq!(loaded.chunks().count(), map.chunks().count());
+        assert_eq!(loaded.get_block([1, 2, 3]).unwrap().state(), log);
+        assert_eq!(loaded.chunk([0, 0, 0]).unwrap().palette().states().len(), 2);
+        assert_eq!(loaded.height(15, 15), Some(63));
+    }
+
+    #[test]
+    fn keeps_the_seed_and_the_time() {
+        let saved = decode(&encode(&Map::from_columns(vec![ChunkColumn::new([0, 0])]), 42, 12345)).unwrap();
+        assert_eq!(saved.seed, 42);
+        assert_eq!(saved.ticks, 12345);
+        assert_eq!(saved.map.chunks().count(), SECTIONS as usize);
+    }
+
+    #[test]
+    fn refuses_other_files() {
+        assert!(decode(b"NOTASAVE").is_err());
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((VERSION + 1).to_le_bytes());
+        assert!(decode(&bytes).is_err());
+        let mut bytes = encode(&Map::from_columns(vec![ChunkColumn::new([0, 0])]), 0, 0);
+        bytes.pop();
+        assert!(decode(&bytes).is_err());
+    }
+}
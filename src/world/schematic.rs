@@ -0,0 +1,452 @@
+//! Clipboards read from and written to the files of other tools: Sponge schematics (`.schem`,
+//! gzipped NBT) and MagicaVoxel models (`.vox`, see `vox`). Their blocks are turned into ours
+//! with a `BlockMapping`, the ones it does not know become empty and are reported
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use super::block::{Block, BlockType};
+use super::edit::Clipboard;
+use super::nbt::{self, Tag};
+use super::plants::MAX_STAGE;
+use super::state::{BlockState, Property};
+use super::vox;
+
+/// Namespace of our own blocks in the schematics, `cubegame:log[axis=x]`
+const NAMESPACE: &str = "cubegame:";
+/// Minecraft 1.20.1, the version the exported block names come from
+const DATA_VERSION: i32 = 3465;
+
+/// Foreign blocks and our blocks, the first name of a block is the one it is exported with
+const NAMES: [(&str, &str); 38] = [
+    ("minecraft:air", "void"),
+    ("minecraft:cave_air", "void"),
+    ("minecraft:void_air", "void"),
+    ("minecraft:bedrock", "bedrock"),
+    ("minecraft:glass", "glass"),
+    ("minecraft:oak_leaves", "leaves"),
+    ("minecraft:spruce_leaves", "leaves"),
+    ("minecraft:birch_leaves", "leaves"),
+    ("minecraft:jungle_leaves", "leaves"),
+    ("minecraft:water", "water"),
+    ("minecraft:pink_stained_glass", "stained_glass"),
+    ("minecraft:magenta_stained_glass", "stained_glass"),
+    ("minecraft:lava", "lava"),
+    ("minecraft:stone", "stone"),
+    ("minecraft:sand", "sand"),
+    ("minecraft:gravel", "gravel"),
+    ("minecraft:wheat", "wheat"),
+    ("minecraft:oak_log", "log"),
+    ("minecraft:spruce_log", "log"),
+    ("minecraft:birch_log", "log"),
+    ("minecraft:jungle_log", "log"),
+    ("minecraft:furnace", "furnace"),
+    ("minecraft:oak_trapdoor", "trapdoor"),
+    ("minecraft:spruce_trapdoor", "trapdoor"),
+    ("minecraft:dirt", "dirt"),
+    ("minecraft:coarse_dirt", "dirt"),
+    ("minecraft:grass_block", "grass"),
+    ("minecraft:cobblestone", "cobblestone"),
+    ("minecraft:oak_planks", "planks"),
+    ("minecraft:spruce_planks", "planks"),
+    ("minecraft:birch_planks", "planks"),
+    ("minecraft:jungle_planks", "planks"),
+    ("minecraft:coal_ore", "coal_ore"),
+    ("minecraft:deepslate_coal_ore", "coal_ore"),
+    ("minecraft:iron_ore", "iron_ore"),
+    ("minecraft:deepslate_iron_ore", "iron_ore"),
+    ("minecraft:andesite", "stone"),
+    ("minecraft:granite", "stone"),
+];
+
+/// How the blocks and the colours of the other tools become ours. The entries of a mapping file
+/// come before the default ones, so they win
+#[derive(Clone, Debug)]
+pub struct BlockMapping {
+    /// Names with or without properties: `minecraft:wheat[age=7]` or `minecraft:oak_log`
+    names: Vec<(String, BlockState)>,
+    colours: Vec<([u8; 3], BlockState)>,
+}
+
+impl BlockMapping {
+    /// The default entries only: the usual Minecraft blocks and the colours of our blocks
+    pub fn new() -> BlockMapping {
+        let mut names = vec![];
+        // the growth stages are called age in Minecraft
+        for stage in 0..=MAX_STAGE {
+            let state = BlockState::new(BlockType::Wheat).with(Property::Level, stage);
+            names.push((format!("minecraft:wheat[age={}]", stage), state));
+        }
+        for (name, block) in NAMES {
+            names.push((name.to_string(), BlockState::parse(block).unwrap()));
+        }
+
+        let mut colours = vec![];
+        for block_type in BlockType::ALL.into_iter().filter(|block_type| *block_type != BlockType::Void) {
+            colours.push((colour(BlockState::new(block_type)), BlockState::new(block_type)));
+        }
+        for stage in 1..=MAX_STAGE {
+            let state = BlockState::new(BlockType::Wheat).with(Property::Level, stage);
+            colours.push((colour(state), state));
+        }
+        BlockMapping { names, colours }
+    }
+
+    pub fn load(path: &Path) -> Result<BlockMapping, SchematicError> {
+        let source = std::fs::read_to_string(path).map_err(|error| SchematicError::Io { path: path.to_path_buf(), error })?;
+        BlockMapping::parse(&source, path)
+    }
+
+    /// Lines `minecraft:stone_bricks = cobblestone` or `colour 7f7f7f = stone[...]`, `#` starts a comment
+    pub fn parse(source: &str, path: &Path) -> Result<BlockMapping, SchematicError> {
+        let defaults = BlockMapping::new();
+        let mut mapping = BlockMapping { names: vec![], colours: vec![] };
+
+        for (number, line) in source.lines().enumerate() {
+            let error = |message: String| SchematicError::Mapping { path: path.to_path_buf(), line: number + 1, message };
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (foreign, block) = line.split_once('=').ok_or_else(|| error(format!("expected `foreign = block`, got `{}`", line)))?;
+            let state = BlockState::parse(block.trim()).map_err(error)?;
+            match foreign.trim().strip_prefix("colour ") {
+                Some(hex) => {
+                    let colour = parse_colour(hex.trim()).ok_or_else(|| error(format!("invalid colour `{}`, expected rrggbb", hex.trim())))?;
+                    mapping.colours.push((colour, state));
+                }
+                None => mapping.names.push((foreign.trim().to_string(), state)),
+            }
+        }
+
+        mapping.names.extend(defaults.names);
+        mapping.colours.extend(defaults.colours);
+        Ok(mapping)
+    }
+
+    /// Our block for a foreign one, such as `minecraft:furnace[facing=north,lit=false]`. Without an
+    /// entry for the whole name, the properties we also have are kept and the others are dropped
+    pub fn by_name(&self, foreign: &str) -> Option<BlockState> {
+        if let Some(ours) = foreign.strip_prefix(NAMESPACE) {
+            return BlockState::parse(ours).ok();
+        }
+        if let Some((_, state)) = self.names.iter().find(|(name, _)| name == foreign) {
+            return Some(*state);
+        }
+        let (name, properties) = foreign.split_once('[').unwrap_or((foreign, ""));
+        let mut state = self.names.iter().find(|(other, _)| other == name)?.1;
+        for pair in properties.trim_end_matches(']').split(',') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let property = Property::from_name(key.trim()).filter(|property| state.block_type.properties().contains(property));
+            if let Some(property) = property && let Some(value) = property.parse_value(value.trim()) {
+                state = state.with(property, value);
+            }
+        }
+        Some(state)
+    }
+
+    /// Name a block is exported with: an entry for the whole state, else the first entry of its
+    /// type with our properties, else our own name
+    pub fn name(&self, state: BlockState) -> String {
+        if let Some((name, _)) = self.names.iter().find(|(name, other)| name.contains('[') && *other == state) {
+            return name.clone();
+        }
+        let text = state.to_string();
+        let properties = &text[state.block_type.name().len()..];
+        match self.names.iter().find(|(name, other)| !name.contains('[') && other.block_type == state.block_type) {
+            Some((name, _)) => format!("{}{}", name, properties),
+            None => format!("{}{}", NAMESPACE, text),
+        }
+    }
+
+    pub fn by_colour(&self, colour: [u8; 3]) -> Option<BlockState> {
+        self.colours.iter().find(|(other, _)| *other == colour).map(|(_, state)| *state)
+    }
+
+    /// Colour a block is exported with, the first entry for it or else its own colour
+    pub fn colour(&self, state: BlockState) -> [u8; 3] {
+        self.colours.iter().find(|(_, other)| *other == state).map_or_else(|| colour(state), |(colour, _)| *colour)
+    }
+}
+
+/// Colour of the block as drawn, in bytes
+fn colour(state: BlockState) -> [u8; 3] {
+    let [r, g, b, _] = Block::new(state, [0; 3]).colour();
+    [r, g, b].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+fn parse_colour(hex: &str) -> Option<[u8; 3]> {
+    if hex.len() != 6 {
+        return None;
+    }
+    let mut colour = [0; 3];
+    for (i, c) in colour.iter_mut().enumerate() {
+        *c = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(colour)
+}
+
+/// A clipboard read from a file, with the foreign blocks that had no mapping and how many there were
+pub struct Import {
+    pub clipboard: Clipboard,
+    pub unmapped: Vec<(String, usize)>,
+}
+
+impl Import {
+    pub fn new(size: [i64; 3]) -> Import {
+        Import { clipboard: Clipboard::empty(size), unmapped: vec![] }
+    }
+
+    /// Puts the block in the clipboard, or counts the foreign block when there is no mapping for it
+    pub fn set(&mut self, c: [i64; 3], state: Option<BlockState>, foreign: impl FnOnce() -> String) {
+        let Some(state) = state else {
+            let foreign = foreign();
+            match self.unmapped.iter_mut().find(|(other, _)| *other == foreign) {
+                Some((_, count)) => *count += 1,
+                None => self.unmapped.push((foreign, 1)),
+            }
+            return;
+        };
+        self.clipboard.set(c, state);
+    }
+}
+
+/// Reads a `.schem` or a `.vox` file, depending on its extension
+pub fn load(path: &Path, mapping: &BlockMapping) -> Result<Import, SchematicError> {
+    let bytes = std::fs::read(path).map_err(|error| SchematicError::Io { path: path.to_path_buf(), error })?;
+    let format = |message: String| SchematicError::Format { path: path.to_path_buf(), message };
+    match extension(path) {
+        Some("schem") => {
+            let mut uncompressed = vec![];
+            GzDecoder::new(&bytes[..]).read_to_end(&mut uncompressed).map_err(|error| format(format!("invalid gzip: {}", error)))?;
+            read_sponge(&uncompressed, mapping).map_err(format)
+        }
+        Some("vox") => vox::read(&bytes, mapping).map_err(format),
+        _ => Err(format("unknown format, expected a .schem or .vox file".to_string())),
+    }
+}
+
+/// Writes a `.schem` or a `.vox` file, depending on its extension
+pub fn save(path: &Path, clipboard: &Clipboard, mapping: &BlockMapping) -> Result<(), SchematicError> {
+    let format = |message: String| SchematicError::Format { path: path.to_path_buf(), message };
+    let bytes = match extension(path) {
+        Some("schem") => {
+            let uncompressed = write_sponge(clipboard, mapping).map_err(format)?;
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(&uncompressed).and_then(|_| encoder.finish())
+                .map_err(|error| SchematicError::Io { path: path.to_path_buf(), error })?
+        }
+        Some("vox") => vox::write(clipboard, mapping).map_err(format)?,
+        _ => return Err(format("unknown format, expected a .schem or .vox file".to_string())),
+    };
+    std::fs::write(path, bytes).map_err(|error| SchematicError::Io { path: path.to_path_buf(), error })
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|extension| extension.to_str())
+}
+
+/// Versions 1 to 3 of the Sponge format, the blocks only
+pub fn read_sponge(bytes: &[u8], mapping: &BlockMapping) -> Result<Import, String> {
+    let (_, root) = nbt::read(bytes)?;
+    // version 3 puts everything in a compound named Schematic
+    let root = root.get("Schematic").unwrap_or(&root);
+    let version = root.get("Version").and_then(Tag::as_int).unwrap_or(1);
+    let dimension = |name: &str| {
+        root.get(name).and_then(Tag::as_int).map(|value| value as u16 as i64).ok_or_else(|| format!("missing {}", name))
+    };
+    let (width, height, length) = (dimension("Width")?, dimension("Height")?, dimension("Length")?);
+    let (palette, data) = match version {
+        1 | 2 => (root.get("Palette"), root.get("BlockData")),
+        3 => {
+            let blocks = root.get("Blocks").ok_or_else(|| "missing Blocks".to_string())?;
+            (blocks.get("Palette"), blocks.get("Data"))
+        }
+        _ => return Err(format!("unsupported version {}", version)),
+    };
+    let palette = palette.and_then(Tag::as_compound).ok_or_else(|| "missing Palette".to_string())?;
+    let data = data.and_then(Tag::as_bytes).ok_or_else(|| "missing block data".to_string())?;
+
+    // foreign name of each index of the palette
+    let mut names = vec![None; palette.len()];
+    for (name, index) in palette {
+        let index = index.as_int().and_then(|index| usize::try_from(index).ok()).filter(|index| *index < names.len());
+        let slot = index.ok_or_else(|| format!("invalid palette index for `{}`", name))?;
+        names[slot] = Some(name.as_str());
+    }
+
+    let mut indices = vec![];
+    let (mut value, mut shift) = (0usize, 0);
+    for byte in data {
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            indices.push(value);
+            (value, shift) = (0, 0);
+        } else {
+            shift += 7;
+            if shift > 28 {
+                return Err("invalid block data".to_string());
+            }
+        }
+    }
+    let size = [width, height, length];
+    if indices.len() as i64 != width * height * length {
+        return Err(format!("{} blocks for a size of {}x{}x{}", indices.len(), width, height, length));
+    }
+
+    let mut import = Import::new(size);
+    for (i, index) in indices.into_iter().enumerate() {
+        let name = names.get(index).copied().flatten().ok_or_else(|| format!("invalid palette index {}", index))?;
+        // the Sponge order is x, then z, then y
+        let i = i as i64;
+        let [x, z, y] = [i % width, i / width % length, i / (width * length)];
+        import.set([x, y, z], mapping.by_name(name), || name.to_string());
+    }
+    Ok(import)
+}
+
+/// Version 2 of the Sponge format, uncompressed
+pub fn write_sponge(clipboard: &Clipboard, mapping: &BlockMapping) -> Result<Vec<u8>, String> {
+    let [width, height, length] = clipboard.size;
+    if clipboard.size.iter().any(|size| *size > u16::MAX as i64) {
+        return Err(format!("the clipboard is too big, {}x{}x{}", width, height, length));
+    }
+    let mut palette: Vec<BlockState> = vec![];
+    let mut data = vec![];
+    for y in 0..height {
+        for z in 0..length {
+            for x in 0..width {
+                let state = clipboard.get([x, y, z]);
+                let index = match palette.iter().position(|other| *other == state) {
+                    Some(index) => index,
+                    None => {
+                        palette.push(state);
+                        palette.len() - 1
+                    }
+                };
+                // variable length, 7 bits at a time
+                let mut value = index;
+                while value >= 0x80 {
+                    data.push((value & 0x7f) as u8 | 0x80);
+                    value >>= 7;
+                }
+                data.push(value as u8);
+            }
+        }
+    }
+    let palette_tags: Vec<(String, Tag)> = palette.iter().enumerate()
+        .map(|(index, state)| (mapping.name(*state), Tag::Int(index as i32)))
+        .collect();
+    let root = Tag::Compound(vec![
+        ("Version".to_string(), Tag::Int(2)),
+        ("DataVersion".to_string(), Tag::Int(DATA_VERSION)),
+        ("Width".to_string(), Tag::Short(width as u16 as i16)),
+        ("Height".to_string(), Tag::Short(height as u16 as i16)),
+        ("Length".to_string(), Tag::Short(length as u16 as i16)),
+        ("Offset".to_string(), Tag::IntArray(vec![0, 0, 0])),
+        ("PaletteMax".to_string(), Tag::Int(palette.len() as i32)),
+        ("Palette".to_string(), Tag::Compound(palette_tags)),
+        ("BlockData".to_string(), Tag::ByteArray(data)),
+        ("BlockEntities".to_string(), Tag::List(vec![])),
+    ]);
+    Ok(nbt::write("Schematic", &root))
+}
+
+#[derive(Debug)]
+pub enum SchematicError {
+    Io { path: PathBuf, error: std::io::Error },
+    Format { path: PathBuf, message: String },
+    Mapping { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchematicError::Io { path, error } => write!(f, "could not access {}: {}", path.display(), error),
+            SchematicError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+            SchematicError::Mapping { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for SchematicError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clipboard() -> Clipboard {
+        let mut clipboard = Clipboard::empty([3, 2, 4]);
+        for (c, state) in [
+            ([0, 0, 0], "log[axis=x]"),
+            ([1, 0, 0], "furnace[facing=east]"),
+            ([2, 0, 3], "trapdoor[facing=south,open=true]"),
+            ([0, 1, 2], "wheat[level=5]"),
+            ([1, 1, 1], "water[level=3,falling=false]"),
+            ([2, 1, 3], "stone"),
+        ] {
+            clipboard.set(c, BlockState::parse(state).unwrap());
+        }
+        clipboard
+    }
+
+    #[test]
+    fn imports_what_it_exports() {
+        let mapping = BlockMapping::new();
+        let import = read_sponge(&write_sponge(&clipboard(), &mapping).unwrap(), &mapping).unwrap();
+        assert_eq!(import.clipboard, clipboard());
+        assert!(import.unmapped.is_empty());
+    }
+
+    #[test]
+    fn exports_with_the_minecraft_names() {
+        let mapping = BlockMapping::new();
+        assert_eq!(mapping.name(BlockState::parse("log[axis=x]").unwrap()), "minecraft:oak_log[axis=x]");
+        assert_eq!(mapping.name(BlockState::parse("wheat[level=5]").unwrap()), "minecraft:wheat[age=5]");
+        assert_eq!(mapping.by_name("minecraft:spruce_log[axis=z]"), BlockState::parse("log[axis=z]").ok());
+        assert_eq!(mapping.by_name("minecraft:furnace[facing=west,lit=true]"), BlockState::parse("furnace[facing=west]").ok());
+        assert_eq!(mapping.by_name("minecraft:diamond_block"), None);
+    }
+
+    #[test]
+    fn saves_and_loads_the_files() {
+        let mapping = BlockMapping::new();
+        for extension in ["schem", "vox"] {
+            let path = std::env::temp_dir().join(format!("cubegame-test-{}.{}", std::process::id(), extension));
+            save(&path, &clipboard(), &mapping).unwrap();
+            let import = load(&path, &mapping);
+            std::fs::remove_file(&path).unwrap();
+            let clipboard = import.unwrap().clipboard;
+            assert_eq!(clipboard.size, [3, 2, 4]);
+            assert_eq!(clipboard.get([2, 1, 3]), BlockState::parse("stone").unwrap());
+        }
+        assert!(matches!(save(Path::new("blocks.txt"), &clipboard(), &mapping), Err(SchematicError::Format { .. })));
+    }
+
+    #[test]
+    fn counts_the_unmapped_blocks() {
+        let mapping = BlockMapping::parse("minecraft:stone_bricks = cobblestone", Path::new("mapping.txt")).unwrap();
+        let root = Tag::Compound(vec![
+            ("Version".to_string(), Tag::Int(2)),
+            ("Width".to_string(), Tag::Short(3)),
+            ("Height".to_string(), Tag::Short(1)),
+            ("Length".to_string(), Tag::Short(1)),
+            ("Palette".to_string(), Tag::Compound(vec![
+                ("minecraft:stone_bricks".to_string(), Tag::Int(0)),
+                ("minecraft:diamond_block".to_string(), Tag::Int(1)),
+            ])),
+            ("BlockData".to_string(), Tag::ByteArray(vec![0, 1, 1])),
+        ]);
+        let import = read_sponge(&nbt::write("", &root), &mapping).unwrap();
+        assert_eq!(import.clipboard.get([0, 0, 0]), BlockState::new(BlockType::Cobblestone));
+        assert_eq!(import.unmapped, [("minecraft:diamond_block".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,316 @@
+//! Properties of a block beyond its type (orientation, level...), packed in a few bits.
+//! Each block type lists the properties it has in `BlockType::properties`
+
+use std::fmt;
+
+use super::block::BlockType;
+
+/// Direction a block is turned to, also the sides of a block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facing {
+    North,
+    South,
+    East,
+    West,
+    Up,
+    Down,
+}
+
+impl Facing {
+    pub const ALL: [Facing; 6] = [Facing::North, Facing::South, Facing::East, Facing::West, Facing::Up, Facing::Down];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Facing::North => "north",
+            Facing::South => "south",
+            Facing::East => "east",
+            Facing::West => "west",
+            Facing::Up => "up",
+            Facing::Down => "down",
+        }
+    }
+
+    /// North is towards -z and east towards +x
+    pub fn offset(self) -> [i64; 3] {
+        match self {
+            Facing::North => [0, 0, -1],
+            Facing::South => [0, 0, 1],
+            Facing::East => [1, 0, 0],
+            Facing::West => [-1, 0, 0],
+            Facing::Up => [0, 1, 0],
+            Facing::Down => [0, -1, 0],
+        }
+    }
+
+    pub fn from_offset(offset: [i64; 3]) -> Option<Facing> {
+        Facing::ALL.into_iter().find(|facing| facing.offset() == offset)
+    }
+
+    pub fn opposite(self) -> Facing {
+        Facing::from_offset(self.offset().map(|c| -c)).unwrap()
+    }
+
+    pub fn axis(self) -> Axis {
+        match self {
+            Facing::East | Facing::West => Axis::X,
+            Facing::Up | Facing::Down => Axis::Y,
+            Facing::North | Facing::South => Axis::Z,
+        }
+    }
+
+    /// Turned by quarter turns around the y axis, from east towards south
+    pub fn rotated(self, turns: u32) -> Facing {
+        let mut offset = self.offset();
+        for _ in 0..turns % 4 {
+            offset = [-offset[2], offset[1], offset[0]];
+        }
+        Facing::from_offset(offset).unwrap()
+    }
+
+    /// Reflected across the plane perpendicular to the axis
+    pub fn mirrored(self, axis: Axis) -> Facing {
+        let mut offset = self.offset();
+        offset[axis.index()] = -offset[axis.index()];
+        Facing::from_offset(offset).unwrap()
+    }
+
+    /// Horizontal side the direction points to the most
+    pub fn horizontal(direction: [f32; 3]) -> Facing {
+        if direction[0].abs() > direction[2].abs() {
+            if direction[0] > 0.0 { Facing::East } else { Facing::West }
+        } else if direction[2] > 0.0 {
+            Facing::South
+        } else {
+            Facing::North
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        }
+    }
+
+    /// Index of the coordinate along the axis
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Property {
+    Facing,
+    Axis,
+    Open,
+    /// For the fluids see `fluid::SOURCE`, for the plants their growth stage
+    Level,
+    /// Fluid flowing down from the block above, see `fluid::update`
+    Falling,
+}
+
+impl Property {
+    pub const ALL: [Property; 5] = [Property::Facing, Property::Axis, Property::Open, Property::Level, Property::Falling];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Property::Facing => "facing",
+            Property::Axis => "axis",
+            Property::Open => "open",
+            Property::Level => "level",
+            Property::Falling => "falling",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Property> {
+        Property::ALL.into_iter().find(|property| property.name() == name)
+    }
+
+    /// Bits used in the packed state
+    fn bits(self) -> u32 {
+        match self {
+            Property::Facing => 3,
+            Property::Axis => 2,
+            Property::Open | Property::Falling => 1,
+            Property::Level => 3,
+        }
+    }
+
+    /// Number of values, from 0
+    pub fn values(self) -> u8 {
+        match self {
+            Property::Facing => Facing::ALL.len() as u8,
+            Property::Axis => Axis::ALL.len() as u8,
+            Property::Open | Property::Falling => 2,
+            Property::Level => 8,
+        }
+    }
+
+    pub fn value_name(self, value: u8) -> String {
+        match self {
+            Property::Facing => Facing::ALL[value as usize].name().to_string(),
+            Property::Axis => Axis::ALL[value as usize].name().to_string(),
+            Property::Open | Property::Falling => (value != 0).to_string(),
+            Property::Level => value.to_string(),
+        }
+    }
+
+    pub fn parse_value(self, name: &str) -> Option<u8> {
+        match self {
+            Property::Facing => Facing::ALL.iter().position(|facing| facing.name() == name).map(|i| i as u8),
+            Property::Axis => Axis::ALL.iter().position(|axis| axis.name() == name).map(|i| i as u8),
+            Property::Open | Property::Falling => name.parse::<bool>().ok().map(u8::from),
+            Property::Level => name.parse().ok().filter(|&level| level < self.values()),
+        }
+    }
+}
+
+/// A block type and the values of its properties, what the chunk palettes store
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockState {
+    pub block_type: BlockType,
+    /// Values of `block_type.properties()` one after the other, from the lowest bits
+    pub properties: u16,
+}
+
+impl BlockState {
+    /// Every property at 0
+    pub fn new(block_type: BlockType) -> BlockState {
+        BlockState { block_type, properties: 0 }
+    }
+
+    /// None if the block does not have the property
+    pub fn get(self, property: Property) -> Option<u8> {
+        let (shift, mask) = self.position(property)?;
+        Some(((self.properties >> shift) & mask) as u8)
+    }
+
+    /// Properties the block does not have are ignored
+    pub fn with(self, property: Property, value: u8) -> BlockState {
+        let Some((shift, mask)) = self.position(property) else {
+            return self;
+        };
+        let value = value.min(property.values() - 1) as u16;
+        BlockState {
+            block_type: self.block_type,
+            properties: (self.properties & !(mask << shift)) | (value << shift),
+        }
+    }
+
+    pub fn facing(self) -> Option<Facing> {
+        self.get(Property::Facing).map(|value| Facing::ALL[value as usize])
+    }
+
+    pub fn axis(self) -> Option<Axis> {
+        self.get(Property::Axis).map(|value| Axis::ALL[value as usize])
+    }
+
+    pub fn is_open(self) -> bool {
+        self.get(Property::Open) == Some(1)
+    }
+
+    /// 0 for the blocks without a level
+    pub fn level(self) -> u8 {
+        self.get(Property::Level).unwrap_or(0)
+    }
+
+    /// State of a block placed against `face` of another block, by someone looking along `look`
+    pub fn placed(block_type: BlockType, face: Facing, look: [f32; 3]) -> BlockState {
+        let state = BlockState::new(block_type);
+        let facing = match block_type {
+            // the front is turned to whoever placed it
+            BlockType::Furnace | BlockType::Trapdoor => Facing::horizontal(look).opposite(),
+            _ => face,
+        };
+        state
+            .with(Property::Axis, face.axis() as u8)
+            .with(Property::Facing, facing as u8)
+    }
+
+    /// Same block turned by quarter turns around the y axis, see `Facing::rotated`
+    pub fn rotated(self, turns: u32) -> BlockState {
+        let mut state = self;
+        if let Some(facing) = self.facing() {
+            state = state.with(Property::Facing, facing.rotated(turns) as u8);
+        }
+        let swapped = match self.axis() {
+            Some(Axis::X) => Axis::Z,
+            Some(Axis::Z) => Axis::X,
+            _ => Axis::Y,
+        };
+        if turns % 2 == 1 {
+            state = state.with(Property::Axis, swapped as u8);
+        }
+        state
+    }
+
+    /// Same block reflected across the plane perpendicular to the axis
+    pub fn mirrored(self, axis: Axis) -> BlockState {
+        match self.facing() {
+            Some(facing) => self.with(Property::Facing, facing.mirrored(axis) as u8),
+            None => self,
+        }
+    }
+
+    /// Shift and mask of the property in `properties`
+    fn position(self, property: Property) -> Option<(u32, u16)> {
+        let mut shift = 0;
+        for other in self.block_type.properties() {
+            if *other == property {
+                return Some((shift, (1 << property.bits()) - 1));
+            }
+            shift += other.bits();
+        }
+        None
+    }
+
+    /// Parses `name` or `name[property=value,...]`, the missing properties are 0
+    pub fn parse(text: &str) -> Result<BlockState, String> {
+        let (name, properties) = match text.split_once('[') {
+            Some((name, rest)) => {
+                let properties = rest.strip_suffix(']').ok_or_else(|| format!("missing `]` in `{}`", text))?;
+                (name, properties)
+            }
+            None => (text, ""),
+        };
+        let block_type = BlockType::from_name(name).ok_or_else(|| format!("unknown block `{}`", name))?;
+        let mut state = BlockState::new(block_type);
+        for pair in properties.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| format!("expected `property=value`, found `{}`", pair))?;
+            let property = Property::from_name(key.trim())
+                .filter(|property| block_type.properties().contains(property))
+                .ok_or_else(|| format!("{} has no property `{}`", name, key.trim()))?;
+            let value = property.parse_value(value.trim())
+                .ok_or_else(|| format!("invalid {} `{}`", key.trim(), value.trim()))?;
+            state = state.with(property, value);
+        }
+        Ok(state)
+    }
+}
+
+/// Same syntax as `BlockState::parse`, without the brackets when there are no properties
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.block_type.name())?;
+        let properties = self.block_type.properties();
+        if properties.is_empty() {
+            return Ok(());
+        }
+        let values: Vec<String> = properties.iter()
+            .map(|property| format!("{}={}", property.name(), property.value_name(self.get(*property).unwrap())))
+            .collect();
+        write!(f, "[{}]", values.join(","))
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+/// Simulation steps per second of real time
+pub const TICKS_PER_SECOND: u32 = 20;
+/// A full day lasts 20 minutes
+pub const TICKS_PER_DAY: u64 = 24000;
+
+// Moments of the day, in ticks since the start of the day
+pub const SUNRISE: u64 = 0;
+pub const NOON: u64 = 6000;
+pub const SUNSET: u64 = 12000;
+pub const MIDNIGHT: u64 = 18000;
+
+/// Farthest the time can be moved to, far enough from the end of u64 for the ticks and the
+/// updates scheduled after them to keep counting
+pub const MAX_TICKS: u64 = u64::MAX / 2;
+
+/// Sky light at night, so the world is never completely black
+pub const MIN_SKY_LIGHT: f32 = 0.2;
+
+/// Time of the world, only advanced by the simulation so it can be driven without a window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldTime {
+    /// Ticks since the creation of the world
+    pub ticks: u64,
+}
+
+impl WorldTime {
+    pub fn new() -> WorldTime {
+        WorldTime { ticks: NOON }
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    pub fn day(&self) -> u64 {
+        self.ticks / TICKS_PER_DAY
+    }
+
+    /// Ticks since the start of the current day
+    pub fn time_of_day(&self) -> u64 {
+        self.ticks % TICKS_PER_DAY
+    }
+
+    /// Moves to the given moment of the current day, the day count is kept
+    pub fn set_time_of_day(&mut self, time: u64) {
+        self.ticks = self.day() * TICKS_PER_DAY + time % TICKS_PER_DAY;
+    }
+
+    /// Angle of the sun above the east horizon, in radians: 0 at sunrise, PI/2 at noon
+    pub fn sun_angle(&self) -> f32 {
+        self.time_of_day() as f32 / TICKS_PER_DAY as f32 * std::f32::consts::TAU
+    }
+
+    /// Unit vector towards the sun, it rises in +x and sets in -x
+    pub fn sun_direction(&self) -> [f32; 3] {
+        let angle = self.sun_angle();
+        [angle.cos(), angle.sin(), 0.0]
+    }
+
+    /// The moon is always opposite the sun
+    pub fn moon_direction(&self) -> [f32; 3] {
+        self.sun_direction().map(|coord| -coord)
+    }
+
+    /// Intensity of the light coming from the sky, from `MIN_SKY_LIGHT` at night to 1.0 by day.
+    /// The transition happens while the sun is close to the horizon
+    pub fn sky_light(&self) -> f32 {
+        let height = self.sun_direction()[1];
+        let t = ((height + 0.1) / 0.3).clamp(0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        MIN_SKY_LIGHT + (1.0 - MIN_SKY_LIGHT) * t
+    }
+}
+
+/// Runs the simulation ticks at a fixed rate, whatever the frame rate is
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    next_tick: Option<Instant>,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock { next_tick: None }
+    }
+
+    /// Number of ticks due at `now`. After a long pause the missed ticks are dropped
+    /// instead of being run all at once
+    pub fn ticks_due(&mut self, now: Instant) -> u32 {
+        let step = Duration::from_secs(1) / TICKS_PER_SECOND;
+        let next_tick = self.next_tick.get_or_insert(now);
+        let mut count = 0;
+        while *next_tick <= now {
+            *next_tick += step;
+            count += 1;
+            if count == TICKS_PER_SECOND {
+                *next_tick = now + step;
+                break;
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_into_the_next_day() {
+        let mut time = WorldTime { ticks: TICKS_PER_DAY - 1 };
+        assert_eq!((time.day(), time.time_of_day()), (0, TICKS_PER_DAY - 1));
+        time.tick();
+        assert_eq!((time.day(), time.time_of_day()), (1, SUNRISE));
+        time.tick();
+        assert_eq!(time.ticks, TICKS_PER_DAY + 1);
+    }
+
+    #[test]
+    fn sets_the_time_of_the_current_day() {
+        let mut time = WorldTime { ticks: 3 * TICKS_PER_DAY + NOON };
+        time.set_time_of_day(MIDNIGHT);
+        assert_eq!(time.ticks, 3 * TICKS_PER_DAY + MIDNIGHT);
+        // earlier in the day goes back, past the end of the day wraps around
+        time.set_time_of_day(SUNRISE);
+        assert_eq!(time.ticks, 3 * TICKS_PER_DAY);
+        time.set_time_of_day(TICKS_PER_DAY + NOON);
+        assert_eq!(time.ticks, 3 * TICKS_PER_DAY + NOON);
+    }
+
+    #[test]
+    fn lights_the_day_and_darkens_the_night() {
+        let at = |ticks| WorldTime { ticks };
+        assert!((at(NOON).sky_light() - 1.0).abs() < 1e-6);
+        assert!((at(MIDNIGHT).sky_light() - MIN_SKY_LIGHT).abs() < 1e-6);
+        assert!((at(TICKS_PER_DAY + NOON).sky_light() - 1.0).abs() < 1e-6);
+        let light = at(SUNSET).sky_light();
+        assert!(light > MIN_SKY_LIGHT && light < 1.0);
+        assert!(at(NOON).sun_direction()[1] > 0.99);
+        assert!(at(MIDNIGHT).moon_direction()[1] > 0.99);
+    }
+
+    #[test]
+    fn runs_the_ticks_at_a_fixed_rate() {
+        let mut clock = Clock::new();
+        let start = Instant::now();
+        assert_eq!(clock.ticks_due(start), 1);
+        assert_eq!(clock.ticks_due(start + Duration::from_millis(20)), 0);
+        assert_eq!(clock.ticks_due(start + Duration::from_millis(160)), 3);
+        // the ticks missed during a long pause are dropped
+        assert_eq!(clock.ticks_due(start + Duration::from_secs(10)), TICKS_PER_SECOND);
+        assert_eq!(clock.ticks_due(start + Duration::from_secs(10)), 0);
+    }
+}
@@ -0,0 +1,109 @@
+//! How blocks react to changes: neighbour notifications, scheduled ticks and random ticks.
+//! Everything is processed in a deterministic order so a world always evolves the same way
+
+use std::collections::{BTreeMap, HashSet};
+
+use super::block::BlockType;
+use super::map::{Map, CHUNK_SIZE};
+use super::random::Random;
+use super::{falling, fluid, plants};
+
+/// Random ticks given to each chunk at every tick
+pub const RANDOM_TICKS_PER_CHUNK: u32 = 3;
+
+/// The block itself and its 6 neighbours
+pub const NOTIFIED: [[i64; 3]; 7] = [[0, 0, 0], [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+
+/// Ticks before the update of a notified block, None if it does not react to its neighbours
+pub fn delay(map: &Map, coordinates: [i64; 3]) -> Option<u64> {
+    let block = map.get_block(coordinates)?;
+    if block.block_type.has_gravity() {
+        Some(falling::DELAY)
+    } else if block.block_type == BlockType::Wheat {
+        Some(plants::DELAY)
+    } else {
+        fluid::delay(map, coordinates)
+    }
+}
+
+pub struct BlockUpdates {
+    /// By tick when the update is due, then by order of scheduling
+    scheduled: BTreeMap<(u64, u64), [i64; 3]>,
+    /// A block is scheduled at most once at a time
+    pending: HashSet<[i64; 3]>,
+    order: u64,
+    random: Random,
+}
+
+impl BlockUpdates {
+    pub fn new(seed: u64) -> BlockUpdates {
+        BlockUpdates {
+            scheduled: BTreeMap::new(),
+            pending: HashSet::new(),
+            order: 0,
+            random: Random::new(seed),
+        }
+    }
+
+    /// The block will be updated `delay` ticks after `now`, unless it already is scheduled
+    pub fn schedule(&mut self, coordinates: [i64; 3], now: u64, delay: u64) {
+        if !self.pending.insert(coordinates) {
+            return;
+        }
+        self.scheduled.insert((now + delay, self.order), coordinates);
+        self.order += 1;
+    }
+
+    /// Removes and returns the updates due at `now`, in the order they must run
+    pub fn take_due(&mut self, now: u64) -> Vec<[i64; 3]> {
+        let later = self.scheduled.split_off(&(now + 1, 0));
+        let due = std::mem::replace(&mut self.scheduled, later);
+        due.into_values()
+            .inspect(|coordinates| {
+                self.pending.remove(coordinates);
+            })
+            .collect()
+    }
+
+    /// Blocks picked at random in every chunk, for slow processes such as crop growth
+    pub fn random_ticks(&mut self, map: &Map) -> Vec<[i64; 3]> {
+        let mut picked = vec![];
+        for chunk in map.chunks() {
+            let origin = chunk.origin();
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let local: [i64; 3] = std::array::from_fn(|_| self.random.below(CHUNK_SIZE as u64) as i64);
+                picked.push(std::array::from_fn(|i| origin[i] + local[i]));
+            }
+        }
+        picked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::map::ChunkColumn;
+
+    #[test]
+    fn runs_the_updates_of_a_tick_in_the_order_they_were_scheduled() {
+        let mut updates = BlockUpdates::new(0);
+        updates.schedule([3, 0, 0], 0, 10);
+        updates.schedule([1, 0, 0], 5, 5);
+        updates.schedule([2, 0, 0], 9, 1);
+        // already scheduled, it keeps its place
+        updates.schedule([3, 0, 0], 9, 1);
+        updates.schedule([0, 0, 0], 0, 11);
+        assert_eq!(updates.take_due(9), Vec::<[i64; 3]>::new());
+        assert_eq!(updates.take_due(10), [[3, 0, 0], [1, 0, 0], [2, 0, 0]]);
+        assert_eq!(updates.take_due(11), [[0, 0, 0]]);
+    }
+
+    #[test]
+    fn picks_the_same_random_ticks_with_the_same_seed() {
+        let map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        let (mut a, mut b, mut c) = (BlockUpdates::new(7), BlockUpdates::new(7), BlockUpdates::new(8));
+        let picked = a.random_ticks(&map);
+        assert_eq!(picked, b.random_ticks(&map));
+        assert_ne!(picked, c.random_ticks(&map));
+    }
+}
@@ -0,0 +1,189 @@
+//! MagicaVoxel models: chunks of little endian data, the size of the model, its voxels and the
+//! colours of the palette. Only the first model of a file is read. Their z axis is up, our y
+
+use super::block::BlockType;
+use super::edit::Clipboard;
+use super::schematic::{BlockMapping, Import};
+
+const MAGIC: &[u8; 4] = b"VOX ";
+const VERSION: i32 = 150;
+/// Voxels along each axis at most
+const MAX_SIZE: i64 = 256;
+
+/// Blocks without a mapping for their colour are reported as `colour rrggbb`
+pub fn read(bytes: &[u8], mapping: &BlockMapping) -> Result<Import, String> {
+    if bytes.get(..4) != Some(&MAGIC[..]) {
+        return Err("not a MagicaVoxel file".to_string());
+    }
+    let mut reader = Reader { bytes, position: 8 };
+    let (id, content, children) = reader.chunk()?;
+    if id != *b"MAIN" {
+        return Err("missing the MAIN chunk".to_string());
+    }
+    reader.take(content)?;
+    let end = reader.position + children;
+
+    let (mut size, mut voxels, mut palette) = (None, None, None);
+    while reader.position < end {
+        let (id, content, children) = reader.chunk()?;
+        let data = reader.take(content)?;
+        reader.take(children)?;
+        match &id {
+            b"SIZE" if size.is_none() => {
+                let values: Vec<i64> = data.chunks_exact(4).take(3).map(|value| i32::from_le_bytes(value.try_into().unwrap()) as i64).collect();
+                if values.len() != 3 || values.iter().any(|value| !(1..=MAX_SIZE).contains(value)) {
+                    return Err("invalid model size".to_string());
+                }
+                size = Some([values[0], values[1], values[2]]);
+            }
+            b"XYZI" if voxels.is_none() => {
+                let count = data.get(..4).map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+                let voxels_data = count.and_then(|count| data.get(4..4 + count.checked_mul(4)?));
+                voxels = Some(voxels_data.ok_or_else(|| "invalid voxels".to_string())?);
+            }
+            b"RGBA" => {
+                if data.len() < 1024 {
+                    return Err("invalid palette".to_string());
+                }
+                palette = Some(data);
+            }
+            _ => {}
+        }
+    }
+    let size = size.ok_or_else(|| "missing the size of the model".to_string())?;
+    let voxels = voxels.ok_or_else(|| "missing the voxels of the model".to_string())?;
+    // files without a palette use the default palette of MagicaVoxel, which is not known here
+    let palette = palette.ok_or_else(|| "missing the palette, the default one is not supported".to_string())?;
+
+    let mut import = Import::new([size[0], size[2], size[1]]);
+    for voxel in voxels.chunks_exact(4) {
+        let [x, y, z] = [voxel[0], voxel[1], voxel[2]].map(|c| c as i64);
+        if x >= size[0] || y >= size[1] || z >= size[2] {
+            return Err(format!("voxel {} {} {} outside of the model", x, y, z));
+        }
+        // the colour index 0 is not used, index i is the colour i - 1 of the palette
+        let index = (voxel[3] as usize).max(1) - 1;
+        let colour = [palette[index * 4], palette[index * 4 + 1], palette[index * 4 + 2]];
+        let hex = || format!("colour {:02x}{:02x}{:02x}", colour[0], colour[1], colour[2]);
+        import.set([x, z, y], mapping.by_colour(colour), hex);
+    }
+    Ok(import)
+}
+
+/// Empty blocks are left out, and the blocks of the same colour become the same voxel
+pub fn write(clipboard: &Clipboard, mapping: &BlockMapping) -> Result<Vec<u8>, String> {
+    let [width, height, length] = clipboard.size;
+    if clipboard.size.iter().any(|size| *size > MAX_SIZE) {
+        return Err(format!("the clipboard is too big, {}x{}x{}, {} at most", width, height, length, MAX_SIZE));
+    }
+    let mut colours: Vec<[u8; 3]> = vec![];
+    let mut voxels = vec![];
+    for x in 0..width {
+        for y in 0..height {
+            for z in 0..length {
+                let state = clipboard.get([x, y, z]);
+                if state.block_type == BlockType::Void {
+                    continue;
+                }
+                let colour = mapping.colour(state);
+                let index = match colours.iter().position(|other| *other == colour) {
+                    Some(index) => index,
+                    None if colours.len() < 255 => {
+                        colours.push(colour);
+                        colours.len() - 1
+                    }
+                    None => return Err("more than 255 colours".to_string()),
+                };
+                voxels.extend([x as u8, z as u8, y as u8, index as u8 + 1]);
+            }
+        }
+    }
+
+    let mut size = vec![];
+    for value in [width, length, height] {
+        size.extend((value as i32).to_le_bytes());
+    }
+    let mut xyzi = ((voxels.len() / 4) as u32).to_le_bytes().to_vec();
+    xyzi.extend(voxels);
+    let mut rgba = vec![0; 1024];
+    for (i, colour) in colours.iter().enumerate() {
+        rgba[i * 4..i * 4 + 4].copy_from_slice(&[colour[0], colour[1], colour[2], 255]);
+    }
+    let mut children = vec![];
+    for (id, data) in [(b"SIZE", size), (b"XYZI", xyzi), (b"RGBA", rgba)] {
+        write_chunk(&mut children, id, &data, &[]);
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    write_chunk(&mut bytes, b"MAIN", &[], &children);
+    Ok(bytes)
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend(id);
+    bytes.extend((content.len() as u32).to_le_bytes());
+    bytes.extend((children.len() as u32).to_le_bytes());
+    bytes.extend(content);
+    bytes.extend(children);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(length).ok_or_else(|| "invalid length".to_string())?;
+        let slice = self.bytes.get(self.position..end).ok_or_else(|| "unexpected end of the file".to_string())?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    /// Id, size of the content and size of the children
+    fn chunk(&mut self) -> Result<([u8; 4], usize, usize), String> {
+        let header = self.take(12)?;
+        let size = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap()) as usize;
+        Ok((header[..4].try_into().unwrap(), size(4), size(8)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::state::BlockState;
+
+    #[test]
+    fn imports_what_it_exports() {
+        let mapping = BlockMapping::new();
+        let mut clipboard = Clipboard::empty([2, 3, 4]);
+        // only the blocks of different colours keep their type, the properties are lost
+        for (c, state) in [([0, 0, 0], "stone"), ([1, 2, 3], "planks"), ([0, 1, 3], "water"), ([1, 0, 2], "wheat[level=3]")] {
+            clipboard.set(c, BlockState::parse(state).unwrap());
+        }
+        let import = read(&write(&clipboard, &mapping).unwrap(), &mapping).unwrap();
+        assert_eq!(import.clipboard, clipboard);
+        assert!(import.unmapped.is_empty());
+    }
+
+    #[test]
+    fn reports_the_unknown_colours() {
+        let exported = BlockMapping::parse("colour 123456 = stone", std::path::Path::new("mapping.txt")).unwrap();
+        let mut clipboard = Clipboard::empty([2, 1, 1]);
+        clipboard.set([0, 0, 0], BlockState::parse("stone").unwrap());
+        clipboard.set([1, 0, 0], BlockState::parse("stone").unwrap());
+        let import = read(&write(&clipboard, &exported).unwrap(), &BlockMapping::new()).unwrap();
+        assert_eq!(import.unmapped, [("colour 123456".to_string(), 2)]);
+        assert_eq!(import.clipboard, Clipboard::empty([2, 1, 1]));
+    }
+
+    #[test]
+    fn refuses_broken_files() {
+        let mapping = BlockMapping::new();
+        let bytes = write(&Clipboard::empty([1, 1, 1]), &mapping).unwrap();
+        assert!(read(b"NOPE", &mapping).is_err());
+        assert!(read(&bytes[..bytes.len() - 1], &mapping).is_err());
+        assert!(write(&Clipboard::empty([257, 1, 1]), &mapping).is_err());
+    }
+}
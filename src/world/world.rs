@@ -1,246 +1,701 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
 use glium::winit::event::Event;
-use glium::winit::event_loop::ActiveEventLoop;
-use glium::winit::window::Window;
-use glium::{draw_parameters, DrawParameters};
-use glium::{glutin::surface::WindowSurface, Display};
 
-use crate::models::cube;
-use crate::models::object::Object;
 use crate::render::camera::Camera as Camera;
-use crate::glium::Surface;
 use crate::render::quaternion::Quaternion;
 use crate::render::vec3d::Vec3d;
 
-use glium::winit::{event::{DeviceEvent, ElementState, RawKeyEvent}, keyboard::{KeyCode, PhysicalKey}};
+use glium::winit::{event::{DeviceEvent, ElementState, KeyEvent, RawKeyEvent}, keyboard::{KeyCode, PhysicalKey}};
 
-use super::map::Map;
-pub struct World {
-    pub camera: Camera,
-    display: Display<WindowSurface>,
-    window: Window,
-    map: Map
-}
+use crate::models::cube::SIZE;
+use crate::models::object::Object;
+use crate::render::matrix;
+use crate::render::text::Font;
+use crate::render::transform::Transform;
+use crate::settings::Settings;
 
-const VERTEX_SHADER_SRC: &str = r#"
-    #version 140
+use super::falling::{self, Fall, FallingBlock};
+use super::block::BlockType;
+use super::command;
+use super::console::Console;
+use super::edit::WorldEdit;
+use super::fluid;
+use super::generation::Generator;
+use super::graph::{Node, NodeId, SceneGraph};
+use super::map::{Map, CHUNK_SIZE, MIN_Y};
+use super::mesher::MeshCache;
+use super::plants;
+use super::raycast::{raycast, Hit};
+use super::save;
+use super::state::{BlockState, Property};
+use super::time::{Clock, WorldTime};
+use super::updates::{self, BlockUpdates};
 
-    in vec3 position;
+/// Scale of the placed models, the teapot is about 200 units wide
+const MODEL_SCALE: f32 = 0.002;
+const PLACED_COLOURS: [[f32; 4]; 4] = [
+    [1.0, 1.0, 1.0, 1.0],
+    [1.0, 0.8, 0.2, 1.0],
+    [0.2, 0.8, 1.0, 1.0],
+    [0.6, 1.0, 0.4, 1.0],
+];
 
-    uniform mat4 perspective;
-    uniform mat4 view;
-    uniform mat4 model;
+/// Chunks generated around the origin by `generate`
+const GENERATED_RADIUS: i64 = 2;
+/// Blocks between the ground and the camera when it spawns
+const SPAWN_HEIGHT: i64 = 3;
 
-    void main() {
-        mat4 modelview = view * model;
-        gl_Position = perspective * modelview * vec4(position, 1.0);
-    }
-"#;
+/// Columns sampled on each side of the camera for the sky tint, 4 blocks apart
+const SKY_BLEND: i64 = 2;
 
-const RED_FRAGMENT_SHADER_SRC: &str = r#"
-    #version 140
+/// Farthest block that can be placed or used, in blocks
+const REACH: f32 = 8.0;
+const SURVIVAL_REACH: f32 = 4.5;
 
-    out vec4 color;
+/// Name of the player in the chat
+const PLAYER: &str = "Player";
 
-    void main() {
-        color = vec4(1.0, 0.0, 0.0, 1.0);
-    }
-"#;
+/// Step of the field of view keys, in radians
+const FOV_STEP: f32 = 5.0 * std::f32::consts::PI / 180.0;
 
-const GREEN_FRAGMENT_SHADER_SRC: &str = r#"
-    #version 140
+/// What the owner of the window has to do after an event was handled by the world
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Exit,
+    Render,
+    RequestRedraw,
+    Resize((u32, u32)),
+    Screenshot,
+}
 
-    out vec4 color;
+/// What the player can do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    /// Moves around without changing anything
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Survival, GameMode::Creative, GameMode::Spectator];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+            GameMode::Spectator => "spectator",
+        }
+    }
 
-    void main() {
-        color = vec4(0.0, 1.0, 0.0, 1.0);
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        GameMode::ALL.into_iter().find(|mode| mode.name() == name)
     }
-"#;
+
+    /// Farthest block that can be placed or used, None if none can
+    pub fn reach(self) -> Option<f32> {
+        match self {
+            GameMode::Survival => Some(SURVIVAL_REACH),
+            GameMode::Creative => Some(REACH),
+            GameMode::Spectator => None,
+        }
+    }
+}
+
+/// Game state, independent of any window or OpenGL context
+pub struct World {
+    pub camera: Camera,
+    pub map: Map,
+    /// Where the voxel grid is in the world
+    pub grid: Transform,
+    /// Meshes shared by the nodes of the scene graph, their index is their id
+    pub models: Vec<Object>,
+    /// Font of the texts on the screen, nothing is written without it
+    pub font: Option<Font>,
+    pub graph: SceneGraph,
+    pub time: WorldTime,
+    pub settings: Settings,
+    /// Blocks between two cells of the map
+    pub falling: Vec<FallingBlock>,
+    /// Meshes of the chunks, made again when the map changes
+    pub meshes: MeshCache,
+    clock: Clock,
+    updates: BlockUpdates,
+    /// Nodes placed with the P key, the last one is removed first
+    placed: Vec<NodeId>,
+    /// Block placed with the B key
+    pub held: BlockType,
+    pub generator: Generator,
+    /// Clipboard and history of the building commands
+    pub edit: WorldEdit,
+    pub game_mode: GameMode,
+    pub console: Console,
+    /// Whether the renderer can draw with `DepthMode::Reversed`, the R key is refused without it
+    pub reversed_depth: bool,
+    /// Where F5 saves the map, nothing is saved without it
+    pub save_path: Option<PathBuf>,
+    /// Foreign blocks of the schematics mapped to ours, only the default mapping is used without it
+    pub mapping_path: Option<PathBuf>,
+}
 
 impl World {
-    pub fn new(display: Display<WindowSurface>, window: Window) -> World {
-        
+    pub fn new() -> World {
         World {
             camera: Camera::new(),
-            display: display,
-            window: window,
             map: Map::new(),
+            grid: Transform::from_translation([0.0, 0.0, 2.0]).with_scale(0.01),
+            models: vec![],
+            font: None,
+            graph: SceneGraph::new(),
+            time: WorldTime::new(),
+            settings: Settings::new(),
+            falling: vec![],
+            meshes: MeshCache::new(),
+            clock: Clock::new(),
+            updates: BlockUpdates::new(0),
+            placed: vec![],
+            held: BlockType::Log,
+            generator: Generator::new(0),
+            edit: WorldEdit::new(),
+            game_mode: GameMode::Creative,
+            console: Console::new(),
+            reversed_depth: true,
+            save_path: None,
+            mapping_path: None,
         }
     }
 
-    pub fn render(&self) {
-        let mut target = self.display.draw();
-
-        target.clear_color_and_depth((0.0, 0.0, 1.0, 1.0), 1.0);
-
-        let perspective = {
-            let (width, height) = target.get_dimensions();
-            let aspect_ratio = height as f32 / width as f32;
-    
-            let fov: f32 = 3.141592 / 3.0;
-            let zfar = 1024.0;
-            let znear = 0.1;
-    
-            let f = 1.0 / (fov / 2.0).tan();
-    
-            [
-                [f *   aspect_ratio   ,    0.0,              0.0              ,   0.0],
-                [         0.0         ,     f ,              0.0              ,   0.0],
-                [         0.0         ,    0.0,  (zfar+znear)/(zfar-znear)    ,   1.0],
-                [         0.0         ,    0.0, -(2.0*zfar*znear)/(zfar-znear),   0.0],
-            ]
+    /// Returns the id of the model
+    pub fn add_model(&mut self, object: Object) -> usize {
+        self.models.push(object);
+        self.models.len() - 1
+    }
+
+    /// Adds a node showing the model, `position` is relative to the parent
+    pub fn place(&mut self, model: usize, position: [f32; 3], parent: Option<NodeId>) -> NodeId {
+        let colour = PLACED_COLOURS[self.graph.len() % PLACED_COLOURS.len()];
+        let node = Node::new(Transform::from_translation(position).with_scale(MODEL_SCALE))
+            .with_model(model, colour);
+        self.graph.add(node, parent)
+    }
+
+    /// Replaces the map by a new one generated from the seed, `GENERATED_RADIUS` chunks around the origin.
+    /// The random ticks follow the same seed
+    pub fn generate(&mut self, seed: u64) {
+        self.map = Map::from_columns(vec![]);
+        self.generator = Generator::new(seed);
+        self.updates = BlockUpdates::new(seed);
+        self.generator.generate_area(&mut self.map, GENERATED_RADIUS);
+    }
+
+    /// Écrit la carte avec la graine et l'heure du monde
+    pub fn save(&self, path: &Path) -> Result<(), save::SaveError> {
+        save::save(&self.map, self.generator.seed, self.time.ticks, path)
+    }
+
+    /// Reprend la carte, la graine et l'heure d'une sauvegarde
+    pub fn load(&mut self, path: &Path) -> Result<(), save::SaveError> {
+        let saved = save::load(path)?;
+        self.map = saved.map;
+        self.generator = Generator::new(saved.seed);
+        self.updates = BlockUpdates::new(saved.seed);
+        self.time.ticks = saved.ticks;
+        Ok(())
+    }
+
+    /// Render distance of the settings, in world units
+    pub fn render_distance(&self) -> f32 {
+        let chunk_size = CHUNK_SIZE as f32 * SIZE * 2.0 * self.grid.scale[0];
+        self.settings.render_distance as f32 * chunk_size
+    }
+
+    /// Moves the camera above the ground at the origin
+    pub fn spawn(&mut self) {
+        let ground = self.map.height(0, 0).unwrap_or(MIN_Y);
+        self.teleport([0.0, (ground + SPAWN_HEIGHT) as f32, 0.0]);
+    }
+
+    /// Moves the camera to a position in blocks, see `camera_block`
+    pub fn teleport(&mut self, block: [f32; 3]) {
+        let [x, y, z] = block.map(|c| c * SIZE * 2.0);
+        let [x, y, z, _] = matrix::transform(&self.grid.matrix(), [x, y, z, 1.0]);
+        self.camera.position = [x, y, z];
+    }
+
+    /// Position of the camera in blocks, with the centers of the blocks on integer coordinates
+    pub fn camera_block(&self) -> [f32; 3] {
+        let [x, y, z, _] = matrix::transform(&self.grid.inverse_matrix(), [self.camera.position[0], self.camera.position[1], self.camera.position[2], 1.0]);
+        [x, y, z].map(|c| c / (SIZE * 2.0))
+    }
+
+    /// Block the camera looks at, within the reach of the game mode
+    pub fn target(&self) -> Option<Hit> {
+        raycast(&self.map, self.camera_block(), self.look(), self.game_mode.reach()?)
+    }
+
+    /// Runs the line as a command if it starts with `/`, else says it in the chat
+    pub fn send(&mut self, line: &str) {
+        let message = if line.starts_with('/') {
+            command::execute(self, line).unwrap_or_else(|error| error)
+        } else {
+            format!("<{}> {}", PLAYER, line)
         };
+        self.console.print(message);
+    }
 
-        let params = glium::DrawParameters {
-            depth: glium::Depth {
-                test: glium::draw_parameters::DepthTest::IfLess,
-                write: true,
-                .. Default::default()
-            },
-            .. Default::default()
+    /// Mean of the sky tints of the biomes around the camera, so it changes smoothly
+    /// from one biome to the next
+    pub fn sky_tint(&self) -> [f32; 3] {
+        let [x, _, z] = self.camera_block().map(|c| c.round() as i64);
+        let mut total = [0.0; 3];
+        let mut count = 0.0;
+        for dx in -SKY_BLEND..=SKY_BLEND {
+            for dz in -SKY_BLEND..=SKY_BLEND {
+                if let Some(biome) = self.map.biome(x + dx * 4, z + dz * 4) {
+                    let tint = biome.sky_tint();
+                    total = std::array::from_fn(|i| total[i] + tint[i]);
+                    count += 1.0;
+                }
+            }
+        }
+        if count == 0.0 {
+            return [1.0; 3];
+        }
+        total.map(|c| c / count)
+    }
+
+    /// Places a block against the targeted one, oriented by the face and where the camera looks.
+    /// Returns where it was placed
+    pub fn place_block(&mut self, block_type: BlockType) -> Option<[i64; 3]> {
+        let hit = self.target()?;
+        let cell = hit.adjacent();
+        if !self.map.get_block(cell)?.block_type.is_replaceable() {
+            return None;
+        }
+        self.map.set_state(cell, BlockState::placed(block_type, hit.face, self.look())).ok()?;
+        Some(cell)
+    }
+
+    /// Opens or closes the targeted block, returns its new state
+    pub fn use_block(&mut self) -> Option<BlockState> {
+        let hit = self.target()?;
+        let state = self.map.get_block(hit.block)?.state();
+        let open = state.get(Property::Open)?;
+        let state = state.with(Property::Open, 1 - open);
+        self.map.set_state(hit.block, state).ok()?;
+        Some(state)
+    }
+
+    fn look(&self) -> [f32; 3] {
+        [self.camera.direction.x, self.camera.direction.y, self.camera.direction.z]
+    }
+
+    /// Scheduled update of a block, the changes it makes notify the neighbours at the next tick
+    fn update_block(&mut self, coordinates: [i64; 3]) {
+        let Some(block) = self.map.get_block(coordinates) else {
+            return;
         };
+        if block.block_type.has_gravity() {
+            self.falling.extend(falling::update(&mut self.map, coordinates));
+        } else if block.block_type == BlockType::Wheat {
+            plants::update(&mut self.map, coordinates);
+        } else {
+            fluid::update(&mut self.map, coordinates);
+        }
+    }
 
-        let model = [
-            [0.01, 0.0, 0.0, 0.0],
-            [0.0, 0.01, 0.0, 0.0],
-            [0.0, 0.0, 0.01, 0.0],
-            [0.0, 0.0, 2.0, 1.0f32]
-        ];
-
-        let red_program = glium::Program::from_source(&self.display, VERTEX_SHADER_SRC, RED_FRAGMENT_SHADER_SRC, None).unwrap();
-        let green_program = glium::Program::from_source(&self.display, VERTEX_SHADER_SRC, GREEN_FRAGMENT_SHADER_SRC, None).unwrap();
-
-        for chunk in &self.map.chunks {
-            let to_show = chunk.get_show();
-            for obj in to_show {
-                let positions = glium::VertexBuffer::new(&self.display, &obj.vertices).unwrap();
-                let indices = glium::IndexBuffer::new(&self.display, glium::index::PrimitiveType::TrianglesList,
-                                                    &obj.indices).unwrap();
-                
-                target.draw(&positions, &indices, &red_program, 
-                    &uniform! { model: model, view: self.camera.render(), perspective: perspective },
-                &params).unwrap();
-
-                let params_wireframe = DrawParameters {
-                    depth: glium::Depth {
-                        test: glium::draw_parameters::DepthTest::IfLess,
-                        write: true,
-                        .. Default::default()
-                    },
-                    polygon_mode: draw_parameters::PolygonMode::Line,  // Mode wireframe pour dessiner les bords
-                    line_width: Some(1.0),
-                    ..Default::default()
-                };
+    /// One step of the simulation
+    pub fn tick(&mut self) {
+        self.time.tick();
+        self.console.tick();
+        let now = self.time.ticks;
 
-                target.draw(&positions, &indices, &green_program, 
-                    &uniform! { model: model, view: self.camera.render(), perspective: perspective },
-                &params_wireframe).unwrap();
+        // the changed blocks notify themselves and their neighbours
+        for changed in self.map.take_changes() {
+            for offset in updates::NOTIFIED {
+                let coordinates: [i64; 3] = std::array::from_fn(|i| changed[i] + offset[i]);
+                if let Some(delay) = updates::delay(&self.map, coordinates) {
+                    self.updates.schedule(coordinates, now, delay);
+                }
             }
         }
+        for coordinates in self.updates.take_due(now) {
+            self.update_block(coordinates);
+        }
+        for coordinates in self.updates.random_ticks(&self.map) {
+            plants::random_tick(&mut self.map, coordinates);
+        }
 
-        target.finish().unwrap();
-
+        let map = &mut self.map;
+        self.falling.retain_mut(|block| match block.tick(map) {
+            Fall::Falling => true,
+            Fall::Landed(cell) => {
+                falling::land(map, block.block_type, cell);
+                false
+            }
+            Fall::OutOfWorld => false,
+        });
     }
 
-    pub fn run(&mut self, ev: Event<()>, window_target: &ActiveEventLoop) {
+    pub fn run(&mut self, ev: Event<()>) -> Option<Action> {
         match ev {
             glium::winit::event::Event::WindowEvent { event, .. } => match event {
                 glium::winit::event::WindowEvent::CloseRequested => {
-                    window_target.exit();
+                    Some(Action::Exit)
                 },
                 // We now need to render everyting in response to a RedrawRequested event due to the animation
                 glium::winit::event::WindowEvent::RedrawRequested => {
-                    self.render();
+                    Some(Action::Render)
                 },
                 glium::winit::event::WindowEvent::Resized(window_size) => {
-                    self.display.resize(window_size.into());
+                    Some(Action::Resize(window_size.into()))
+                },
+                glium::winit::event::WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                    self.console_key(&event);
+                    None
                 },
-                _ => (),
+                _ => None,
             },
             // keyboard
             glium::winit::event::Event::DeviceEvent { event, .. } => match event {
-                DeviceEvent::Key(RawKeyEvent { physical_key, state }) if state == ElementState::Pressed => {
-                    println!("Physical key: {:?} State: {:?}", physical_key, state);
-                    let direction = Vec3d::new(self.camera.direction.x, self.camera.direction.y, self.camera.direction.z).normalize();
-                    let up = Vec3d::new(0.0, 1.0, 0.0);
-                    let right = -direction.cross(up).normalize();
-                    match physical_key {
-                        PhysicalKey::Code(KeyCode::Escape) => {
-                            window_target.exit();
-                        }
-                        // QWE
-                        // ASD
-                        // W - Avancer
-                        PhysicalKey::Code(KeyCode::KeyW) => {
-                            self.camera.deplace(direction);
-                        }
-                        // S - Reculer
-                        PhysicalKey::Code(KeyCode::KeyS) => {
-                            self.camera.deplace(-direction);
-                        }
-                        // A - Aller à gauche
-                        PhysicalKey::Code(KeyCode::KeyA) => {
-                            self.camera.deplace(-right);
-                        }
-                        // D - Aller à droite
-                        PhysicalKey::Code(KeyCode::KeyD) => {
-                            self.camera.deplace(right);
-                        }
-                        // E - Monter
-                        PhysicalKey::Code(KeyCode::KeyE) => {
-                            self.camera.deplace(up);
-                        }
-                        // Q - Descendre
-                        PhysicalKey::Code(KeyCode::KeyQ) => {
-                            self.camera.deplace(-up);
-                        }
-                        //  ^
-                        // <_>
-                        // rotation
-                        PhysicalKey::Code(KeyCode::ArrowDown) => {
-                            // Pitch down
-                            let dir = Vec3d::new(self.camera.direction.x, self.camera.direction.y, self.camera.direction.z).normalize();
-                            let right = Vec3d::new(dir.z, 0.0, -dir.x).normalize(); // axe horizontal
-                            let angle = 0.1;
-                            let rotation = Quaternion::from_rotation(right, angle);
-                            self.camera.direction = (rotation * self.camera.direction).normalize();
-                        }
-                        PhysicalKey::Code(KeyCode::ArrowUp) => {
-                            // Pitch up
-                            let dir = Vec3d::new(self.camera.direction.x, self.camera.direction.y, self.camera.direction.z).normalize();
-                            let right = Vec3d::new(dir.z, 0.0, -dir.x).normalize(); // axe horizontal
-                            let angle = -0.1;
-                            let rotation = Quaternion::from_rotation(right, angle);
-                            self.camera.direction = (rotation * self.camera.direction).normalize();
-                        }
-                        PhysicalKey::Code(KeyCode::ArrowLeft) => {
-                            // Yaw left
-                            let axis = Vec3d::new(0.0, 1.0, 0.0); // axe vertical
-                            let angle = -0.1;
-                            let rotation = Quaternion::from_rotation(axis, angle);
-                            self.camera.direction = (rotation * self.camera.direction).normalize();
-                        }
-                        PhysicalKey::Code(KeyCode::ArrowRight) => {
-                            // Yaw right
-                            let axis = Vec3d::new(0.0, 1.0, 0.0); // axe vertical
-                            let angle = 0.1;
-                            let rotation = Quaternion::from_rotation(axis, angle);
-                            self.camera.direction = (rotation * self.camera.direction).normalize();
-                        }
-                        // other
-                        PhysicalKey::Code(unknown) => {
-                            println!("Unknown key: {:?}", unknown);
-                        }
-                        PhysicalKey::Unidentified(_) => {
-                            println!("Unidentified key");
-                        }
+                // the keys typed in the console do not move the camera, Escape only closes it
+                DeviceEvent::Key(RawKeyEvent { physical_key, state: ElementState::Pressed }) if self.console.open => {
+                    if physical_key == PhysicalKey::Code(KeyCode::Escape) {
+                        self.console.close();
                     }
+                    None
+                },
+                DeviceEvent::Key(RawKeyEvent { physical_key, state: ElementState::Pressed }) => {
+                    self.key_pressed(physical_key)
+                },
+                DeviceEvent::Key(RawKeyEvent { physical_key, state: ElementState::Released }) => {
+                    self.key_released(physical_key)
                 },
-                _ => ()
+                _ => None
             },
             glium::winit::event::Event::AboutToWait => {
-                self.window.request_redraw();
+                for _ in 0..self.clock.ticks_due(Instant::now()) {
+                    self.tick();
+                }
+                Some(Action::RequestRedraw)
             },
-            _ => (),
+            _ => None,
         }
     }
-}
\ No newline at end of file
+
+    /// Text of the console, the other keys are handled by `key_pressed`
+    fn console_key(&mut self, event: &KeyEvent) {
+        if !self.console.open {
+            match event.physical_key {
+                PhysicalKey::Code(KeyCode::KeyT) => self.console.open(""),
+                PhysicalKey::Code(KeyCode::Slash) => self.console.open("/"),
+                _ => {}
+            }
+            return;
+        }
+        match event.physical_key {
+            // closed with the raw key event, so it does not also exit
+            PhysicalKey::Code(KeyCode::Escape) => {}
+            PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => {
+                if let Some(line) = self.console.submit() {
+                    self.send(&line);
+                }
+            }
+            PhysicalKey::Code(KeyCode::Backspace) => self.console.backspace(),
+            PhysicalKey::Code(KeyCode::Tab) => self.console.complete(),
+            PhysicalKey::Code(KeyCode::ArrowUp) => self.console.previous(),
+            PhysicalKey::Code(KeyCode::ArrowDown) => self.console.next(),
+            _ => {
+                if let Some(text) = &event.text {
+                    self.console.type_text(text);
+                }
+            }
+        }
+    }
+
+    fn key_pressed(&mut self, physical_key: PhysicalKey) -> Option<Action> {
+        let direction = Vec3d::new(self.camera.direction.x, self.camera.direction.y, self.camera.direction.z).normalize();
+        let up = Vec3d::new(0.0, 1.0, 0.0);
+        let right = -direction.cross(up).normalize();
+        match physical_key {
+            PhysicalKey::Code(KeyCode::Escape) => {
+                return Some(Action::Exit);
+            }
+            // F2 - Capture d'écran
+            PhysicalKey::Code(KeyCode::F2) => {
+                return Some(Action::Screenshot);
+            }
+            // QWE
+            // ASD
+            // W - Avancer
+            PhysicalKey::Code(KeyCode::KeyW) => {
+                self.camera.deplace(direction);
+            }
+            // S - Reculer
+            PhysicalKey::Code(KeyCode::KeyS) => {
+                self.camera.deplace(-direction);
+            }
+            // A - Aller à gauche
+            PhysicalKey::Code(KeyCode::KeyA) => {
+                self.camera.deplace(-right);
+            }
+            // D - Aller à droite
+            PhysicalKey::Code(KeyCode::KeyD) => {
+                self.camera.deplace(right);
+            }
+            // E - Monter
+            PhysicalKey::Code(KeyCode::KeyE) => {
+                self.camera.deplace(up);
+            }
+            // Q - Descendre
+            PhysicalKey::Code(KeyCode::KeyQ) => {
+                self.camera.deplace(-up);
+            }
+            // P - Poser le premier modèle devant la caméra
+            PhysicalKey::Code(KeyCode::KeyP) if !self.models.is_empty() => {
+                let position = [
+                    self.camera.position[0] + direction.x,
+                    self.camera.position[1] + direction.y,
+                    self.camera.position[2] + direction.z,
+                ];
+                let node = self.place(0, position, None);
+                // tourné vers la caméra, autour de l'axe vertical
+                let yaw = direction.x.atan2(direction.z);
+                let prop = self.graph.get_mut(node).unwrap();
+                prop.transform = prop.transform.with_rotation(Quaternion::from_rotation(Vec3d::new(0.0, 1.0, 0.0), yaw));
+                self.placed.push(node);
+            }
+            // B - Poser le bloc tenu, N - Changer de bloc, V - Ouvrir ou fermer
+            PhysicalKey::Code(KeyCode::KeyB) => {
+                self.place_block(self.held);
+            }
+            PhysicalKey::Code(KeyCode::KeyN) => {
+                let placeable: Vec<BlockType> = BlockType::ALL.into_iter().filter(|block_type| *block_type != BlockType::Void).collect();
+                let current = placeable.iter().position(|block_type| *block_type == self.held).unwrap_or(0);
+                // montré par le HUD
+                self.held = placeable[(current + 1) % placeable.len()];
+            }
+            PhysicalKey::Code(KeyCode::KeyV) => {
+                if let Some(state) = self.use_block() {
+                    self.console.print(format!("Now {}", state));
+                }
+            }
+            // F5 - Sauvegarder la carte
+            PhysicalKey::Code(KeyCode::F5) => {
+                let Some(path) = self.save_path.clone() else {
+                    self.console.print("No file to save the map to".to_string());
+                    return None;
+                };
+                match self.save(&path) {
+                    Ok(()) => self.console.print(format!("Map saved to {}", path.display())),
+                    Err(err) => self.console.print(format!("Could not save the map: {}", err)),
+                }
+            }
+            // Retour arrière - Enlever le dernier modèle posé
+            PhysicalKey::Code(KeyCode::Backspace) => {
+                if let Some(node) = self.placed.pop() {
+                    self.graph.remove(node);
+                }
+            }
+            // + / - Champ de vision
+            PhysicalKey::Code(KeyCode::Equal | KeyCode::NumpadAdd) => {
+                let fov = self.camera.projection.fov + FOV_STEP;
+                self.camera.projection.set_fov(fov);
+            }
+            PhysicalKey::Code(KeyCode::Minus | KeyCode::NumpadSubtract) => {
+                let fov = self.camera.projection.fov - FOV_STEP;
+                self.camera.projection.set_fov(fov);
+            }
+            // C - Zoom tant que la touche est enfoncée
+            PhysicalKey::Code(KeyCode::KeyC) => {
+                self.camera.projection.zoomed = true;
+            }
+            // O - Vue orthographique
+            PhysicalKey::Code(KeyCode::KeyO) => {
+                self.camera.projection.toggle_mode();
+            }
+            // R - Profondeur inversée
+            PhysicalKey::Code(KeyCode::KeyR) => {
+                if self.reversed_depth {
+                    self.camera.projection.toggle_depth();
+                } else {
+                    self.console.print("Reversed depth needs OpenGL 4.5 or ARB_clip_control".to_string());
+                }
+            }
+            //  ^
+            // <_>
+            // rotation
+            PhysicalKey::Code(KeyCode::ArrowDown) => {
+                // Pitch down
+                let dir = Vec3d::new(self.camera.direction.x, self.camera.direction.y, self.camera.direction.z).normalize();
+                let right = Vec3d::new(dir.z, 0.0, -dir.x).normalize(); // axe horizontal
+                let angle = 0.1;
+                let rotation = Quaternion::from_rotation(right, angle);
+                self.camera.direction = (rotation * self.camera.direction).normalize();
+            }
+            PhysicalKey::Code(KeyCode::ArrowUp) => {
+                // Pitch up
+                let dir = Vec3d::new(self.camera.direction.x, self.camera.direction.y, self.camera.direction.z).normalize();
+                let right = Vec3d::new(dir.z, 0.0, -dir.x).normalize(); // axe horizontal
+                let angle = -0.1;
+                let rotation = Quaternion::from_rotation(right, angle);
+                self.camera.direction = (rotation * self.camera.direction).normalize();
+            }
+            PhysicalKey::Code(KeyCode::ArrowLeft) => {
+                // Yaw left
+                let axis = Vec3d::new(0.0, 1.0, 0.0); // axe vertical
+                let angle = -0.1;
+                let rotation = Quaternion::from_rotation(axis, angle);
+                self.camera.direction = (rotation * self.camera.direction).normalize();
+            }
+            PhysicalKey::Code(KeyCode::ArrowRight) => {
+                // Yaw right
+                let axis = Vec3d::new(0.0, 1.0, 0.0); // axe vertical
+                let angle = 0.1;
+                let rotation = Quaternion::from_rotation(axis, angle);
+                self.camera.direction = (rotation * self.camera.direction).normalize();
+            }
+            // the other keys do nothing
+            PhysicalKey::Code(_) | PhysicalKey::Unidentified(_) => {}
+        }
+        None
+    }
+
+    fn key_released(&mut self, physical_key: PhysicalKey) -> Option<Action> {
+        if physical_key == PhysicalKey::Code(KeyCode::KeyC) {
+            self.camera.projection.zoomed = false;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::projection::DepthMode;
+    use crate::world::map::ChunkColumn;
+
+    /// A floor of stone at y = 10, the camera 4 blocks above it looking down
+    fn world() -> World {
+        let mut world = World::new();
+        world.map = Map::from_columns(vec![ChunkColumn::new([0, 0])]);
+        for x in 0..16 {
+            for z in 0..16 {
+                world.map.set_block([x, 10, z], BlockType::Stone).unwrap();
+            }
+        }
+        world.teleport([5.0, 14.0, 5.0]);
+        world.camera.direction = Vec3d::new(0.0, -1.0, 0.0).to_quaternion();
+        world
+    }
+
+    #[test]
+    fn places_blocks_on_the_target() {
+        let mut world = world();
+        assert_eq!(world.place_block(BlockType::Planks), Some([5, 11, 5]));
+        assert_eq!(world.map.get_block([5, 11, 5]).unwrap().block_type, BlockType::Planks);
+        assert_eq!(world.place_block(BlockType::Planks), Some([5, 12, 5]));
+        world.game_mode = GameMode::Spectator;
+        assert_eq!(world.place_block(BlockType::Planks), None);
+    }
+
+    #[test]
+    fn keeps_the_seed_and_the_time_in_the_saves() {
+        let mut world = world();
+        world.generator = Generator::new(7);
+        world.time.ticks = 1234;
+        let path = std::env::temp_dir().join(format!("cubegame-test-{}.save", std::process::id()));
+        world.save(&path).unwrap();
+        let mut loaded = World::new();
+        let result = loaded.load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        assert_eq!(loaded.generator.seed, 7);
+        assert_eq!(loaded.time.ticks, 1234);
+        assert_eq!(loaded.map.get_block([5, 10, 5]).unwrap().block_type, BlockType::Stone);
+    }
+
+    #[test]
+    fn opens_and_closes_blocks() {
+        let mut world = world();
+        assert_eq!(world.use_block(), None);
+        world.map.set_block([5, 11, 5], BlockType::Trapdoor).unwrap();
+        assert_eq!(world.use_block().and_then(|state| state.get(Property::Open)), Some(1));
+        assert_eq!(world.use_block().and_then(|state| state.get(Property::Open)), Some(0));
+    }
+
+    fn key(code: KeyCode, state: ElementState) -> Event<()> {
+        let event = DeviceEvent::Key(RawKeyEvent { physical_key: PhysicalKey::Code(code), state });
+        Event::DeviceEvent { device_id: glium::winit::event::DeviceId::dummy(), event }
+    }
+
+    fn window(event: glium::winit::event::WindowEvent) -> Event<()> {
+        Event::WindowEvent { window_id: glium::winit::window::WindowId::dummy(), event }
+    }
+
+    #[test]
+    fn handles_the_window_events() {
+        use glium::winit::dpi::PhysicalSize;
+        use glium::winit::event::WindowEvent;
+
+        let mut world = world();
+        assert_eq!(world.run(window(WindowEvent::CloseRequested)), Some(Action::Exit));
+        assert_eq!(world.run(window(WindowEvent::RedrawRequested)), Some(Action::Render));
+        assert_eq!(world.run(window(WindowEvent::Resized(PhysicalSize::new(640, 480)))), Some(Action::Resize((640, 480))));
+        assert_eq!(world.run(Event::AboutToWait), Some(Action::RequestRedraw));
+    }
+
+    #[test]
+    fn handles_the_keys() {
+        let mut world = world();
+        assert_eq!(world.run(key(KeyCode::F2, ElementState::Pressed)), Some(Action::Screenshot));
+        assert_eq!(world.run(key(KeyCode::F2, ElementState::Released)), None);
+        assert_eq!(world.run(key(KeyCode::Escape, ElementState::Pressed)), Some(Action::Exit));
+
+        // the camera looks down, forward is down
+        let position = world.camera.position;
+        assert_eq!(world.run(key(KeyCode::KeyW, ElementState::Pressed)), None);
+        assert!(world.camera.position[1] < position[1]);
+        assert_eq!([world.camera.position[0], world.camera.position[2]], [position[0], position[2]]);
+
+        // zoomed while the key is held
+        world.run(key(KeyCode::KeyC, ElementState::Pressed));
+        assert!(world.camera.projection.zoomed);
+        world.run(key(KeyCode::KeyC, ElementState::Released));
+        assert!(!world.camera.projection.zoomed);
+
+        world.run(key(KeyCode::KeyR, ElementState::Pressed));
+        assert_eq!(world.camera.projection.depth, DepthMode::Reversed);
+        world.run(key(KeyCode::KeyR, ElementState::Pressed));
+        // without clip control the depth stays standard
+        world.reversed_depth = false;
+        world.run(key(KeyCode::KeyR, ElementState::Pressed));
+        assert_eq!(world.camera.projection.depth, DepthMode::Standard);
+
+        let path = std::env::temp_dir().join(format!("cubegame-test-{}-f5.save", std::process::id()));
+        world.save_path = Some(path.clone());
+        world.run(key(KeyCode::F5, ElementState::Pressed));
+        let saved = path.exists();
+        let _ = std::fs::remove_file(&path);
+        assert!(saved);
+    }
+
+    #[test]
+    fn ignores_the_mouse_and_the_keys_typed_in_the_console() {
+        let mut world = world();
+        let (position, direction) = (world.camera.position, world.camera.direction);
+        let motion = DeviceEvent::MouseMotion { delta: (10.0, -4.0) };
+        assert_eq!(world.run(Event::DeviceEvent { device_id: glium::winit::event::DeviceId::dummy(), event: motion }), None);
+        assert_eq!(world.look(), [direction.x, direction.y, direction.z]);
+
+        world.console.open("");
+        assert_eq!(world.run(key(KeyCode::KeyW, ElementState::Pressed)), None);
+        assert_eq!(world.run(key(KeyCode::F2, ElementState::Pressed)), None);
+        assert_eq!(world.camera.position, position);
+        // Escape closes the console instead of the window
+        assert_eq!(world.run(key(KeyCode::Escape, ElementState::Pressed)), None);
+        assert!(!world.console.open);
+    }
+}